@@ -0,0 +1,49 @@
+-- env_parents stores which environments an environment inherits from. Parents
+-- are resolved in `position` order: variables found in earlier parents take
+-- precedence over the ones found in later parents.
+CREATE TABLE IF NOT EXISTS env_parents (
+    env VARCHAR(50) NOT NULL,
+    parent VARCHAR(50) NOT NULL,
+    position INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    PRIMARY KEY(env, parent)
+);
+
+-- a tombstone is a NULL value that explicitly unsets a variable inherited from
+-- a parent environment, as opposed to a plain soft delete which lets the
+-- parent value show through
+ALTER TABLE environments ADD COLUMN tombstone INTEGER NOT NULL DEFAULT 0;
+
+DROP VIEW IF EXISTS latest_envs;
+DROP VIEW IF EXISTS active_envs;
+
+-- latest_envs returns all the latest environments key-value pairs, including
+-- the not active (null) values
+CREATE VIEW IF NOT EXISTS latest_envs AS
+SELECT
+    env
+    , key
+    , value
+    , tombstone
+    , created_at
+FROM (
+    SELECT
+        *
+        -- rows written within the same second are ordered by insertion
+        , ROW_NUMBER() OVER (PARTITION BY env, key ORDER BY created_at DESC, rowid DESC) AS rn
+    FROM environments
+)
+WHERE
+    rn = 1;
+
+-- active_envs returns all the active (non-null) latest environments key-value
+-- pairs
+CREATE VIEW IF NOT EXISTS active_envs AS
+SELECT
+    env
+    , key
+    , value
+    , created_at
+FROM latest_envs
+WHERE
+    value IS NOT NULL;
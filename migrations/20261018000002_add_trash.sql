@@ -0,0 +1,27 @@
+-- trash keeps dropped environments until they are restored or permanently
+-- deleted. The same environment can be dropped more than once, each drop is a
+-- separate entry.
+CREATE TABLE IF NOT EXISTS trash (
+    id INTEGER PRIMARY KEY,
+    env VARCHAR(50) NOT NULL,
+    dropped_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+-- trash_environments holds the whole history of the dropped environments.
+-- `seq` is the original rowid, so that rows are restored in insertion order.
+CREATE TABLE IF NOT EXISTS trash_environments (
+    trash_id INTEGER NOT NULL REFERENCES trash(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT,
+    tombstone INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+-- trash_parents holds the parents the dropped environments inherited from
+CREATE TABLE IF NOT EXISTS trash_parents (
+    trash_id INTEGER NOT NULL REFERENCES trash(id) ON DELETE CASCADE,
+    parent VARCHAR(50) NOT NULL,
+    position INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
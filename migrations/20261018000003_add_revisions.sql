@@ -0,0 +1,66 @@
+-- revision is a global, monotonically increasing number identifying every
+-- value written to the database. It orders the history of variables instead
+-- of created_at, which used to have a resolution of one second: writing the
+-- same value twice within a second collided, and writes within the same
+-- second were ordered ambiguously. AUTOINCREMENT guarantees that revisions of
+-- deleted rows are never reused. created_at is kept for point in time reads,
+-- now with sub-second precision.
+DROP VIEW IF EXISTS active_envs;
+DROP VIEW IF EXISTS latest_envs;
+
+CREATE TABLE environments_new (
+    revision INTEGER PRIMARY KEY AUTOINCREMENT,
+    env VARCHAR(50) NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT,
+    tombstone INTEGER NOT NULL DEFAULT 0,
+    created_at REAL NOT NULL DEFAULT (unixepoch('subsec'))
+);
+
+-- existing rows are numbered following the order they had so far
+INSERT INTO environments_new (revision, env, key, value, tombstone, created_at)
+SELECT
+    ROW_NUMBER() OVER (ORDER BY created_at, rowid)
+    , env
+    , key
+    , value
+    , tombstone
+    , created_at
+FROM environments;
+
+DROP TABLE environments;
+ALTER TABLE environments_new RENAME TO environments;
+
+CREATE INDEX IF NOT EXISTS environments_env_key ON environments (env, key, revision);
+
+-- latest_envs returns all the latest environments key-value pairs, including
+-- the not active (null) values
+CREATE VIEW IF NOT EXISTS latest_envs AS
+SELECT
+    revision
+    , env
+    , key
+    , value
+    , tombstone
+    , created_at
+FROM (
+    SELECT
+        *
+        , ROW_NUMBER() OVER (PARTITION BY env, key ORDER BY revision DESC) AS rn
+    FROM environments
+)
+WHERE
+    rn = 1;
+
+-- active_envs returns all the active (non-null) latest environments key-value
+-- pairs
+CREATE VIEW IF NOT EXISTS active_envs AS
+SELECT
+    revision
+    , env
+    , key
+    , value
+    , created_at
+FROM latest_envs
+WHERE
+    value IS NOT NULL;
@@ -0,0 +1,22 @@
+-- changesets group the values written together by a single command, along
+-- with an optional message describing why they changed. Values written
+-- before changesets existed do not belong to any.
+CREATE TABLE IF NOT EXISTS changesets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message TEXT,
+    created_at REAL NOT NULL DEFAULT (unixepoch('subsec'))
+);
+
+ALTER TABLE environments ADD COLUMN changeset INTEGER REFERENCES changesets(id);
+ALTER TABLE trash_environments ADD COLUMN changeset INTEGER;
+
+-- staged holds the changes waiting to be committed as a single changeset.
+-- Rows have the same meaning as in environments and are applied in `seq`
+-- order.
+CREATE TABLE IF NOT EXISTS staged (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    env VARCHAR(50) NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT,
+    tombstone INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,9 @@
+-- retention holds how much of the history of an environment is kept by gc.
+-- Revisions beyond the latest `keep_revisions` of a variable, or older than
+-- `keep_newer_than` seconds, are pruned. The latest value of a variable is
+-- always kept.
+CREATE TABLE IF NOT EXISTS retention (
+    env VARCHAR(50) PRIMARY KEY,
+    keep_revisions INTEGER,
+    keep_newer_than INTEGER
+);
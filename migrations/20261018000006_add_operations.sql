@@ -0,0 +1,281 @@
+-- operations record every command changing the envelope, so that its whole
+-- effect can be undone and redone. While a command runs, its operation is the
+-- single row of `recording`, and the triggers below log into `undolog` the
+-- statements reverting every change made to the tracked tables, following
+-- <https://www.sqlite.org/undoredo.html>. Undoing an operation runs its
+-- statements in reverse order while recording their own reverse as `redo`
+-- statements, and redoing it does the opposite.
+CREATE TABLE IF NOT EXISTS operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command TEXT NOT NULL,
+    created_at REAL NOT NULL DEFAULT (unixepoch('subsec')),
+    undone INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS undolog (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation INTEGER NOT NULL REFERENCES operations(id) ON DELETE CASCADE,
+    redo INTEGER NOT NULL,
+    sql TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS recording (
+    operation INTEGER NOT NULL,
+    redo INTEGER NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_insert AFTER INSERT ON environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM environments WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_update AFTER UPDATE ON environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE environments SET '
+        || 'env = ' || quote(OLD.env)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', changeset = ' || quote(OLD.changeset)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_delete AFTER DELETE ON environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO environments (rowid, env, key, value, tombstone, created_at, changeset) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.changeset)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_insert AFTER INSERT ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM env_parents WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_update AFTER UPDATE ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE env_parents SET '
+        || 'env = ' || quote(OLD.env)
+        || ', parent = ' || quote(OLD.parent)
+        || ', position = ' || quote(OLD.position)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_delete AFTER DELETE ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO env_parents (rowid, env, parent, position, created_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.parent)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.created_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS changesets_undo_insert AFTER INSERT ON changesets
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM changesets WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS changesets_undo_update AFTER UPDATE ON changesets
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE changesets SET '
+        || 'message = ' || quote(OLD.message)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS changesets_undo_delete AFTER DELETE ON changesets
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO changesets (rowid, message, created_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.message)
+        || ', ' || quote(OLD.created_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_insert AFTER INSERT ON staged
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM staged WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_update AFTER UPDATE ON staged
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE staged SET '
+        || 'env = ' || quote(OLD.env)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_delete AFTER DELETE ON staged
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO staged (rowid, env, key, value, tombstone) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_insert AFTER INSERT ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM trash WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_update AFTER UPDATE ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash SET '
+        || 'env = ' || quote(OLD.env)
+        || ', dropped_at = ' || quote(OLD.dropped_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_delete AFTER DELETE ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash (rowid, env, dropped_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.dropped_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_environments_undo_insert AFTER INSERT ON trash_environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM trash_environments WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_environments_undo_update AFTER UPDATE ON trash_environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash_environments SET '
+        || 'trash_id = ' || quote(OLD.trash_id)
+        || ', seq = ' || quote(OLD.seq)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', changeset = ' || quote(OLD.changeset)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_environments_undo_delete AFTER DELETE ON trash_environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash_environments (rowid, trash_id, seq, key, value, tombstone, created_at, changeset) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.trash_id)
+        || ', ' || quote(OLD.seq)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.changeset)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_parents_undo_insert AFTER INSERT ON trash_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM trash_parents WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_parents_undo_update AFTER UPDATE ON trash_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash_parents SET '
+        || 'trash_id = ' || quote(OLD.trash_id)
+        || ', parent = ' || quote(OLD.parent)
+        || ', position = ' || quote(OLD.position)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_parents_undo_delete AFTER DELETE ON trash_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash_parents (rowid, trash_id, parent, position, created_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.trash_id)
+        || ', ' || quote(OLD.parent)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.created_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_insert AFTER INSERT ON retention
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM retention WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_update AFTER UPDATE ON retention
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE retention SET '
+        || 'env = ' || quote(OLD.env)
+        || ', keep_revisions = ' || quote(OLD.keep_revisions)
+        || ', keep_newer_than = ' || quote(OLD.keep_newer_than)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_delete AFTER DELETE ON retention
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO retention (rowid, env, keep_revisions, keep_newer_than) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.keep_revisions)
+        || ', ' || quote(OLD.keep_newer_than)
+        || ')'
+    FROM recording;
+END;
@@ -0,0 +1,126 @@
+-- environment holds the metadata of every environment. A row is created along
+-- with the first variable or parent of an environment, except while an
+-- operation is replayed by undo or redo, which restores the rows itself.
+CREATE TABLE IF NOT EXISTS environment (
+    env VARCHAR(50) PRIMARY KEY,
+    description TEXT,
+    owner TEXT,
+    -- changes to a protected environment must be confirmed
+    protected INTEGER NOT NULL DEFAULT 0,
+    -- a read-only environment cannot be changed until the flag is cleared
+    read_only INTEGER NOT NULL DEFAULT 0,
+    created_at REAL NOT NULL DEFAULT (unixepoch('subsec'))
+);
+
+INSERT OR IGNORE INTO environment (env, created_at)
+SELECT env, MIN(created_at)
+FROM (
+    SELECT env, created_at FROM environments
+    UNION ALL
+    SELECT env, created_at FROM env_parents
+)
+GROUP BY env;
+
+ALTER TABLE recording ADD COLUMN replaying INTEGER NOT NULL DEFAULT 0;
+
+CREATE TRIGGER IF NOT EXISTS environments_create_environment AFTER INSERT ON environments
+WHEN NOT EXISTS (SELECT 1 FROM recording WHERE replaying)
+BEGIN
+    INSERT INTO environment (env, created_at) VALUES (NEW.env, NEW.created_at)
+    ON CONFLICT (env) DO UPDATE SET created_at = excluded.created_at
+    WHERE excluded.created_at < environment.created_at;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_create_environment AFTER INSERT ON env_parents
+WHEN NOT EXISTS (SELECT 1 FROM recording WHERE replaying)
+BEGIN
+    INSERT INTO environment (env, created_at) VALUES (NEW.env, NEW.created_at)
+    ON CONFLICT (env) DO UPDATE SET created_at = excluded.created_at
+    WHERE excluded.created_at < environment.created_at;
+END;
+
+-- dropped environments keep their metadata in the trash
+ALTER TABLE trash ADD COLUMN description TEXT;
+ALTER TABLE trash ADD COLUMN owner TEXT;
+ALTER TABLE trash ADD COLUMN protected INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE trash ADD COLUMN read_only INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE trash ADD COLUMN env_created_at REAL;
+
+DROP TRIGGER IF EXISTS trash_undo_insert;
+DROP TRIGGER IF EXISTS trash_undo_update;
+DROP TRIGGER IF EXISTS trash_undo_delete;
+
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_insert AFTER INSERT ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM trash WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_update AFTER UPDATE ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash SET '
+        || 'env = ' || quote(OLD.env)
+        || ', dropped_at = ' || quote(OLD.dropped_at)
+        || ', description = ' || quote(OLD.description)
+        || ', owner = ' || quote(OLD.owner)
+        || ', protected = ' || quote(OLD.protected)
+        || ', read_only = ' || quote(OLD.read_only)
+        || ', env_created_at = ' || quote(OLD.env_created_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_undo_delete AFTER DELETE ON trash
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash (rowid, env, dropped_at, description, owner, protected, read_only, env_created_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.dropped_at)
+        || ', ' || quote(OLD.description)
+        || ', ' || quote(OLD.owner)
+        || ', ' || quote(OLD.protected)
+        || ', ' || quote(OLD.read_only)
+        || ', ' || quote(OLD.env_created_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_insert AFTER INSERT ON environment
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM environment WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_update AFTER UPDATE ON environment
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE environment SET '
+        || 'env = ' || quote(OLD.env)
+        || ', description = ' || quote(OLD.description)
+        || ', owner = ' || quote(OLD.owner)
+        || ', protected = ' || quote(OLD.protected)
+        || ', read_only = ' || quote(OLD.read_only)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_delete AFTER DELETE ON environment
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO environment (rowid, env, description, owner, protected, read_only, created_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.description)
+        || ', ' || quote(OLD.owner)
+        || ', ' || quote(OLD.protected)
+        || ', ' || quote(OLD.read_only)
+        || ', ' || quote(OLD.created_at)
+        || ')'
+    FROM recording;
+END;
@@ -0,0 +1,41 @@
+-- descriptions documents what variables are for. A description without an
+-- environment applies to the variable in every environment, unless the
+-- environment has its own.
+CREATE TABLE IF NOT EXISTS descriptions (
+    id INTEGER PRIMARY KEY,
+    env VARCHAR(50),
+    key TEXT NOT NULL,
+    description TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS descriptions_env_key ON descriptions (COALESCE(env, ''), key);
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_insert AFTER INSERT ON descriptions
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM descriptions WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_update AFTER UPDATE ON descriptions
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE descriptions SET '
+        || 'env = ' || quote(OLD.env)
+        || ', key = ' || quote(OLD.key)
+        || ', description = ' || quote(OLD.description)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_delete AFTER DELETE ON descriptions
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO descriptions (rowid, env, key, description) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.description)
+        || ')'
+    FROM recording;
+END;
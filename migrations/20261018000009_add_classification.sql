@@ -0,0 +1,34 @@
+-- classification overrides whether the values of a variable are secret,
+-- which is otherwise guessed from its name
+CREATE TABLE IF NOT EXISTS classification (
+    key TEXT PRIMARY KEY,
+    secret INTEGER NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS classification_undo_insert AFTER INSERT ON classification
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM classification WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS classification_undo_update AFTER UPDATE ON classification
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE classification SET '
+        || 'key = ' || quote(OLD.key)
+        || ', secret = ' || quote(OLD.secret)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS classification_undo_delete AFTER DELETE ON classification
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO classification (rowid, key, secret) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.secret)
+        || ')'
+    FROM recording;
+END;
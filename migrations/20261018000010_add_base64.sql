@@ -0,0 +1,141 @@
+-- base64 flags values holding arbitrary bytes, stored base64 encoded and
+-- decoded when injected into a command
+ALTER TABLE environments ADD COLUMN base64 INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE staged ADD COLUMN base64 INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE trash_environments ADD COLUMN base64 INTEGER NOT NULL DEFAULT 0;
+
+DROP VIEW IF EXISTS active_envs;
+DROP VIEW IF EXISTS latest_envs;
+
+-- latest_envs returns all the latest environments key-value pairs, including
+-- the not active (null) values
+CREATE VIEW IF NOT EXISTS latest_envs AS
+SELECT
+    revision
+    , env
+    , key
+    , value
+    , tombstone
+    , base64
+    , created_at
+FROM (
+    SELECT
+        *
+        , ROW_NUMBER() OVER (PARTITION BY env, key ORDER BY revision DESC) AS rn
+    FROM environments
+)
+WHERE
+    rn = 1;
+
+-- active_envs returns all the active (non-null) latest environments key-value
+-- pairs
+CREATE VIEW IF NOT EXISTS active_envs AS
+SELECT
+    revision
+    , env
+    , key
+    , value
+    , base64
+    , created_at
+FROM latest_envs
+WHERE
+    value IS NOT NULL;
+
+-- the undo triggers are recreated to restore the new columns as well
+DROP TRIGGER IF EXISTS environments_undo_update;
+DROP TRIGGER IF EXISTS environments_undo_delete;
+DROP TRIGGER IF EXISTS staged_undo_update;
+DROP TRIGGER IF EXISTS staged_undo_delete;
+DROP TRIGGER IF EXISTS trash_environments_undo_update;
+DROP TRIGGER IF EXISTS trash_environments_undo_delete;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_update AFTER UPDATE ON environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE environments SET '
+        || 'env = ' || quote(OLD.env)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ', base64 = ' || quote(OLD.base64)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', changeset = ' || quote(OLD.changeset)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_delete AFTER DELETE ON environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO environments (rowid, env, key, value, tombstone, base64, created_at, changeset) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ', ' || quote(OLD.base64)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.changeset)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_update AFTER UPDATE ON staged
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE staged SET '
+        || 'env = ' || quote(OLD.env)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ', base64 = ' || quote(OLD.base64)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_delete AFTER DELETE ON staged
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO staged (rowid, env, key, value, tombstone, base64) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ', ' || quote(OLD.base64)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_environments_undo_update AFTER UPDATE ON trash_environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash_environments SET '
+        || 'trash_id = ' || quote(OLD.trash_id)
+        || ', seq = ' || quote(OLD.seq)
+        || ', key = ' || quote(OLD.key)
+        || ', value = ' || quote(OLD.value)
+        || ', tombstone = ' || quote(OLD.tombstone)
+        || ', base64 = ' || quote(OLD.base64)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', changeset = ' || quote(OLD.changeset)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_environments_undo_delete AFTER DELETE ON trash_environments
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash_environments (rowid, trash_id, seq, key, value, tombstone, base64, created_at, changeset) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.trash_id)
+        || ', ' || quote(OLD.seq)
+        || ', ' || quote(OLD.key)
+        || ', ' || quote(OLD.value)
+        || ', ' || quote(OLD.tombstone)
+        || ', ' || quote(OLD.base64)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.changeset)
+        || ')'
+    FROM recording;
+END;
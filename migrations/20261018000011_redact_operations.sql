@@ -0,0 +1,7 @@
+-- operations used to be described by the whole command line, which holds the
+-- values of commands like add. Only the name of their command is kept.
+UPDATE operations
+SET command = CASE
+    WHEN instr(command, ' ') > 0 THEN substr(command, 1, instr(command, ' ') - 1)
+    ELSE command
+END;
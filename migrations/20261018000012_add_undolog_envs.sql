@@ -0,0 +1,155 @@
+-- undolog_envs lists the environments changed by the statements of undolog,
+-- so that replaying them is refused on read-only environments and confirmed on
+-- protected ones, like the changes they revert. Changes to the metadata of an
+-- environment only need to be confirmed, so that its flags can be cleared.
+CREATE TABLE IF NOT EXISTS undolog_envs (
+    operation INTEGER NOT NULL REFERENCES operations(id) ON DELETE CASCADE,
+    redo INTEGER NOT NULL,
+    env VARCHAR(50) NOT NULL,
+    metadata INTEGER NOT NULL,
+    UNIQUE (operation, redo, env, metadata)
+);
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_env_insert AFTER INSERT ON environments
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_env_update AFTER UPDATE ON environments
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environments_undo_env_delete AFTER DELETE ON environments
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_insert AFTER INSERT ON env_parents
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_update AFTER UPDATE ON env_parents
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_delete AFTER DELETE ON env_parents
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_env_insert AFTER INSERT ON staged
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_env_update AFTER UPDATE ON staged
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS staged_undo_env_delete AFTER DELETE ON staged
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_env_insert AFTER INSERT ON retention
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_env_update AFTER UPDATE ON retention
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS retention_undo_env_delete AFTER DELETE ON retention
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_env_insert AFTER INSERT ON descriptions
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_env_update AFTER UPDATE ON descriptions
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS descriptions_undo_env_delete AFTER DELETE ON descriptions
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_env_insert AFTER INSERT ON environment
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, TRUE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_env_update AFTER UPDATE ON environment
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, TRUE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS environment_undo_env_delete AFTER DELETE ON environment
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, TRUE
+    FROM recording;
+END;
@@ -0,0 +1,120 @@
+-- removed parents are kept with the time they were removed, so that an
+-- environment read at an earlier point in time still inherits from them. Only
+-- the parents not removed are unique, a parent can be removed and added again.
+CREATE TABLE IF NOT EXISTS env_parents_new (
+    env VARCHAR(50) NOT NULL,
+    parent VARCHAR(50) NOT NULL,
+    position INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    removed_at INTEGER
+);
+
+INSERT INTO env_parents_new (rowid, env, parent, position, created_at)
+SELECT rowid, env, parent, position, created_at
+FROM env_parents;
+
+DROP TABLE env_parents;
+ALTER TABLE env_parents_new RENAME TO env_parents;
+
+CREATE UNIQUE INDEX IF NOT EXISTS env_parents_current ON env_parents (env, parent)
+WHERE removed_at IS NULL;
+
+ALTER TABLE trash_parents ADD COLUMN removed_at INTEGER;
+
+-- the triggers of the dropped table are created again, and the undo ones
+-- restore the removal time as well
+CREATE TRIGGER IF NOT EXISTS env_parents_create_environment AFTER INSERT ON env_parents
+WHEN NOT EXISTS (SELECT 1 FROM recording WHERE replaying)
+BEGIN
+    INSERT INTO environment (env, created_at) VALUES (NEW.env, NEW.created_at)
+    ON CONFLICT (env) DO UPDATE SET created_at = excluded.created_at
+    WHERE excluded.created_at < environment.created_at;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_insert AFTER INSERT ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'DELETE FROM env_parents WHERE rowid = ' || NEW.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_update AFTER UPDATE ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE env_parents SET '
+        || 'env = ' || quote(OLD.env)
+        || ', parent = ' || quote(OLD.parent)
+        || ', position = ' || quote(OLD.position)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', removed_at = ' || quote(OLD.removed_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_delete AFTER DELETE ON env_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO env_parents (rowid, env, parent, position, created_at, removed_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.env)
+        || ', ' || quote(OLD.parent)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.removed_at)
+        || ')'
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_insert AFTER INSERT ON env_parents
+WHEN NEW.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, NEW.env, FALSE
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_update AFTER UPDATE ON env_parents
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, changed.env, FALSE
+    FROM recording, (SELECT OLD.env AS env UNION SELECT NEW.env) AS changed
+    WHERE changed.env IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS env_parents_undo_env_delete AFTER DELETE ON env_parents
+WHEN OLD.env IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO undolog_envs (operation, redo, env, metadata)
+    SELECT operation, redo, OLD.env, FALSE
+    FROM recording;
+END;
+
+DROP TRIGGER IF EXISTS trash_parents_undo_update;
+DROP TRIGGER IF EXISTS trash_parents_undo_delete;
+
+CREATE TRIGGER IF NOT EXISTS trash_parents_undo_update AFTER UPDATE ON trash_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'UPDATE trash_parents SET '
+        || 'trash_id = ' || quote(OLD.trash_id)
+        || ', parent = ' || quote(OLD.parent)
+        || ', position = ' || quote(OLD.position)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', removed_at = ' || quote(OLD.removed_at)
+        || ' WHERE rowid = ' || OLD.rowid
+    FROM recording;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trash_parents_undo_delete AFTER DELETE ON trash_parents
+BEGIN
+    INSERT INTO undolog (operation, redo, sql)
+    SELECT operation, redo, 'INSERT INTO trash_parents (rowid, trash_id, parent, position, created_at, removed_at) VALUES ('
+        || OLD.rowid
+        || ', ' || quote(OLD.trash_id)
+        || ', ' || quote(OLD.parent)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.removed_at)
+        || ')'
+    FROM recording;
+END;
@@ -1,21 +1,95 @@
 use anyhow::{Result, bail};
-use clap::Subcommand;
+use clap::{Args, CommandFactory, Subcommand};
 
 use crate::core::state::{EnvelopeState, UnlockedEnvelope};
-use crate::db::EnvelopeDb;
+use crate::db::{Change, EnvelopeDb};
 use crate::{core, ops, utils};
 
 mod add;
+mod classify;
+mod commit;
 mod delete;
+mod describe;
 mod diff;
 mod drop;
 mod duplicate;
 mod edit;
+mod env;
+mod export;
+mod gc;
 mod history;
 mod import;
 mod list;
+mod log;
+mod parent;
+mod purge;
+mod redo;
+mod rename_env;
+mod rename_key;
+mod reset;
+mod restore;
+mod restore_env;
+mod retention;
 mod revert;
 mod run;
+mod shell_env;
+mod trash;
+mod undo;
+mod unset;
+mod validate;
+
+/// Options of the commands changing variables, describing how the changes are
+/// recorded
+#[derive(Args)]
+pub struct ChangeArgs {
+    /// Message describing the changes, listed by the log command
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Stage the changes instead of applying them, until they are committed
+    /// with the commit command
+    #[arg(long, conflicts_with = "message")]
+    stage: bool,
+}
+
+impl ChangeArgs {
+    fn change(&self) -> Change<'_> {
+        Change {
+            message: self.message.as_deref(),
+            stage: self.stage,
+        }
+    }
+}
+
+/// Arguments naming environments and keys, the only ones kept when describing
+/// a command
+const NAME_ARGS: &[&str] = &[
+    "env", "key", "new_env", "new_key", "parent", "source", "target",
+];
+
+/// Describes the command run with `args` by its subcommands and the
+/// environments and keys it names. Values and every other option are left
+/// out, since the description is stored with its operation and printed back
+/// by undo and redo.
+fn describe(args: impl IntoIterator<Item = String>) -> String {
+    let Ok(matches) = crate::Envelope::command().try_get_matches_from(args) else {
+        return String::new();
+    };
+
+    let mut words = Vec::new();
+    let mut matches = &matches;
+    while let Some((name, sub)) = matches.subcommand() {
+        words.push(name.to_owned());
+        for id in NAME_ARGS {
+            if let Ok(Some(value)) = sub.try_get_one::<String>(id) {
+                words.push(value.clone());
+            }
+        }
+        matches = sub;
+    }
+
+    words.join(" ")
+}
 
 #[derive(Subcommand)]
 #[command(infer_subcommands = true)]
@@ -25,8 +99,14 @@ pub enum EnvelopeCmd {
     /// Check which environment is currently exported
     Check,
 
+    Classify(classify::Cmd),
+
+    Commit(commit::Cmd),
+
     Delete(delete::Cmd),
 
+    Describe(describe::Cmd),
+
     Drop(drop::Cmd),
 
     Duplicate(duplicate::Cmd),
@@ -35,6 +115,12 @@ pub enum EnvelopeCmd {
 
     Edit(edit::Cmd),
 
+    Env(env::Cmd),
+
+    Export(export::Cmd),
+
+    Gc(gc::Cmd),
+
     History(history::Cmd),
 
     /// Initialize envelope
@@ -44,19 +130,50 @@ pub enum EnvelopeCmd {
 
     List(list::Cmd),
 
+    Log(log::Cmd),
+
     /// Encrypt envelope
     Lock,
 
+    Parent(parent::Cmd),
+
+    Purge(purge::Cmd),
+
+    Redo(redo::Cmd),
+
+    RenameEnv(rename_env::Cmd),
+
+    RenameKey(rename_key::Cmd),
+
+    Reset(reset::Cmd),
+
+    Restore(restore::Cmd),
+
+    RestoreEnv(restore_env::Cmd),
+
+    Retention(retention::Cmd),
+
     Revert(revert::Cmd),
 
     Run(run::Cmd),
 
+    ShellEnv(shell_env::Cmd),
+
+    Trash(trash::Cmd),
+
+    Undo(undo::Cmd),
+
     /// Decrypt envelope
     Unlock,
+
+    Unset(unset::Cmd),
+
+    Validate(validate::Cmd),
 }
 
 impl EnvelopeCmd {
-    pub async fn run(self) -> Result<()> {
+    /// Runs the command, answering yes to every confirmation if `yes`
+    pub async fn run(self, yes: bool) -> Result<()> {
         let state = core::state::detect().await?;
 
         match (self, state) {
@@ -92,7 +209,9 @@ impl EnvelopeCmd {
             }
 
             // all other commands: only valid when unlocked
-            (cmd, Some(EnvelopeState::Unlocked(envelope))) => cmd.run_with_db(envelope.db()).await,
+            (cmd, Some(EnvelopeState::Unlocked(envelope))) => {
+                cmd.run_with_db(envelope.db(), yes).await
+            }
 
             // all other commands when locked: ask for password, decrypt to
             // memory, run, re-encrypt if modified. The unlocked working copy
@@ -100,7 +219,7 @@ impl EnvelopeCmd {
             (cmd, Some(EnvelopeState::Locked(envelope))) => {
                 let password = utils::prompt_password("Password: ")?;
                 let unlocked = envelope.unlock(&password).await?;
-                cmd.run_with_db(unlocked.db()).await?;
+                cmd.run_with_db(unlocked.db(), yes).await?;
                 if unlocked.db().total_changes().await? > 0 {
                     let path = core::envelope_path()?;
                     unlocked.lock(&password).await?.store(&path)?;
@@ -113,20 +232,80 @@ impl EnvelopeCmd {
         }
     }
 
-    async fn run_with_db(self, db: &EnvelopeDb) -> Result<()> {
+    /// Whether the command is recorded as an operation that can be undone.
+    /// Commands reading the envelope are not, neither are purge and gc, which
+    /// clear the undo history.
+    fn is_undoable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Check
+                | Self::Diff(_)
+                | Self::Export(_)
+                | Self::Gc(_)
+                | Self::History(_)
+                | Self::List(_)
+                | Self::Log(_)
+                | Self::Purge(_)
+                | Self::Redo(_)
+                | Self::Run(_)
+                | Self::ShellEnv(_)
+                | Self::Undo(_)
+                | Self::Validate(_)
+        )
+    }
+
+    async fn run_with_db(self, db: &EnvelopeDb, yes: bool) -> Result<()> {
+        match yes {
+            true => db.on_confirm(|_| Ok(true)),
+            false => db.on_confirm(utils::confirm),
+        }
+
+        if !self.is_undoable() {
+            return self.dispatch(db).await;
+        }
+
+        let recording = db.begin_operation(&describe(std::env::args())).await?;
+        let res = self.dispatch(db).await;
+        db.end_operation(recording).await?;
+
+        res
+    }
+
+    async fn dispatch(self, db: &EnvelopeDb) -> Result<()> {
         match self {
             Self::Add(add) => add.run(db).await,
             Self::Check => ops::check(&mut std::io::stdout(), db).await,
+            Self::Classify(classify) => classify.run(db).await,
+            Self::Commit(commit) => commit.run(db).await,
             Self::Delete(delete) => delete.run(db).await,
+            Self::Describe(describe) => describe.run(db).await,
             Self::Drop(drop) => drop.run(db).await,
             Self::Duplicate(duplicate) => duplicate.run(db).await,
             Self::Diff(diff) => diff.run(db).await,
             Self::Edit(edit) => edit.run(db).await,
+            Self::Env(env) => env.run(db).await,
+            Self::Export(export) => export.run(db).await,
+            Self::Gc(gc) => gc.run(db).await,
             Self::Import(import) => import.run(db).await,
             Self::History(history) => history.run(db).await,
             Self::List(list) => list.run(db).await,
+            Self::Log(log) => log.run(db).await,
+            Self::Parent(parent) => parent.run(db).await,
+            Self::Purge(purge) => purge.run(db).await,
+            Self::Redo(redo) => redo.run(db).await,
+            Self::RenameEnv(rename) => rename.run(db).await,
+            Self::RenameKey(rename) => rename.run(db).await,
+            Self::Reset(reset) => reset.run(db).await,
+            Self::Restore(restore) => restore.run(db).await,
+            Self::RestoreEnv(restore) => restore.run(db).await,
+            Self::Retention(retention) => retention.run(db).await,
             Self::Revert(revert) => revert.run(db).await,
             Self::Run(run) => run.run(db).await,
+            Self::ShellEnv(shell_env) => shell_env.run(db).await,
+            Self::Trash(trash) => trash.run(db).await,
+            Self::Undo(undo) => undo.run(db).await,
+            Self::Unset(unset) => unset.run(db).await,
+            Self::Validate(validate) => validate.run(db).await,
             Self::Init | Self::Lock | Self::Unlock => unreachable!(),
         }
     }
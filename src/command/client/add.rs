@@ -1,10 +1,11 @@
-use std::io::{BufRead, Write};
+use std::io::{BufRead, IsTerminal, Read, Write};
 
 use anyhow::{Result, bail};
 use clap::Parser;
 
+use super::ChangeArgs;
 use crate::db::EnvelopeDb;
-use crate::ops;
+use crate::{ops, utils};
 
 /// Add environment variables to a specific environment
 #[derive(Parser)]
@@ -15,13 +16,22 @@ pub struct Cmd {
     /// Name of the environment variable
     key: String,
 
-    /// Read environment variable value from stdin
+    /// Read environment variable value from stdin. Piped input is read
+    /// whole, so that it can span multiple lines.
     #[arg(short, long)]
     stdin: bool,
 
+    /// The value is the base64 encoding of arbitrary bytes, which are decoded
+    /// when the variable is injected into a command
+    #[arg(long)]
+    base64: bool,
+
     /// Value of the environment variable. Default to empty string if not
     /// provided.
     value: Option<String>,
+
+    #[command(flatten)]
+    change: ChangeArgs,
 }
 
 impl Cmd {
@@ -30,8 +40,12 @@ impl Cmd {
             bail!("cannot specify both a value argument and --stdin");
         }
 
+        let piped = self.stdin && !std::io::stdin().is_terminal();
         let mut value = String::new();
         match self.stdin {
+            true if piped => {
+                std::io::stdin().read_to_string(&mut value)?;
+            }
             true => {
                 writeln!(std::io::stdout(), "Enter value for env {}: ", self.key)?;
                 let stdin = std::io::stdin();
@@ -44,6 +58,16 @@ impl Cmd {
             }
         }
 
-        ops::add_var(db, &self.env, &self.key, value.trim_end()).await
+        let value = match piped {
+            // piped values keep their whitespace, but for the last line break
+            true => utils::strip_line_break(&value),
+            false => value.trim_end(),
+        };
+        match self.base64 {
+            true => {
+                ops::add_base64_var(db, &self.env, &self.key, value, self.change.change()).await
+            }
+            false => ops::add_var(db, &self.env, &self.key, value, self.change.change()).await,
+        }
     }
 }
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+/// Classes of variables
+#[derive(Debug, Clone, clap::ValueEnum)]
+enum Class {
+    /// Values are masked in output
+    Secret,
+    /// Values are shown in output
+    Plain,
+    /// Guess the class from the name of the variable
+    Auto,
+}
+
+#[derive(Parser)]
+#[command(
+    about = "Classify an environment variable as secret or plain config",
+    long_about = "Classify an environment variable as secret or plain config\n
+The values of secrets are masked by list, diff, history and restore unless --reveal is passed. \
+                  Variables not classified are secret when their name suggests so, such as \
+                  GITHUB_TOKEN or DB_PASSWORD. Without a class, the current one is printed."
+)]
+pub struct Cmd {
+    /// Name of the environment variable
+    key: String,
+
+    /// Class of the variable, in every environment
+    class: Option<Class>,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let secret = match self.class {
+            None => return ops::show_classification(&mut std::io::stdout(), db, &self.key).await,
+            Some(Class::Secret) => Some(true),
+            Some(Class::Plain) => Some(false),
+            Some(Class::Auto) => None,
+        };
+
+        ops::classify(db, &self.key, secret).await
+    }
+}
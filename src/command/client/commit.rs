@@ -0,0 +1,29 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Apply the staged changes",
+    long_about = "Apply the staged changes\n
+Changes made with --stage accumulate until they are committed, and are applied all at once as a \
+                  single changeset."
+)]
+pub struct Cmd {
+    /// Message describing the changes, listed by the log command
+    #[arg(short, long)]
+    message: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match ops::commit(db, self.message.as_deref()).await? {
+            0 => println!("nothing to commit"),
+            n => println!("committed {n} changes"),
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 
+use super::ChangeArgs;
 use crate::db::EnvelopeDb;
 use crate::ops;
 
@@ -14,19 +15,22 @@ pub struct Cmd {
     /// Environment variable name that you wish to delete.
     #[arg(short, long)]
     key: Option<String>,
+
+    #[command(flatten)]
+    change: ChangeArgs,
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
         match (&self.env, &self.key) {
             (Some(e), Some(k)) => {
-                ops::delete_var_in_env(db, e, k).await?;
+                ops::delete_var_in_env(db, e, k, self.change.change()).await?;
             }
             (None, Some(k)) => {
-                ops::delete_var_globally(db, k).await?;
+                ops::delete_var_globally(db, k, self.change.change()).await?;
             }
             (Some(e), None) => {
-                ops::delete_env(db, e).await?;
+                ops::delete_env(db, e, self.change.change()).await?;
             }
             _ => {}
         }
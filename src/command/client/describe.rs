@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Describe what an environment variable is for",
+    long_about = "Describe what an environment variable is for\n
+Without --env the description applies to the variable in every environment that does not have its \
+                  own. Without a description, the current one is printed. Descriptions are listed \
+                  by list --pretty-print and shown as comments by edit."
+)]
+pub struct Cmd {
+    /// Name of the environment variable
+    key: String,
+
+    /// Free text describing the variable
+    #[arg(conflicts_with = "clear")]
+    description: Option<String>,
+
+    /// Only describe the variable in this environment
+    #[arg(short, long)]
+    env: Option<String>,
+
+    /// Remove the description
+    #[arg(long)]
+    clear: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let env = self.env.as_deref();
+        match &self.description {
+            Some(description) => ops::describe(db, env, &self.key, Some(description)).await,
+            None if self.clear => ops::describe(db, env, &self.key, None).await,
+            None => ops::show_description(&mut std::io::stdout(), db, env, &self.key).await,
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use anyhow::Result;
 use clap::Parser;
 
-use crate::db::EnvelopeDb;
-use crate::ops;
+use crate::db::{EnvelopeDb, Resolve};
+use crate::{ops, time};
 
 #[derive(Parser)]
 #[command(
@@ -13,18 +13,59 @@ This command will list all the variables that differ between source and target e
 - Variables present in env1 but not in env2 will be shown in green.
 - Variables not present in env1 but present in env2 will be shown in red.
 - Variables both present in env1 and env2 but with a different value will be shown in gray, first \
-                  value being the value in env1, second value is the value found in env2."
+                  value being the value in env1, second value is the value found in env2.
+
+Either environment can be written as env@time to compare it as it was at that point in time, e.g. \
+                  `envelope diff prod@2h prod`."
 )]
 pub struct Cmd {
-    /// Source environment
-    env1: String,
-    /// Target environment
-    env2: String,
+    /// Source environment, optionally followed by @time
+    #[arg(value_parser = parse_env_at)]
+    env1: (String, Option<i64>),
+    /// Target environment, optionally followed by @time
+    #[arg(value_parser = parse_env_at)]
+    env2: (String, Option<i64>),
+
+    /// Do not include variables inherited from parent environments
+    #[arg(long)]
+    no_inherit: bool,
+
+    /// Compare the environments as they were at a point in time, unless a
+    /// side specifies its own with env@time
+    #[arg(long, value_parser = time::parse_at)]
+    at: Option<i64>,
+
+    /// Show the values of secrets instead of masking them
+    #[arg(long)]
+    reveal: bool,
+}
+
+/// Splits `env@time` into the environment name and the parsed point in time
+fn parse_env_at(s: &str) -> Result<(String, Option<i64>)> {
+    match s.rsplit_once('@') {
+        Some((env, at)) => Ok((env.to_owned(), Some(time::parse_at(at)?))),
+        None => Ok((s.to_owned(), None)),
+    }
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ops::diff(&mut std::io::stdout(), db, &self.env1, &self.env2).await?;
+        let resolve = |at: Option<i64>| Resolve {
+            inherit: !self.no_inherit,
+            at: at.or(self.at),
+        };
+        let ((env1, at1), (env2, at2)) = (&self.env1, &self.env2);
+
+        ops::diff(
+            &mut std::io::stdout(),
+            db,
+            env1,
+            resolve(*at1),
+            env2,
+            resolve(*at2),
+            self.reveal,
+        )
+        .await?;
 
         Ok(())
     }
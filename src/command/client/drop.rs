@@ -1,11 +1,16 @@
-use anyhow::Result;
+use anyhow::{Result, ensure};
 use clap::Parser;
 
 use crate::db::EnvelopeDb;
 use crate::ops;
 
-/// Drop environment
 #[derive(Parser)]
+#[command(
+    about = "Drop environment",
+    long_about = "Drop environment\n
+The environment is moved to the trash along with its whole history, and can be brought back with \
+                  restore-env. Use `trash empty` to delete it permanently."
+)]
 pub struct Cmd {
     /// Environment to drop
     env: String,
@@ -13,6 +18,17 @@ pub struct Cmd {
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ops::drop(db, &self.env).await
+        ensure!(
+            db.env_exists(&self.env).await?,
+            "environment '{}' does not exist",
+            self.env
+        );
+
+        let prompt = format!("Move environment '{}' to the trash?", self.env);
+        if db.confirm(&prompt)? {
+            ops::drop(db, &self.env).await?;
+        }
+
+        Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 
+use super::ChangeArgs;
 use crate::db::EnvelopeDb;
 use crate::ops;
 
@@ -9,11 +10,14 @@ use crate::ops;
 pub struct Cmd {
     /// Environment that you wish to edit.
     env: String,
+
+    #[command(flatten)]
+    change: ChangeArgs,
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ops::edit(db, &self.env).await?;
+        ops::edit(db, &self.env, self.change.change()).await?;
         Ok(())
     }
 }
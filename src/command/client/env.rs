@@ -0,0 +1,72 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::db::{EnvelopeDb, MetaUpdate};
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Manage the metadata of environments",
+    long_about = "Manage the metadata of environments\n
+Changes to a protected environment must be confirmed interactively. A read-only environment cannot \
+                  be changed at all until the flag is cleared."
+)]
+pub struct Cmd {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Set the description, owner or flags of an environment
+    SetMeta {
+        /// Environment to update
+        env: String,
+
+        /// What the environment is used for, an empty value clears it
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Person or team to contact about the environment, an empty value
+        /// clears it
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Whether changes to the environment must be confirmed
+        #[arg(long)]
+        protected: Option<bool>,
+
+        /// Whether changes to the environment are refused
+        #[arg(long)]
+        read_only: Option<bool>,
+    },
+
+    /// Show the metadata of an environment
+    Show {
+        /// Environment to show
+        env: String,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match &self.action {
+            Action::SetMeta {
+                env,
+                description,
+                owner,
+                protected,
+                read_only,
+            } => {
+                let update = MetaUpdate {
+                    description: description.as_deref(),
+                    owner: owner.as_deref(),
+                    protected: *protected,
+                    read_only: *read_only,
+                };
+                ops::set_meta(db, env, update).await
+            }
+            Action::Show { env } => ops::show_meta(&mut std::io::stdout(), db, env).await,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::IsTerminal;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::{EnvelopeDb, Resolve};
+use crate::format::{Format, Options};
+use crate::{ops, time};
+
+/// Export the variables of an environment to a file format
+#[derive(Parser)]
+pub struct Cmd {
+    /// Environment to export
+    env: String,
+
+    /// Format to write the variables in, sorted by key
+    #[arg(short, long, default_value = "dotenv")]
+    format: Format,
+
+    /// Nest the variables by splitting their keys on the separator, in json,
+    /// yaml or toml
+    #[arg(long)]
+    nested: bool,
+
+    /// Separator of the nested keys
+    #[arg(long, default_value = "__", requires = "nested")]
+    separator: String,
+
+    /// Name of the Kubernetes object, in k8s-secret or k8s-configmap
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Namespace of the Kubernetes object
+    #[arg(long)]
+    namespace: Option<String>,
+
+    /// Write the values of the Secret to stringData instead of base64
+    /// encoding them to data
+    #[arg(long)]
+    string_data: bool,
+
+    /// Service whose environment is set, in compose
+    #[arg(long)]
+    service: Option<String>,
+
+    /// Unit whose environment is set, in systemd-dropin
+    #[arg(long)]
+    unit: Option<String>,
+
+    /// Do not include variables inherited from parent environments
+    #[arg(long)]
+    no_inherit: bool,
+
+    /// Read the environment as it was at a point in time: a unix timestamp, a
+    /// UTC date such as "2024-01-31 12:30" or a duration ago such as "2h"
+    #[arg(long, value_parser = time::parse_at)]
+    at: Option<i64>,
+
+    /// Show the values of secrets instead of masking them. Secrets are only
+    /// masked when printed to a terminal.
+    #[arg(long)]
+    reveal: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let resolve = Resolve {
+            inherit: !self.no_inherit,
+            at: self.at,
+        };
+
+        let formatter = self.format.formatter(Options {
+            separator: self.nested.then(|| self.separator.clone()),
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            string_data: self.string_data,
+            service: self.service.clone(),
+            unit: self.unit.clone(),
+        })?;
+
+        ops::export(
+            &mut std::io::stdout(),
+            db,
+            &self.env,
+            formatter.as_ref(),
+            resolve,
+            // exports are meant to be written to files
+            self.reveal || !std::io::stdout().is_terminal(),
+        )
+        .await
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Prune history and shrink the envelope",
+    long_about = "Prune history and shrink the envelope\n
+Revisions repeating the previous value of their variable are removed, along with the revisions \
+                  beyond the retention settings of their environment, see the retention command. \
+                  The latest value of every variable is always kept, but reading an environment \
+                  at a point in time before the removed revisions is no longer accurate. \
+                  Read-only environments keep their whole history, and pruning a protected \
+                  environment must be confirmed.\n
+Environments that have been in the trash for longer than 30 days, or than the duration set in \
+                  ENVELOPE_TRASH_RETENTION, are permanently deleted along with the whole history."
+)]
+pub struct Cmd {
+    /// Only prune the history of this environment
+    env: Option<String>,
+
+    /// List the revisions that would be removed without removing them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let revisions = ops::collectable(db, self.env.as_deref()).await?;
+        // the trash only expires when pruning every environment
+        let expiry = match self.env {
+            Some(_) => None,
+            None => Some(ops::trash_expiry()?),
+        };
+
+        if self.dry_run {
+            ops::write_revisions(&mut std::io::stdout(), &revisions)?;
+            println!("would remove {} revisions", revisions.len());
+            let expired = match expiry {
+                Some(expiry) => ops::list_trash(&mut std::io::stdout(), db, Some(expiry)).await?,
+                None => false,
+            };
+            if expired {
+                println!("would delete these environments from the trash");
+            }
+            return Ok(());
+        }
+
+        ops::gc(db, &revisions).await?;
+        println!("removed {} revisions", revisions.len());
+        if let Some(expiry) = expiry {
+            match ops::empty_trash(db, Some(expiry)).await? {
+                0 => {}
+                n => println!("deleted {n} environments from the trash"),
+            }
+        }
+
+        Ok(())
+    }
+}
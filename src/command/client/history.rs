@@ -12,11 +12,22 @@ pub struct Cmd {
 
     /// The key whose value history you want to retrieve.
     key: String,
+
+    /// Show the values of secrets instead of masking them
+    #[arg(long)]
+    reveal: bool,
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ops::history(&mut std::io::stdout(), db, &self.env, &self.key).await?;
+        ops::history(
+            &mut std::io::stdout(),
+            db,
+            &self.env,
+            &self.key,
+            self.reveal,
+        )
+        .await?;
 
         Ok(())
     }
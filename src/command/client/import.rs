@@ -1,10 +1,12 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 
 use anyhow::Result;
 use clap::Parser;
 
+use super::ChangeArgs;
 use crate::db::EnvelopeDb;
+use crate::nested::{Arrays, Document};
 use crate::ops;
 
 /// Import environment variables
@@ -17,6 +19,26 @@ pub struct Cmd {
     /// Path of the file from which you want to import environment variables.
     /// Defaults to stdin if not provided.
     path: Option<String>,
+
+    /// Fail on the first line that cannot be parsed, instead of skipping it.
+    #[arg(long, conflicts_with = "format")]
+    strict: bool,
+
+    /// Read a JSON, YAML or TOML document instead of KEY=value lines, and
+    /// flatten its nested keys.
+    #[arg(short, long)]
+    format: Option<Document>,
+
+    /// Separator joining the nested keys of a document.
+    #[arg(long, default_value = "__", requires = "format")]
+    separator: String,
+
+    /// How the arrays of a document are imported.
+    #[arg(long, default_value = "index", requires = "format")]
+    arrays: Arrays,
+
+    #[command(flatten)]
+    change: ChangeArgs,
 }
 
 impl Cmd {
@@ -29,7 +51,34 @@ impl Cmd {
             }
         };
 
-        ops::import(reader, &mut std::io::stdout(), db, &self.env).await?;
+        if let Some(document) = self.format {
+            let keys = ops::import_document(
+                reader,
+                db,
+                &self.env,
+                document,
+                &self.separator,
+                self.arrays,
+                self.change.change(),
+            )
+            .await?;
+
+            let mut stdout = std::io::stdout();
+            for key in keys {
+                writeln!(stdout, "imported {key}")?;
+            }
+            return Ok(());
+        }
+
+        ops::import(
+            reader,
+            &mut std::io::stdout(),
+            db,
+            &self.env,
+            self.strict,
+            self.change.change(),
+        )
+        .await?;
 
         Ok(())
     }
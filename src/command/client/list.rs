@@ -1,8 +1,10 @@
+use std::io::IsTerminal;
+
 use anyhow::Result;
 use clap::Parser;
 
-use crate::db::{self, EnvelopeDb};
-use crate::ops;
+use crate::db::{self, EnvelopeDb, Resolve};
+use crate::{ops, time};
 
 /// Valid sorting types
 #[derive(Debug, Clone, clap::ValueEnum)]
@@ -39,8 +41,15 @@ impl Sort {
 pub struct Cmd {
     /// Environment that you wish to list.
     /// If not provided, all environments will be listed.
+    #[arg(conflicts_with = "trash")]
     env: Option<String>,
 
+    /// List the dropped environments in the trash
+    #[arg(long)]
+    trash: bool,
+
+    /// Print a table, listing the metadata of environments when no
+    /// environment is provided
     #[arg(long, short)]
     pretty_print: bool,
 
@@ -50,21 +59,84 @@ pub struct Cmd {
     /// How envelope should sort result
     #[arg(long, short, default_value = "d")]
     sort: Sort,
+
+    /// Do not include variables inherited from parent environments
+    #[arg(long)]
+    no_inherit: bool,
+
+    /// Read the environment as it was at a point in time: a unix timestamp, a
+    /// UTC date such as "2024-01-31 12:30" or a duration ago such as "2h"
+    #[arg(long, value_parser = time::parse_at)]
+    at: Option<i64>,
+
+    /// Show values as stored, without expanding `${KEY}` references
+    #[arg(long)]
+    raw: bool,
+
+    /// End every variable with a NUL byte instead of a line break, writing
+    /// values unquoted and base64 encoded values decoded
+    #[arg(short = '0', long, conflicts_with = "pretty_print")]
+    null: bool,
+
+    /// Show the values of secrets instead of masking them. Secrets are only
+    /// masked in tables and when printed to a terminal.
+    #[arg(long)]
+    reveal: bool,
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let resolve = Resolve {
+            inherit: !self.no_inherit,
+            at: self.at,
+        };
+
         match &self.env {
+            None if self.trash => {
+                ops::list_trash(&mut std::io::stdout(), db, None).await?;
+            }
+            None if self.pretty_print => ops::table_list_envs(db).await?,
             None => ops::list_envs(&mut std::io::stdout(), db).await?,
+            Some(env) if self.null => {
+                ops::list_nul(
+                    &mut std::io::stdout(),
+                    db,
+                    env,
+                    self.sort.to_str(),
+                    resolve,
+                    self.raw,
+                    self.reveal || !std::io::stdout().is_terminal(),
+                )
+                .await?;
+            }
             Some(env) => {
                 if !self.pretty_print {
-                    ops::list_raw(&mut std::io::stdout(), db, env, self.sort.to_str()).await?;
+                    ops::list_raw(
+                        &mut std::io::stdout(),
+                        db,
+                        env,
+                        self.sort.to_str(),
+                        resolve,
+                        self.raw,
+                        // piped output is meant for other programs
+                        self.reveal || !std::io::stdout().is_terminal(),
+                    )
+                    .await?;
                 } else {
                     let truncate = match self.truncate {
                         true => db::Truncate::Max(60),
                         false => db::Truncate::None,
                     };
-                    ops::table_list(db, env, truncate, self.sort.to_str()).await?;
+                    ops::table_list(
+                        db,
+                        env,
+                        truncate,
+                        self.sort.to_str(),
+                        resolve,
+                        self.raw,
+                        self.reveal,
+                    )
+                    .await?;
                 }
             }
         }
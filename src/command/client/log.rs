@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "List changesets along with the variables they changed",
+    long_about = "List changesets along with the variables they changed\n
+Every command changing variables records its changes as a changeset, most recent first. Staged \
+                  changes are listed on top."
+)]
+pub struct Cmd {
+    /// Only list the changesets of this environment
+    env: Option<String>,
+
+    /// Maximum number of changesets to list
+    #[arg(short = 'n', long)]
+    limit: Option<u32>,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::log(&mut std::io::stdout(), db, self.env.as_deref(), self.limit).await
+    }
+}
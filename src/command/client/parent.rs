@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Manage the environments an environment inherits from",
+    long_about = "Manage the environments an environment inherits from\n
+An environment resolves its variables by layering its own values on top of the ones of its \
+                  parents. Parents are resolved in the order they were added: a variable found in \
+                  an earlier parent takes precedence over the same variable found in a later one."
+)]
+pub struct Cmd {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Make an environment inherit the variables of another one
+    Add {
+        /// Environment that inherits the variables
+        env: String,
+
+        /// Environment whose variables are inherited
+        parent: String,
+    },
+
+    /// Stop an environment from inheriting the variables of another one
+    Remove {
+        /// Environment that inherits the variables
+        env: String,
+
+        /// Environment whose variables are inherited
+        parent: String,
+    },
+
+    /// List the parents of an environment, in resolution order
+    List {
+        /// Environment whose parents should be listed
+        env: String,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match &self.action {
+            Action::Add { env, parent } => ops::add_parent(db, env, parent).await,
+            Action::Remove { env, parent } => ops::remove_parent(db, env, parent).await,
+            Action::List { env } => ops::list_parents(&mut std::io::stdout(), db, env).await,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{BufRead, IsTerminal, Read};
+
+use anyhow::{Result, bail};
+use clap::{Parser, Subcommand};
+
+use crate::db::EnvelopeDb;
+use crate::{ops, utils};
+
+#[derive(Parser)]
+#[command(
+    about = "Permanently remove values from history",
+    long_about = "Permanently remove values from history\n
+Unlike delete, purged values are removed from history and from the database file, so that they \
+                  cannot be recovered, not even from a locked envelope. Use it to get rid of \
+                  secrets that were stored by mistake."
+)]
+pub struct Cmd {
+    #[command(subcommand)]
+    target: Target,
+}
+
+#[derive(Subcommand)]
+enum Target {
+    /// Purge specific revisions of a variable, as numbered by history
+    Revision {
+        /// Environment of the variable
+        env: String,
+
+        /// Name of the variable
+        key: String,
+
+        /// Revisions to purge
+        #[arg(required = true)]
+        revisions: Vec<i64>,
+    },
+
+    /// Purge every revision holding a value, in every environment
+    Value {
+        /// Value to purge
+        value: Option<String>,
+
+        /// Read the value from stdin, keeping it out of shell history
+        #[arg(short, long)]
+        stdin: bool,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let revisions = match &self.target {
+            Target::Revision {
+                env,
+                key,
+                revisions,
+            } => ops::find_revisions(db, env, key, revisions).await?,
+            Target::Value { value, stdin } => {
+                let value = match (value, stdin) {
+                    (Some(_), true) => bail!("cannot specify both a value argument and --stdin"),
+                    (None, false) => bail!("a value argument or --stdin is required"),
+                    (Some(value), false) => value.clone(),
+                    // the value keeps its whitespace, which is part of the
+                    // secret, and spans every line when piped
+                    (None, true) => {
+                        let mut value = String::new();
+                        match std::io::stdin().is_terminal() {
+                            true => std::io::stdin().lock().read_line(&mut value)?,
+                            false => std::io::stdin().read_to_string(&mut value)?,
+                        };
+                        utils::strip_line_break(&value).to_owned()
+                    }
+                };
+                ops::find_value(db, &value).await?
+            }
+        };
+
+        if revisions.is_empty() {
+            println!("nothing to purge");
+            return Ok(());
+        }
+
+        let mut stdout = std::io::stdout();
+        ops::write_revisions(&mut stdout, &revisions)?;
+        if db.confirm("Permanently remove these revisions?")? {
+            ops::purge(db, &revisions).await?;
+            println!("purged {} revisions", revisions.len());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+/// Redo the last undone command
+#[derive(Parser)]
+pub struct Cmd {}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::redo(&mut std::io::stdout(), db).await
+    }
+}
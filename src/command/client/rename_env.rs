@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Rename an environment",
+    long_about = "Rename an environment\n
+The whole history of the environment is preserved, along with the parents it inherits from and the \
+                  environments inheriting from it. Fails if the new environment already exists, \
+                  or if a variable references the environment with ${env:KEY}."
+)]
+pub struct Cmd {
+    /// Environment to rename
+    env: String,
+
+    /// New environment name
+    new_env: String,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::rename_env(db, &self.env, &self.new_env).await
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Rename an environment variable",
+    long_about = "Rename an environment variable\n
+The whole history of the variable is preserved. Without --env the variable is renamed in every \
+                  environment. Fails if the new variable already exists in any of the \
+                  environments where the variable is renamed, or if the variable is referenced \
+                  there by another one."
+)]
+pub struct Cmd {
+    /// Name of the environment variable to rename
+    key: String,
+
+    /// New name of the environment variable
+    new_key: String,
+
+    /// Only rename the variable in this environment
+    #[arg(short, long)]
+    env: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::rename_key(db, self.env.as_deref(), &self.key, &self.new_key).await
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+/// Discard the staged changes
+#[derive(Parser)]
+pub struct Cmd {}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match ops::reset(db).await? {
+            0 => println!("nothing to discard"),
+            n => println!("discarded {n} changes"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::{Change, EnvelopeDb};
+use crate::{ops, time};
+
+#[derive(Parser)]
+#[command(
+    about = "Restore an environment to an earlier point in time",
+    long_about = "Restore an environment to an earlier point in time\n
+Every variable of the environment is set back to the value it had at that point in time, and \
+                  variables added afterwards are deleted. History is preserved: the restore is \
+                  recorded as new changes. The changes are previewed like `diff env@time env` \
+                  before being applied, with unset variables shown as (unset) and base64 encoded \
+                  values followed by (base64)."
+)]
+pub struct Cmd {
+    /// Environment to restore
+    env: String,
+
+    /// Point in time to restore: a unix timestamp, a UTC date such as
+    /// "2024-01-31 12:30" or a duration ago such as "2h"
+    #[arg(long, value_parser = time::parse_at)]
+    to: i64,
+
+    /// Message describing the changes, listed by the log command
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Show the values of secrets in the preview instead of masking them
+    #[arg(long)]
+    reveal: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        if !ops::restore_preview(&mut stdout, db, &self.env, self.to, self.reveal).await? {
+            println!("nothing to restore");
+            return Ok(());
+        }
+
+        if db.confirm("Restore these changes?")? {
+            let change = Change {
+                message: self.message.as_deref(),
+                stage: false,
+            };
+            ops::restore(db, &self.env, self.to, change).await?;
+        }
+
+        Ok(())
+    }
+}
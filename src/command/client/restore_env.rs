@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Restore a dropped environment from the trash",
+    long_about = "Restore a dropped environment from the trash\n
+The environment is restored with its whole history. If it was dropped more than once, the most \
+                  recently dropped one is restored. Fails if an environment with the same name \
+                  exists."
+)]
+pub struct Cmd {
+    /// Environment to restore
+    env: String,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::restore_env(db, &self.env).await
+    }
+}
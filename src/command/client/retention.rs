@@ -0,0 +1,62 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::db::EnvelopeDb;
+use crate::{ops, time};
+
+#[derive(Parser)]
+#[command(
+    about = "Manage how much history the gc command keeps",
+    long_about = "Manage how much history the gc command keeps\n
+Without retention settings, gc keeps the whole history of an environment except for revisions \
+                  repeating the previous value of their variable. With both --keep and \
+                  --newer-than, a revision is removed as soon as it is beyond either of them: \
+                  only the revisions among the last ones of their variable and newer than the \
+                  duration are kept. Retention settings are changed like the environment itself: \
+                  they are refused on read-only environments and must be confirmed on protected \
+                  ones."
+)]
+pub struct Cmd {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Set how much of the history of an environment is kept
+    Set {
+        /// Environment whose history is pruned
+        env: String,
+
+        /// Number of revisions to keep per variable
+        #[arg(long)]
+        keep: Option<u32>,
+
+        /// Keep the revisions newer than this duration, such as "30d"
+        #[arg(long, value_parser = time::parse_duration)]
+        newer_than: Option<u64>,
+    },
+
+    /// Keep the whole history of an environment
+    Clear {
+        /// Environment whose retention settings are removed
+        env: String,
+    },
+
+    /// List the retention settings of every environment
+    List,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match &self.action {
+            Action::Set {
+                env,
+                keep,
+                newer_than,
+            } => ops::set_retention(db, env, *keep, *newer_than).await,
+            Action::Clear { env } => ops::clear_retention(db, env).await,
+            Action::List => ops::list_retention(&mut std::io::stdout(), db).await,
+        }
+    }
+}
@@ -1,23 +1,50 @@
 use anyhow::Result;
 use clap::Parser;
 
-use crate::db::EnvelopeDb;
+use crate::db::{Change, EnvelopeDb};
 use crate::ops;
 
-/// Revert environment variable to previous value
 #[derive(Parser)]
+#[command(
+    about = "Revert environment variable to previous value",
+    long_about = "Revert environment variable to previous value\n
+The reverted value is recorded as a new revision, so the history of the variable is preserved. \
+                  Revisions are numbered by the history command."
+)]
 pub struct Cmd {
     /// Environment of the key you wish to revert
     env: String,
 
     /// Environment key you wish to revert
     key: String,
+
+    /// Revision to restore, as numbered by the history command
+    #[arg(long, conflicts_with = "steps")]
+    to: Option<i64>,
+
+    /// Number of revisions to go back
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(i64).range(1..))]
+    steps: i64,
+
+    /// Delete the latest revision from history instead of recording a new one
+    #[arg(long, conflicts_with_all = ["to", "steps"])]
+    discard: bool,
+
+    /// Message describing the changes, listed by the log command
+    #[arg(short, long, conflicts_with = "discard")]
+    message: Option<String>,
 }
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ops::revert(db, &self.env, &self.key).await?;
-
-        Ok(())
+        let change = Change {
+            message: self.message.as_deref(),
+            stage: false,
+        };
+        match (self.discard, self.to) {
+            (true, _) => ops::revert_discard(db, &self.env, &self.key).await,
+            (false, Some(rev)) => ops::revert_to(db, &self.env, &self.key, rev, change).await,
+            (false, None) => ops::revert_steps(db, &self.env, &self.key, self.steps, change).await,
+        }
     }
 }
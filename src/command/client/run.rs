@@ -1,8 +1,12 @@
-use anyhow::{Context, Result, ensure};
+use std::ffi::{OsStr, OsString};
+
+use anyhow::{Context, Result, bail, ensure};
 use clap::Parser;
 
-use crate::db::EnvelopeDb;
+use crate::db::{EnvelopeDb, Resolve};
+use crate::schema::{self, Schema};
 use crate::subproc::ChildProcess;
+use crate::{ops, time};
 
 #[derive(Parser)]
 pub struct Cmd {
@@ -13,6 +17,21 @@ pub struct Cmd {
     #[arg(short, long)]
     isolated: bool,
 
+    /// Do not include variables inherited from parent environments
+    #[arg(long)]
+    no_inherit: bool,
+
+    /// Read the environment as it was at a point in time: a unix timestamp, a
+    /// UTC date such as "2024-01-31 12:30" or a duration ago such as "2h"
+    #[arg(long, value_parser = time::parse_at)]
+    at: Option<i64>,
+
+    /// Check the environment against .envelope.schema.toml first, refusing
+    /// to run the command if it does not match, and pass the defaults of the
+    /// missing variables
+    #[arg(long)]
+    validate: bool,
+
     /// Command and arguments to run
     #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
     args: Vec<String>,
@@ -20,19 +39,43 @@ pub struct Cmd {
 
 impl Cmd {
     pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
-        ensure!(
-            db.env_exists(&self.env).await?,
-            "environment '{}' does not exist",
-            self.env
-        );
-
         let (command, remaining_args) = self.args.split_first().context("no command provided")?;
 
-        let env_vars = db.list_kv_in_env(&self.env).await?;
+        let resolve = Resolve {
+            inherit: !self.no_inherit,
+            at: self.at,
+        };
+        let mut masker = ops::Masker::load(db, false).await?;
+        let mut env_vars = ops::read_expanded(db, &self.env, resolve, &mut masker).await?;
+        if self.validate {
+            let schema = Schema::load(&schema::schema_path()?)?.with_context(|| {
+                format!("no schema found, expected {}", schema::SCHEMA_FILENAME)
+            })?;
+
+            let problems = ops::check_vars(&schema, &env_vars);
+            if !problems.is_empty() {
+                ops::write_problems(&mut std::io::stderr(), &problems, &masker)?;
+                bail!("environment '{}' does not match the schema", self.env);
+            }
+            ops::apply_defaults(&schema, &self.env, &mut env_vars);
+        }
 
-        let env_refs: Vec<(&str, &str)> = env_vars
+        let values = env_vars
             .iter()
-            .map(|row| (row.key.as_str(), row.value.as_str()))
+            .map(|row| {
+                let bytes = row.bytes()?;
+                ensure!(
+                    !bytes.contains(&0),
+                    "value of '{}' contains a NUL byte, which cannot be passed to a command",
+                    row.key
+                );
+                os_value(bytes.into_owned())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let env_refs: Vec<(&str, &OsStr)> = env_vars
+            .iter()
+            .zip(&values)
+            .map(|(row, value)| (row.key.as_str(), value.as_os_str()))
             .collect();
 
         let args_refs: Vec<&str> = remaining_args.iter().map(String::as_str).collect();
@@ -48,3 +91,19 @@ impl Cmd {
         Ok(())
     }
 }
+
+/// Converts the bytes of a value, which may not be UTF-8 once decoded from
+/// base64, to the value of an environment variable
+#[cfg(unix)]
+fn os_value(bytes: Vec<u8>) -> Result<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_value(bytes: Vec<u8>) -> Result<OsString> {
+    String::from_utf8(bytes)
+        .map(OsString::from)
+        .context("values that are not UTF-8 are only supported on unix")
+}
@@ -0,0 +1,61 @@
+use std::io::IsTerminal;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::{EnvelopeDb, Resolve};
+use crate::format::{Shell, ShellEnv};
+use crate::{ops, time};
+
+/// Print statements setting the variables of an environment in a shell
+#[derive(Parser)]
+pub struct Cmd {
+    /// Environment to read the variables from
+    env: String,
+
+    /// Shell to write the statements for, defaults to the one named by $SHELL
+    #[arg(short, long)]
+    shell: Option<Shell>,
+
+    /// Print statements removing the variables of the environment instead
+    #[arg(long)]
+    unset: bool,
+
+    /// Do not include variables inherited from parent environments
+    #[arg(long)]
+    no_inherit: bool,
+
+    /// Read the environment as it was at a point in time: a unix timestamp, a
+    /// UTC date such as "2024-01-31 12:30" or a duration ago such as "2h"
+    #[arg(long, value_parser = time::parse_at)]
+    at: Option<i64>,
+
+    /// Show the values of secrets instead of masking them. Secrets are only
+    /// masked when printed to a terminal.
+    #[arg(long)]
+    reveal: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let resolve = Resolve {
+            inherit: !self.no_inherit,
+            at: self.at,
+        };
+        let formatter = ShellEnv {
+            shell: self.shell.unwrap_or_else(Shell::detect),
+            unset: self.unset,
+        };
+
+        ops::export(
+            &mut std::io::stdout(),
+            db,
+            &self.env,
+            &formatter,
+            resolve,
+            // statements are meant to be evaluated by the shell
+            self.reveal || !std::io::stdout().is_terminal(),
+        )
+        .await
+    }
+}
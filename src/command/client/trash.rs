@@ -0,0 +1,49 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::db::EnvelopeDb;
+use crate::{ops, time};
+
+#[derive(Parser)]
+#[command(
+    about = "Manage dropped environments",
+    long_about = "Manage dropped environments\n
+Dropped environments are kept in the trash for 30 days, or for the duration set in \
+                  ENVELOPE_TRASH_RETENTION, and can be restored with restore-env until gc deletes \
+                  them."
+)]
+pub struct Cmd {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Permanently delete the environments in the trash
+    Empty {
+        /// Only delete the environments dropped longer ago than this
+        /// duration, such as "7d"
+        #[arg(long, value_parser = time::parse_duration)]
+        older_than: Option<u64>,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        match &self.action {
+            Action::Empty { older_than } => {
+                let before = older_than.map(|d| time::now() - d as i64);
+                if !ops::list_trash(&mut std::io::stdout(), db, before).await? {
+                    println!("nothing to delete");
+                    return Ok(());
+                }
+
+                if db.confirm("Permanently delete these environments?")? {
+                    ops::empty_trash(db, before).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
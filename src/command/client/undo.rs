@@ -0,0 +1,30 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Undo the last command",
+    long_about = "Undo the last command\n
+Every command changing the envelope is recorded as an operation, and undo reverts all of its \
+                  changes, in every environment and variable it touched. Undone commands can be \
+                  redone with redo until another command changes the envelope. Only the last 20 \
+                  commands are kept, and purge and gc clear them."
+)]
+pub struct Cmd {
+    /// List the recent commands instead, most recent first
+    #[arg(short, long)]
+    list: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        match self.list {
+            true => ops::list_operations(&mut stdout, db).await,
+            false => ops::undo(&mut stdout, db).await,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use clap::Parser;
+
+use super::ChangeArgs;
+use crate::db::EnvelopeDb;
+use crate::ops;
+
+#[derive(Parser)]
+#[command(
+    about = "Unset a variable inherited from a parent environment",
+    long_about = "Unset a variable inherited from a parent environment\n
+The variable is hidden from the environment even if one of its parents defines it. Delete the \
+                  variable from the environment to inherit it again."
+)]
+pub struct Cmd {
+    /// Environment in which the variable should be unset
+    env: String,
+
+    /// Name of the environment variable
+    key: String,
+
+    #[command(flatten)]
+    change: ChangeArgs,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        ops::unset_var(db, &self.env, &self.key, self.change.change()).await
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+
+use crate::db::EnvelopeDb;
+use crate::ops;
+use crate::schema::{self, Schema};
+
+#[derive(Parser)]
+#[command(
+    about = "Check environments against the schema",
+    long_about = "Check environments against the schema\n
+The schema is read from .envelope.schema.toml and declares the expected variables along with their \
+                  type, whether they are required and their default. Missing variables are shown \
+                  in red, variables that are not declared in green and values of the wrong type \
+                  in gray."
+)]
+pub struct Cmd {
+    /// Environment to check
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    env: Option<String>,
+
+    /// Check every environment
+    #[arg(long)]
+    all: bool,
+
+    /// Show the values of secrets instead of masking them
+    #[arg(long)]
+    reveal: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, db: &EnvelopeDb) -> Result<()> {
+        let schema = Schema::load(&schema::schema_path()?)?
+            .with_context(|| format!("no schema found, expected {}", schema::SCHEMA_FILENAME))?;
+
+        let envs = match &self.env {
+            Some(env) => vec![env.clone()],
+            None => db
+                .list_environments()
+                .await?
+                .into_iter()
+                .map(|e| e.env)
+                .collect(),
+        };
+
+        let invalid =
+            ops::validate(&mut std::io::stdout(), db, &schema, &envs, self.reveal).await?;
+        match invalid.len() {
+            0 => Ok(()),
+            1 => bail!("environment '{}' does not match the schema", invalid[0]),
+            n => bail!("{n} environments do not match the schema"),
+        }
+    }
+}
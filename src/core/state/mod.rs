@@ -89,7 +89,7 @@ mod tests {
     use super::*;
     use crate::core::crypto::encrypt;
     use crate::core::crypto::header::EnvelopeFileHeader;
-    use crate::db::EnvelopeDb;
+    use crate::db::{EnvelopeDb, Resolve};
 
     // -- state transition tests (all in-memory, no disk) --
 
@@ -105,7 +105,11 @@ mod tests {
         let locked = envelope.lock("password").await.unwrap();
         let restored = locked.unlock("password").await.unwrap();
 
-        let rows = restored.db().list_kv_in_env("prod").await.unwrap();
+        let rows = restored
+            .db()
+            .list_kv_in_env("prod", Resolve::default())
+            .await
+            .unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].key, "API_KEY");
         assert_eq!(rows[0].value, "secret123");
@@ -164,15 +168,10 @@ mod tests {
     #[tokio::test]
     async fn test_open_in_memory_with_invalid_bytes() {
         // SqliteOwnedBuf accepts arbitrary bytes, but the resulting database
-        // is corrupt and queries will fail.
-        let envelope = UnlockedEnvelope::open_in_memory(b"not a sqlite database")
+        // is corrupt and bringing its schema up to date will fail.
+        let err = UnlockedEnvelope::open_in_memory(b"not a sqlite database")
             .await
-            .unwrap();
-        let err = envelope
-            .db()
-            .get_active_kv_in_env()
-            .await
-            .expect_err("querying a corrupt database should fail");
+            .expect_err("opening a corrupt database should fail");
         assert!(
             err.to_string().to_lowercase().contains("failed"),
             "error should indicate a query failure, got: {err}"
@@ -199,7 +198,11 @@ mod tests {
         envelope.store(&path).await.unwrap();
 
         let restored = UnlockedEnvelope::open_at(&path).await.unwrap();
-        let rows = restored.db().list_kv_in_env("prod").await.unwrap();
+        let rows = restored
+            .db()
+            .list_kv_in_env("prod", Resolve::default())
+            .await
+            .unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].key, "API_KEY");
         assert_eq!(rows[0].value, "secret123");
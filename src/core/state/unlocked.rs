@@ -2,6 +2,7 @@ use std::fs;
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use sqlx::SqlitePool;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteOwnedBuf, SqlitePoolOptions};
 
 use super::LockedEnvelope;
@@ -44,10 +45,7 @@ impl UnlockedEnvelope {
             .await
             .context("failed to open .envelope database")?;
 
-        sqlx::migrate!("./migrations")
-            .run(&pool)
-            .await
-            .context("failed to initialize database schema")?;
+        migrate(&pool).await?;
 
         Ok(Self {
             db: EnvelopeDb::with(pool),
@@ -60,7 +58,8 @@ impl UnlockedEnvelope {
 
     /// Opens an existing unlocked envelope database at `path`.
     ///
-    /// Unlike [`Self::init`], this does not create the file or run migrations.
+    /// Unlike [`Self::init`], this does not create the file. Migrations added
+    /// since the envelope was created are applied.
     pub(super) async fn open_at(path: &Path) -> Result<Self> {
         let pool = SqlitePoolOptions::new()
             .max_connections(1)
@@ -75,6 +74,8 @@ impl UnlockedEnvelope {
             .await
             .context("failed to open .envelope database")?;
 
+        migrate(&pool).await?;
+
         Ok(Self {
             db: EnvelopeDb::with(pool),
         })
@@ -110,6 +111,8 @@ impl UnlockedEnvelope {
             .context("failed to load database into memory")?;
         drop(conn);
 
+        migrate(&pool).await?;
+
         Ok(Self {
             db: EnvelopeDb::with(pool),
         })
@@ -139,3 +142,12 @@ impl UnlockedEnvelope {
         Ok(())
     }
 }
+
+/// Applies the migrations that have not been applied to the database yet, so
+/// that envelopes created by older versions are brought up to date
+async fn migrate(pool: &SqlitePool) -> Result<()> {
+    sqlx::migrate!("./migrations")
+        .run(pool)
+        .await
+        .context("failed to initialize database schema")
+}
@@ -1,12 +1,37 @@
-use anyhow::{Context, Result, ensure};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result, bail, ensure};
+use base64::prelude::{BASE64_STANDARD, Engine};
 use model::*;
-use sqlx::SqlitePool;
+use sqlx::{AssertSqlSafe, SqlitePool};
+pub use transaction::EnvelopeTx;
 
 pub(crate) mod model;
+mod transaction;
 
 #[derive(Debug)]
 pub struct EnvelopeDb {
     db: SqlitePool,
+    /// row changes that left no trace in the database: the ones of
+    /// transactions that were rolled back and of operations that changed
+    /// nothing
+    discarded: AtomicU64,
+    /// asks whether to proceed, see [`Self::on_confirm`]
+    confirm: OnceLock<fn(&str) -> Result<bool>>,
+}
+
+/// Number of operations kept for [`EnvelopeDb::undo`]
+const UNDO_LIMIT: i64 = 20;
+
+/// An operation being recorded, see [`EnvelopeDb::begin_operation`]
+pub struct Recording {
+    id: i64,
+    /// row changes made so far when the recording began
+    changes: i64,
+    /// `discarded` when the recording began
+    discarded: u64,
 }
 
 /// enum specifying truncation logic used in listing functions
@@ -15,21 +40,123 @@ pub enum Truncate {
     Max(u32),
 }
 
+/// Options controlling how the variables of an environment are resolved
+#[derive(Debug, Clone, Copy)]
+pub struct Resolve {
+    /// layer the variables of parent environments below the ones of the
+    /// environment itself
+    pub inherit: bool,
+    /// unix timestamp at which the environment should be read, `None` reads
+    /// the latest values
+    pub at: Option<i64>,
+}
+
+/// Options controlling how the changes made by a transaction are recorded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Change<'a> {
+    /// message describing the changeset
+    pub message: Option<&'a str>,
+    /// stage the changes instead of applying them, they are applied by
+    /// [`EnvelopeDb::commit_staged`]
+    pub stage: bool,
+}
+
+/// Changes to the metadata of an environment, fields left to `None` are kept
+/// as they are. An empty description or owner clears it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetaUpdate<'a> {
+    pub description: Option<&'a str>,
+    pub owner: Option<&'a str>,
+    pub protected: Option<bool>,
+    pub read_only: Option<bool>,
+}
+
+impl Default for Resolve {
+    fn default() -> Self {
+        Self {
+            inherit: true,
+            at: None,
+        }
+    }
+}
+
 #[cfg(test)]
 impl EnvelopeDb {
     pub fn get_pool(&self) -> &SqlitePool {
         &self.db
     }
+
+    /// inserts `key` and `value` to environment `env`
+    pub async fn insert(&self, env: &str, key: &str, var: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.insert(env, key, var).await?;
+        tx.commit().await
+    }
+
+    /// soft deletes all variables in an environment
+    pub async fn soft_delete_env(&self, env: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.soft_delete_env(env).await?;
+        tx.commit().await
+    }
+
+    /// soft deletes all variables with specified key
+    pub async fn soft_delete_keys(&self, key: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.soft_delete_keys(key).await?;
+        tx.commit().await
+    }
+
+    /// Soft deletes specified variable in passed environment. Deleting a
+    /// tombstone makes the variable inherited from parents visible again.
+    pub async fn soft_delete_key_in_env(&self, env: &str, key: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.soft_delete_key_in_env(env, key).await?;
+        tx.commit().await
+    }
 }
 
 impl EnvelopeDb {
     pub(crate) fn with(db: SqlitePool) -> Self {
-        EnvelopeDb { db }
+        EnvelopeDb {
+            db,
+            discarded: AtomicU64::new(0),
+            confirm: OnceLock::new(),
+        }
+    }
+
+    /// Sets how changes to protected environments and other destructive
+    /// changes are confirmed: `confirm` is asked the given question and
+    /// returns whether to proceed. They are refused until it is set.
+    pub fn on_confirm(&self, confirm: fn(&str) -> Result<bool>) {
+        // the first callback is kept, it is the same for a whole command
+        let _ = self.confirm.set(confirm);
     }
 
-    /// checks if an environment exists in the database
+    /// Asks `prompt` with the callback set by [`Self::on_confirm`], returning
+    /// whether to proceed
+    pub fn confirm(&self, prompt: &str) -> Result<bool> {
+        match self.confirm.get() {
+            Some(confirm) => confirm(prompt),
+            None => Ok(false),
+        }
+    }
+
+    /// begins a transaction, see [`EnvelopeTx`]
+    pub async fn begin(&self) -> Result<EnvelopeTx<'_>> {
+        EnvelopeTx::begin(self, Change::default()).await
+    }
+
+    /// begins a transaction whose changes are recorded as described by
+    /// `change`
+    pub async fn begin_with<'a>(&'a self, change: Change<'a>) -> Result<EnvelopeTx<'a>> {
+        EnvelopeTx::begin(self, change).await
+    }
+
+    /// checks if an environment exists in the database. An environment that
+    /// has no variables of its own but inherits from a parent exists as well.
     pub async fn env_exists(&self, env: &str) -> Result<bool> {
-        sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM environments WHERE env = $1)")
+        sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM environment WHERE env = $1)")
             .bind(env)
             .fetch_one(&self.db)
             .await
@@ -44,278 +171,1487 @@ impl EnvelopeDb {
         )
         .fetch_all(&self.db)
         .await
-        .context("failed to read environment variables")
+        .context("failed to read environment variables")
+    }
+
+    /// moves environment to the trash, along with its whole history and the
+    /// list of parents it inherits from. Environments inheriting from it keep
+    /// their parent relation, so that it is resolved again once restored.
+    pub async fn trash_env(&self, env: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        ensure!(
+            tx.env_exists(env).await?,
+            "environment '{env}' does not exist"
+        );
+
+        tx.guard(env).await?;
+
+        let id = tx
+            .execute(
+                sqlx::query(
+                    r"INSERT INTO trash
+                        (env, description, owner, protected, read_only, env_created_at)
+                    SELECT env, description, owner, protected, read_only, created_at
+                    FROM environment
+                    WHERE env = $1",
+                )
+                .bind(env),
+            )
+            .await
+            .context("failed to drop environment")?
+            .last_insert_rowid();
+
+        for query in [
+            r"INSERT INTO trash_environments
+                (trash_id, seq, key, value, tombstone, base64, created_at, changeset)
+            SELECT $2, revision, key, value, tombstone, base64, created_at, changeset
+            FROM environments
+            WHERE env = $1",
+            r"INSERT INTO trash_parents (trash_id, parent, position, created_at, removed_at)
+            SELECT $2, parent, position, created_at, removed_at
+            FROM env_parents
+            WHERE env = $1",
+            r"DELETE FROM environments WHERE env = $1",
+            r"DELETE FROM env_parents WHERE env = $1",
+            r"DELETE FROM environment WHERE env = $1",
+        ] {
+            tx.execute(sqlx::query(query).bind(env).bind(id))
+                .await
+                .context("failed to drop environment")?;
+        }
+
+        tx.commit().await
+    }
+
+    /// lists the environments in the trash dropped at or before `before`, or
+    /// all of them if `before` is `None`, most recently dropped first
+    pub async fn list_trash(&self, before: Option<i64>) -> Result<Vec<TrashedEnv>> {
+        sqlx::query_as(
+            r"SELECT
+                env
+                , CAST(DATETIME(dropped_at, 'unixepoch') AS text) AS dropped_at
+            FROM trash
+            WHERE $1 IS NULL OR trash.dropped_at <= $1
+            ORDER BY trash.dropped_at DESC, id DESC",
+        )
+        .bind(before)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to list trash")
+    }
+
+    /// restores the most recently dropped environment named `env` from the
+    /// trash. In order for this to work, `env` must not be present.
+    pub async fn restore_trashed_env(&self, env: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        ensure!(
+            !tx.env_exists(env).await?,
+            "environment '{env}' already exists"
+        );
+
+        let id: Option<i64> = sqlx::query_scalar(
+            r"SELECT id
+            FROM trash
+            WHERE env = $1
+            ORDER BY dropped_at DESC, id DESC
+            LIMIT 1",
+        )
+        .bind(env)
+        .fetch_optional(tx.conn())
+        .await
+        .context("failed to restore environment")?;
+        let Some(id) = id else {
+            bail!("environment '{env}' is not in the trash");
+        };
+
+        for query in [
+            r"INSERT INTO environment (env, description, owner, protected, read_only, created_at)
+            SELECT env, description, owner, protected, read_only, COALESCE(env_created_at, dropped_at)
+            FROM trash
+            WHERE id = $2",
+            r"INSERT INTO environments (env, key, value, tombstone, base64, created_at, changeset)
+            SELECT $1, key, value, tombstone, base64, created_at, changeset
+            FROM trash_environments
+            WHERE trash_id = $2
+            ORDER BY seq",
+            r"INSERT INTO env_parents (env, parent, position, created_at, removed_at)
+            SELECT $1, parent, position, created_at, removed_at
+            FROM trash_parents
+            WHERE trash_id = $2",
+            r"DELETE FROM trash WHERE env = $1 AND id = $2",
+        ] {
+            tx.execute(sqlx::query(query).bind(env).bind(id))
+                .await
+                .context("failed to restore environment")?;
+        }
+
+        tx.commit().await
+    }
+
+    /// permanently deletes the environments dropped at or before `before`, or
+    /// the whole trash if `before` is `None`. Returns the number of deleted
+    /// environments.
+    pub async fn empty_trash(&self, before: Option<i64>) -> Result<u64> {
+        let mut tx = self.begin().await?;
+        let res = tx
+            .execute(
+                sqlx::query(r"DELETE FROM trash WHERE $1 IS NULL OR dropped_at <= $1").bind(before),
+            )
+            .await
+            .context("failed to empty trash")?;
+
+        // descriptions are kept while an environment can still be restored
+        tx.execute(sqlx::query(
+            r"DELETE FROM descriptions
+            WHERE
+                env IS NOT NULL AND
+                env NOT IN (SELECT env FROM environment) AND
+                env NOT IN (SELECT env FROM trash)",
+        ))
+        .await
+        .context("failed to empty trash")?;
+
+        tx.commit().await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// makes `env` inherit the variables of `parent`. The new parent has the
+    /// lowest precedence among the existing parents of `env`.
+    pub async fn add_parent(&self, env: &str, parent: &str) -> Result<()> {
+        ensure!(
+            env != parent,
+            "environment '{env}' cannot inherit from itself"
+        );
+        ensure!(
+            self.env_exists(parent).await?,
+            "environment '{parent}' does not exist"
+        );
+        ensure!(
+            !self.parents(env).await?.iter().any(|p| p == parent),
+            "environment '{env}' already inherits from '{parent}'"
+        );
+        ensure!(
+            !self.ancestors(parent).await?.iter().any(|a| a == env),
+            "environment '{parent}' already inherits from '{env}', adding it as a parent would \
+             create a cycle"
+        );
+
+        let mut tx = self.begin().await?;
+        tx.guard(env).await?;
+        tx.execute(
+            sqlx::query(
+                r"INSERT INTO env_parents (env, parent, position)
+                SELECT $1, $2, COALESCE(MAX(position) + 1, 0)
+                FROM env_parents
+                WHERE env = $1",
+            )
+            .bind(env)
+            .bind(parent),
+        )
+        .await
+        .context("failed to add parent environment")?;
+
+        tx.commit().await
+    }
+
+    /// stops `env` from inheriting the variables of `parent`. The parent is
+    /// kept as removed, so that `env` still inherits from it when read at an
+    /// earlier point in time.
+    pub async fn remove_parent(&self, env: &str, parent: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        let res = tx
+            .execute(
+                sqlx::query(
+                    r"UPDATE env_parents
+                    SET removed_at = strftime('%s', 'now')
+                    WHERE
+                        env = $1 AND
+                        parent = $2 AND
+                        removed_at IS NULL",
+                )
+                .bind(env)
+                .bind(parent),
+            )
+            .await
+            .context("failed to remove parent environment")?;
+
+        ensure!(
+            res.rows_affected() > 0,
+            "environment '{env}' does not inherit from '{parent}'"
+        );
+        tx.guard(env).await?;
+
+        tx.commit().await
+    }
+
+    /// lists the direct parents of `env`, in resolution order
+    pub async fn parents(&self, env: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            r"SELECT parent
+            FROM env_parents
+            WHERE env = $1 AND removed_at IS NULL
+            ORDER BY position",
+        )
+        .bind(env)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to list parent environments")
+    }
+
+    /// lists `env` and every environment it transitively inherits from
+    async fn ancestors(&self, env: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            r"WITH RECURSIVE lineage(env) AS (
+                SELECT $1
+                UNION
+                SELECT p.parent
+                FROM env_parents p
+                JOIN lineage l ON p.env = l.env
+                WHERE p.removed_at IS NULL
+            )
+            SELECT env FROM lineage",
+        )
+        .bind(env)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to resolve parent environments")
+    }
+
+    /// duplicates `source_env` in a new environment `target_env`.
+    /// In order for this to work, `tgt_env` must not be present.
+    pub async fn duplicate_env(&self, source_env: &str, target_env: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.duplicate_env(source_env, target_env).await?;
+        tx.commit().await
+    }
+
+    /// renames `env` to `new_env`, rewriting its whole history and every
+    /// parent relation it is part of, dropped environments included.
+    /// `new_env` must not be present, and no variable may reference `env`.
+    pub async fn rename_env(&self, env: &str, new_env: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        ensure!(
+            tx.env_exists(env).await?,
+            "environment '{env}' does not exist"
+        );
+        ensure!(
+            !tx.env_exists(new_env).await?,
+            "environment '{new_env}' already exists"
+        );
+        let referencing = tx.referencing(|_, ref_env, _| ref_env == Some(env)).await?;
+        ensure!(
+            referencing.is_empty(),
+            "environment '{env}' is referenced by {}, update them first",
+            referencing.join(", ")
+        );
+        tx.guard(env).await?;
+
+        for query in [
+            r"UPDATE environment SET env = $2 WHERE env = $1",
+            r"UPDATE environments SET env = $2 WHERE env = $1",
+            r"UPDATE env_parents SET env = $2 WHERE env = $1",
+            r"UPDATE env_parents SET parent = $2 WHERE parent = $1",
+            // dropped environments inherit from it again once restored
+            r"UPDATE trash_parents SET parent = $2 WHERE parent = $1",
+            r"UPDATE staged SET env = $2 WHERE env = $1",
+            r"UPDATE retention SET env = $2 WHERE env = $1",
+            r"UPDATE descriptions SET env = $2 WHERE env = $1",
+        ] {
+            tx.execute(sqlx::query(query).bind(env).bind(new_env))
+                .await
+                .context("failed to rename environment")?;
+        }
+
+        tx.commit().await
+    }
+
+    /// renames `key` to `new_key`, rewriting its whole history and its staged
+    /// changes, either in `env` only or in every environment. `new_key` must
+    /// not be present, nor staged, in any of the environments where `key` is
+    /// renamed, and no variable may reference `key` there: with
+    /// `${env:KEY}`, or with `${KEY}` from `env` or from the environments
+    /// inheriting from it.
+    pub async fn rename_key(&self, env: Option<&str>, key: &str, new_key: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+
+        let exists: bool = sqlx::query_scalar(
+            r"SELECT EXISTS(
+                SELECT 1
+                FROM (SELECT env, key FROM environments UNION ALL SELECT env, key FROM staged)
+                WHERE
+                    key = upper($1) AND
+                    ($2 IS NULL OR env = $2)
+            )",
+        )
+        .bind(key)
+        .bind(env)
+        .fetch_one(tx.conn())
+        .await
+        .context("failed to rename variable")?;
+
+        match env {
+            Some(env) => ensure!(
+                exists,
+                "variable '{}' does not exist in environment '{env}'",
+                key.to_uppercase()
+            ),
+            None => ensure!(exists, "variable '{}' does not exist", key.to_uppercase()),
+        }
+
+        let taken: Vec<String> = sqlx::query_scalar(
+            r"WITH stored AS (
+                SELECT env, key FROM environments
+                UNION ALL
+                SELECT env, key FROM staged
+            )
+            SELECT DISTINCT env
+            FROM stored
+            WHERE
+                key = upper($3) AND
+                env IN (
+                    SELECT env
+                    FROM stored
+                    WHERE
+                        key = upper($1) AND
+                        ($2 IS NULL OR env = $2)
+                )
+            ORDER BY env",
+        )
+        .bind(key)
+        .bind(env)
+        .bind(new_key)
+        .fetch_all(tx.conn())
+        .await
+        .context("failed to rename variable")?;
+
+        // `${KEY}` is resolved in the environment being read, which may
+        // inherit `key` from `env`
+        let inheriting: Vec<String> = sqlx::query_scalar(
+            r"WITH RECURSIVE inheriting (env) AS (
+                SELECT $1
+                UNION
+                SELECT p.env
+                FROM env_parents p
+                JOIN inheriting i ON p.parent = i.env
+                WHERE p.removed_at IS NULL
+            )
+            SELECT env FROM inheriting",
+        )
+        .bind(env)
+        .fetch_all(tx.conn())
+        .await
+        .context("failed to rename variable")?;
+        let referencing = tx
+            .referencing(|holder, ref_env, ref_key| {
+                ref_key.eq_ignore_ascii_case(key)
+                    && match (env, ref_env) {
+                        (None, _) => true,
+                        (Some(env), Some(ref_env)) => ref_env == env,
+                        (Some(_), None) => inheriting.iter().any(|e| e == holder),
+                    }
+            })
+            .await?;
+        ensure!(
+            referencing.is_empty(),
+            "variable '{}' is referenced by {}, update them first",
+            key.to_uppercase(),
+            referencing.join(", ")
+        );
+
+        let envs: Vec<String> = sqlx::query_scalar(
+            r"SELECT DISTINCT env
+            FROM (SELECT env, key FROM environments UNION ALL SELECT env, key FROM staged)
+            WHERE
+                key = upper($1) AND
+                ($2 IS NULL OR env = $2)
+            ORDER BY env",
+        )
+        .bind(key)
+        .bind(env)
+        .fetch_all(tx.conn())
+        .await
+        .context("failed to rename variable")?;
+        for env in envs {
+            tx.guard(&env).await?;
+        }
+
+        ensure!(
+            taken.is_empty(),
+            "variable '{}' already exists in {}",
+            new_key.to_uppercase(),
+            taken
+                .iter()
+                .map(|e| format!("'{e}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        // pending changes are committed under the new name
+        for query in [
+            r"UPDATE environments
+            SET key = upper($3)
+            WHERE
+                key = upper($1) AND
+                ($2 IS NULL OR env = $2)",
+            r"UPDATE staged
+            SET key = upper($3)
+            WHERE
+                key = upper($1) AND
+                ($2 IS NULL OR env = $2)",
+        ] {
+            tx.execute(sqlx::query(query).bind(key).bind(env).bind(new_key))
+                .await
+                .context("failed to rename variable")?;
+        }
+
+        // the classification follows the values to their new name, and is
+        // only kept under the old one if other environments still use it
+        let classification = match env {
+            Some(_) => {
+                r"INSERT OR IGNORE INTO classification (key, secret)
+                SELECT upper($2), secret
+                FROM classification
+                WHERE key = upper($1)"
+            }
+            None => r"UPDATE OR REPLACE classification SET key = upper($2) WHERE key = upper($1)",
+        };
+        tx.execute(sqlx::query(classification).bind(key).bind(new_key))
+            .await
+            .context("failed to rename variable")?;
+
+        // descriptions of `new_key` are replaced, like its values would be
+        tx.execute(
+            sqlx::query(
+                r"UPDATE OR REPLACE descriptions
+                SET key = upper($3)
+                WHERE
+                    key = upper($1) AND
+                    ($2 IS NULL OR env = $2)",
+            )
+            .bind(key)
+            .bind(env)
+            .bind(new_key),
+        )
+        .await
+        .context("failed to rename variable")?;
+
+        tx.commit().await
+    }
+
+    /// lists all active variables in an environment, including the ones
+    /// inherited from its parents
+    pub async fn list_kv_in_env(&self, env: &str, resolve: Resolve) -> Result<Vec<EnvironmentRow>> {
+        self.list_kv_in_env_alt(env, Truncate::None, "da", resolve)
+            .await
+    }
+
+    /// list all key-value for specified environment, this also takes an option
+    /// to truncate the max length of values returned.
+    ///
+    /// When `resolve.inherit` is set, variables of parent environments are
+    /// layered below the ones of `env`: the first environment in the lineage
+    /// that defines or unsets a key wins. The `env` column of each returned
+    /// row is the environment the value was resolved from.
+    ///
+    /// When `resolve.at` is set, the environment is reconstructed as it was at
+    /// that moment, ignoring values and parents added afterwards and
+    /// inheriting from the parents removed since.
+    pub async fn list_kv_in_env_alt(
+        &self,
+        env: &str,
+        truncate: Truncate,
+        sort: &str,
+        resolve: Resolve,
+    ) -> Result<Vec<EnvironmentRow>> {
+        let max = match truncate {
+            Truncate::None => None,
+            Truncate::Max(x) => Some(x),
+        };
+
+        sqlx::query_as(
+            r"WITH RECURSIVE lineage(env, path) AS (
+                SELECT $1, ''
+                UNION ALL
+                -- path sorts the lineage depth-first, following parent positions
+                SELECT p.parent, l.path || printf('%04d', p.position)
+                FROM env_parents p
+                JOIN lineage l ON p.env = l.env
+                WHERE
+                    $4 AND
+                    ($5 IS NULL OR p.created_at <= $5) AND
+                    (p.removed_at IS NULL OR p.removed_at > $5)
+            ),
+            latest AS (
+                SELECT
+                    *
+                    , ROW_NUMBER() OVER (PARTITION BY env, key ORDER BY revision DESC) AS rn
+                FROM environments
+                WHERE
+                    env IN (SELECT env FROM lineage) AND
+                    -- `at` is in whole seconds, values written within that
+                    -- second are included
+                    ($5 IS NULL OR created_at < $5 + 1)
+            ),
+            resolved AS (
+                SELECT
+                    e.env
+                    , e.key
+                    , e.value
+                    , e.base64
+                    , e.revision
+                    , e.created_at
+                    , ROW_NUMBER() OVER (PARTITION BY e.key ORDER BY l.path) AS rn
+                FROM latest e
+                JOIN lineage l ON l.env = e.env
+                WHERE
+                    e.rn = 1 AND
+                    (e.value IS NOT NULL OR e.tombstone)
+            )
+            SELECT
+                env
+                , key
+                , base64
+                , created_at
+                , CASE
+                    WHEN $2 IS NULL THEN value
+                    ELSE substr(value, 0, $2 + 1)
+                END AS value
+            FROM resolved
+            WHERE
+                rn = 1 AND
+                value IS NOT NULL
+            ORDER BY
+                CASE
+                    WHEN $3 = 'k' THEN key END,
+                CASE
+                    WHEN $3 = 'kd' THEN key END DESC,
+                CASE
+                    WHEN $3 = 'v' THEN value END,
+                CASE
+                    WHEN $3 = 'vd' THEN value END DESC,
+                CASE
+                    WHEN $3 = 'd' THEN created_at END,
+                CASE
+                    WHEN $3 = 'dd' THEN created_at END DESC,
+                CASE
+                    WHEN $3 = 'dd' THEN revision END DESC,
+                created_at ASC,
+                revision ASC",
+        )
+        .bind(env)
+        .bind(max)
+        .bind(sort)
+        .bind(resolve.inherit)
+        .bind(resolve.at)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to list variables")
+    }
+
+    // lists environments present in the database. Environments that only contain
+    // deletes variables or that only inherit from other environments will be
+    // listed as well.
+    pub async fn list_environments(&self) -> Result<Vec<Environment>> {
+        sqlx::query_as(
+            r"SELECT env
+            FROM environment
+            ORDER BY created_at, env",
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("failed to list environments")
+    }
+
+    /// Reads the metadata of `env`, if it exists
+    pub async fn environment_meta(&self, env: &str) -> Result<Option<EnvironmentMeta>> {
+        sqlx::query_as(
+            r"SELECT
+                env
+                , description
+                , owner
+                , protected
+                , read_only
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+            FROM environment
+            WHERE env = $1",
+        )
+        .bind(env)
+        .fetch_optional(&self.db)
+        .await
+        .context("failed to read environment metadata")
+    }
+
+    /// Lists the metadata of every environment, in the order of
+    /// [`Self::list_environments`]
+    pub async fn list_environment_meta(&self) -> Result<Vec<EnvironmentMeta>> {
+        sqlx::query_as(
+            r"SELECT
+                env
+                , description
+                , owner
+                , protected
+                , read_only
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+            FROM environment
+            ORDER BY created_at, env",
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("failed to list environments")
+    }
+
+    /// Updates the metadata of `env`. Changing a protected environment must be
+    /// confirmed, but read-only environments can be changed, so that the flag
+    /// can be cleared.
+    pub async fn set_meta(&self, env: &str, update: MetaUpdate<'_>) -> Result<()> {
+        let mut tx = self.begin().await?;
+        ensure!(
+            tx.env_exists(env).await?,
+            "environment '{env}' does not exist"
+        );
+
+        tx.confirm_protected(env).await?;
+        tx.execute(
+            sqlx::query(
+                r"UPDATE environment SET
+                    description = IIF($2 IS NULL, description, NULLIF($2, ''))
+                    , owner = IIF($3 IS NULL, owner, NULLIF($3, ''))
+                    , protected = COALESCE($4, protected)
+                    , read_only = COALESCE($5, read_only)
+                WHERE env = $1",
+            )
+            .bind(env)
+            .bind(update.description)
+            .bind(update.owner)
+            .bind(update.protected)
+            .bind(update.read_only),
+        )
+        .await
+        .context("failed to update environment metadata")?;
+
+        tx.commit().await
+    }
+
+    /// Returns the description of every variable of `env` having one, by key.
+    /// Descriptions of `env` take precedence over the ones of every
+    /// environment.
+    pub async fn descriptions(&self, env: &str) -> Result<BTreeMap<String, String>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r"SELECT key, description
+            FROM descriptions
+            WHERE env = $1 OR env IS NULL
+            ORDER BY key, env IS NOT NULL",
+        )
+        .bind(env)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to read descriptions")?;
+
+        // the description of `env` comes last and wins
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Returns the description of `key` set for `env`, or for every
+    /// environment if `env` is `None`, without falling back
+    pub async fn description(&self, env: Option<&str>, key: &str) -> Result<Option<String>> {
+        sqlx::query_scalar(
+            r"SELECT description
+            FROM descriptions
+            WHERE env IS $1 AND key = upper($2)",
+        )
+        .bind(env)
+        .bind(key)
+        .fetch_optional(&self.db)
+        .await
+        .context("failed to read description")
+    }
+
+    /// Sets or removes the description of `key`, see
+    /// [`EnvelopeTx::set_description`]
+    pub async fn set_description(
+        &self,
+        env: Option<&str>,
+        key: &str,
+        description: Option<&str>,
+    ) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.set_description(env, key, description).await?;
+
+        tx.commit().await
+    }
+
+    /// Lists the variables of `env` whose state differs from the one they had
+    /// at unix timestamp `at`, which restoring it would change, see
+    /// [`EnvelopeTx::restore_env`]
+    pub async fn restorable(&self, env: &str, at: i64) -> Result<Vec<Restorable>> {
+        // nothing is written, the transaction is rolled back when dropped
+        self.begin().await?.restorable(env, at).await
+    }
+
+    /// This returns the diff between the two specified environments, ordered by
+    /// key. Each environment is resolved according to its own options first.
+    pub async fn diff(
+        &self,
+        e1: &str,
+        r1: Resolve,
+        e2: &str,
+        r2: Resolve,
+    ) -> Result<Vec<EnvironmentDiff>> {
+        for e in [e1, e2] {
+            ensure!(
+                self.env_exists(e).await?,
+                "environment '{e}' does not exist"
+            );
+        }
+
+        let mut first: BTreeMap<String, String> = self
+            .list_kv_in_env_alt(e1, Truncate::None, "k", r1)
+            .await?
+            .into_iter()
+            .map(|row| (row.key, row.value))
+            .collect();
+        let second = self.list_kv_in_env_alt(e2, Truncate::None, "k", r2).await?;
+
+        let mut diffs = Vec::new();
+        for EnvironmentRow { key, value, .. } in second {
+            match first.remove(&key) {
+                None => diffs.push(EnvironmentDiff::InSecond(key, value)),
+                Some(v) if v != value => diffs.push(EnvironmentDiff::Different(key, v, value)),
+                Some(_) => {}
+            }
+        }
+        diffs.extend(
+            first
+                .into_iter()
+                .map(|(k, v)| EnvironmentDiff::InFirst(k, v)),
+        );
+        diffs.sort_by(|a, b| a.key().cmp(b.key()));
+
+        Ok(diffs)
+    }
+
+    /// Reverts a specific key-value in a specific env by deleting the most
+    /// recent entry in database. Unlike [`EnvelopeTx::revert_to`] this destroys
+    /// the history entry.
+    pub async fn revert(&self, env: &str, key: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.guard(env).await?;
+        tx.execute(
+            sqlx::query(
+                r"DELETE FROM environments
+                WHERE revision = (
+                    SELECT MAX(revision)
+                    FROM environments
+                    WHERE
+                        env = $1 AND
+                        key = upper($2)
+                )",
+            )
+            .bind(env)
+            .bind(key),
+        )
+        .await
+        .context("failed to revert variable")?;
+
+        tx.commit().await
+    }
+
+    /// Lists all values for an env key pair, oldest first, along with their
+    /// revision
+    pub async fn history(&self, env: &str, key: &str) -> Result<Vec<EnvironmentRowNullable>> {
+        sqlx::query_as(
+            "SELECT
+                env
+                , key
+                , value
+                , tombstone
+                , revision
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+            FROM environments
+            WHERE
+                env = $1 AND
+                key = upper($2)
+            ORDER BY revision",
+        )
+        .bind(env)
+        .bind(key)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to read variable history")
+    }
+
+    /// Lists the latest `limit` changesets, or all of them if `limit` is
+    /// `None`, most recent first. Only the changesets touching `env` are
+    /// listed if specified, and the changesets whose values were all purged or
+    /// dropped are left out.
+    pub async fn log(&self, env: Option<&str>, limit: Option<u32>) -> Result<Vec<Changeset>> {
+        #[derive(sqlx::FromRow)]
+        struct LogRow {
+            id: i64,
+            message: Option<String>,
+            created_at: String,
+            env: String,
+            key: String,
+        }
+
+        let rows: Vec<LogRow> = sqlx::query_as(
+            r"WITH changed AS (
+                SELECT DISTINCT changeset, env, key
+                FROM environments
+                WHERE
+                    changeset IS NOT NULL AND
+                    ($1 IS NULL OR env = $1)
+            ), listed AS (
+                SELECT DISTINCT changeset AS id
+                FROM changed
+                ORDER BY changeset DESC
+                LIMIT COALESCE($2, -1)
+            )
+            SELECT
+                changesets.id
+                , changesets.message
+                , CAST(DATETIME(changesets.created_at, 'unixepoch') AS text) AS created_at
+                , changed.env
+                , changed.key
+            FROM changesets
+            JOIN listed ON listed.id = changesets.id
+            JOIN changed ON changed.changeset = changesets.id
+            ORDER BY changesets.id DESC, changed.env, changed.key",
+        )
+        .bind(env)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to read changesets")?;
+
+        let mut log: Vec<Changeset> = Vec::new();
+        for row in rows {
+            let key = ChangedKey {
+                env: row.env,
+                key: row.key,
+            };
+            match log.last_mut() {
+                Some(changeset) if changeset.id == row.id => changeset.keys.push(key),
+                _ => log.push(Changeset {
+                    id: row.id,
+                    message: row.message,
+                    created_at: row.created_at,
+                    keys: vec![key],
+                }),
+            }
+        }
+
+        Ok(log)
+    }
+
+    /// Lists the variables changed by the staged changes, see
+    /// [`Change::stage`]
+    pub async fn staged(&self, env: Option<&str>) -> Result<Vec<ChangedKey>> {
+        sqlx::query_as(
+            r"SELECT DISTINCT env, key
+            FROM staged
+            WHERE $1 IS NULL OR env = $1
+            ORDER BY env, key",
+        )
+        .bind(env)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to read staged changes")
+    }
+
+    /// Applies every staged change as a single changeset described by
+    /// `message`. Returns the number of applied changes.
+    pub async fn commit_staged(&self, message: Option<&str>) -> Result<u64> {
+        let mut tx = self
+            .begin_with(Change {
+                message,
+                stage: false,
+            })
+            .await?;
+
+        let envs: Vec<String> = sqlx::query_scalar(r"SELECT DISTINCT env FROM staged ORDER BY env")
+            .fetch_all(tx.conn())
+            .await
+            .context("failed to read staged changes")?;
+        if envs.is_empty() {
+            return Ok(0);
+        }
+        for env in envs {
+            tx.guard(&env).await?;
+        }
+
+        let changeset = tx
+            .changeset()
+            .await
+            .context("failed to commit staged changes")?;
+        let res = tx
+            .execute(
+                sqlx::query(
+                    r"INSERT INTO environments (env, key, value, tombstone, base64, changeset)
+                    SELECT env, key, value, tombstone, base64, $1
+                    FROM staged
+                    ORDER BY seq",
+                )
+                .bind(changeset),
+            )
+            .await
+            .context("failed to commit staged changes")?;
+        tx.execute(sqlx::query(r"DELETE FROM staged"))
+            .await
+            .context("failed to commit staged changes")?;
+        tx.commit().await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Discards every staged change. Returns the number of discarded changes.
+    pub async fn reset_staged(&self) -> Result<u64> {
+        let res = sqlx::query(r"DELETE FROM staged")
+            .execute(&self.db)
+            .await
+            .context("failed to discard staged changes")?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Lists the stored revisions `revs` of `key` in `env`
+    pub async fn find_revisions(
+        &self,
+        env: &str,
+        key: &str,
+        revs: &[i64],
+    ) -> Result<Vec<Revision>> {
+        sqlx::query_as(
+            r"SELECT
+                revision AS rowid
+                , env
+                , key
+                , revision
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+                , FALSE AS trashed
+                , FALSE AS staged
+            FROM environments
+            WHERE
+                env = $1 AND
+                key = upper($2) AND
+                revision IN (SELECT value FROM json_each($3))
+            ORDER BY revision",
+        )
+        .bind(env)
+        .bind(key)
+        .bind(json_array(revs))
+        .fetch_all(&self.db)
+        .await
+        .context("failed to find revisions")
+    }
+
+    /// Lists every stored revision, in any environment, whose value is `value`,
+    /// decoded if it is base64 encoded. Revisions of dropped environments
+    /// still in the trash and staged changes are included.
+    pub async fn find_value(&self, value: &str) -> Result<Vec<Revision>> {
+        sqlx::query_as(
+            r"SELECT rowid, env, key, revision, created_at, trashed, staged
+            FROM (
+                SELECT
+                    revision AS rowid
+                    , env
+                    , key
+                    , value
+                    , base64
+                    , revision
+                    , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+                    , FALSE AS trashed
+                    , FALSE AS staged
+                FROM environments
+                UNION ALL
+                SELECT
+                    e.rowid AS rowid
+                    , t.env
+                    , e.key
+                    , e.value
+                    , e.base64
+                    -- revision the value had before being dropped
+                    , e.seq AS revision
+                    , CAST(DATETIME(e.created_at, 'unixepoch') AS text) AS created_at
+                    , TRUE AS trashed
+                    , FALSE AS staged
+                FROM trash_environments e
+                JOIN trash t ON t.id = e.trash_id
+                UNION ALL
+                SELECT
+                    seq AS rowid
+                    , env
+                    , key
+                    , value
+                    , base64
+                    , seq AS revision
+                    , '' AS created_at
+                    , FALSE AS trashed
+                    , TRUE AS staged
+                FROM staged
+            )
+            -- base64 encoded values are stored encoded
+            WHERE (NOT base64 AND value = $1) OR (base64 AND value = $2)
+            ORDER BY staged, trashed, env, key, revision",
+        )
+        .bind(value)
+        .bind(BASE64_STANDARD.encode(value))
+        .fetch_all(&self.db)
+        .await
+        .context("failed to find revisions")
     }
 
-    /// inserts `key` and `value` to environment `env`
-    pub async fn insert(&self, env: &str, key: &str, var: &str) -> Result<()> {
-        sqlx::query(r"INSERT INTO environments (env, key, value) VALUES ($1, upper($2), $3)")
-            .bind(env)
-            .bind(key)
-            .bind(var)
+    /// Permanently deletes `revisions` from history, then vacuums the database
+    /// so that their content does not survive in free pages of the file or of
+    /// its serialized form.
+    pub async fn purge(&self, revisions: &[Revision]) -> Result<()> {
+        let (staged, stored): (Vec<&Revision>, Vec<&Revision>) =
+            revisions.iter().partition(|r| r.staged);
+        let (trashed, live): (Vec<&Revision>, Vec<&Revision>) =
+            stored.into_iter().partition(|r| r.trashed);
+        let rowids = |revisions: Vec<&Revision>| {
+            json_array(&revisions.iter().map(|r| r.rowid).collect::<Vec<_>>())
+        };
+
+        // the revisions are all purged, or none is
+        let mut tx = self.begin().await?;
+        tx.execute(sqlx::query("PRAGMA secure_delete = ON"))
+            .await
+            .context("failed to enable secure delete")?;
+
+        for (query, revisions) in [
+            (
+                r"DELETE FROM environments WHERE rowid IN (SELECT value FROM json_each($1))",
+                live,
+            ),
+            (
+                r"DELETE FROM trash_environments WHERE rowid IN (SELECT value FROM json_each($1))",
+                trashed,
+            ),
+            (
+                r"DELETE FROM staged WHERE seq IN (SELECT value FROM json_each($1))",
+                staged,
+            ),
+        ] {
+            tx.execute(sqlx::query(query).bind(rowids(revisions)))
+                .await
+                .context("failed to purge revisions")?;
+        }
+
+        // the undo log holds copies of the purged values
+        tx.execute(sqlx::query(r"DELETE FROM operations"))
+            .await
+            .context("failed to clear undo history")?;
+        tx.commit().await?;
+
+        // VACUUM cannot run within a transaction
+        sqlx::query("VACUUM")
             .execute(&self.db)
             .await
-            .context("failed to add variable")?;
+            .context("failed to vacuum database")?;
 
         Ok(())
     }
 
-    /// soft deletes all variables in an environment
-    pub async fn soft_delete_env(&self, env: &str) -> Result<()> {
-        sqlx::query(
-            r"INSERT INTO environments (env, key, value)
-            SELECT env, key, NULL
-            FROM active_envs
-            WHERE env = $1",
+    /// Sets how much of the history of `env` is kept by [`Self::gc`]: a
+    /// revision is kept while it is among the latest `keep_revisions` of its
+    /// variable and newer than `keep_newer_than` seconds, so that with both
+    /// set it is removed as soon as it is beyond either
+    pub async fn set_retention(
+        &self,
+        env: &str,
+        keep_revisions: Option<u32>,
+        keep_newer_than: Option<u64>,
+    ) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.guard(env).await?;
+        tx.execute(
+            sqlx::query(
+                r"INSERT INTO retention (env, keep_revisions, keep_newer_than)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (env) DO UPDATE SET
+                    keep_revisions = excluded.keep_revisions,
+                    keep_newer_than = excluded.keep_newer_than",
+            )
+            .bind(env)
+            .bind(keep_revisions)
+            .bind(keep_newer_than.map(|secs| secs as i64)),
         )
-        .bind(env)
-        .execute(&self.db)
         .await
-        .context("failed to delete environment")?;
+        .context("failed to set retention")?;
 
-        Ok(())
+        tx.commit().await
     }
 
-    /// soft deletes all variables with specified key
-    pub async fn soft_delete_keys(&self, key: &str) -> Result<()> {
-        sqlx::query(
-            r"INSERT INTO environments (env, key, value)
-            SELECT env, key, NULL
-            FROM active_envs
-            WHERE key = UPPER($1)",
-        )
-        .bind(key)
-        .execute(&self.db)
-        .await
-        .context("failed to delete variable")?;
+    /// Removes the retention settings of `env`, so that its history is kept
+    /// whole. Returns whether `env` had any.
+    pub async fn clear_retention(&self, env: &str) -> Result<bool> {
+        let mut tx = self.begin().await?;
+        tx.guard(env).await?;
+        let res = tx
+            .execute(sqlx::query(r"DELETE FROM retention WHERE env = $1").bind(env))
+            .await
+            .context("failed to clear retention")?;
+        tx.commit().await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Lists the variables classified as secret or not, overriding the
+    /// classification guessed from their name
+    pub async fn classification(&self) -> Result<Vec<(String, bool)>> {
+        sqlx::query_as(r"SELECT key, secret FROM classification ORDER BY key")
+            .fetch_all(&self.db)
+            .await
+            .context("failed to read classification")
+    }
+
+    /// Classifies `key` as secret or not, or removes its classification if
+    /// `secret` is `None`
+    pub async fn set_classification(&self, key: &str, secret: Option<bool>) -> Result<()> {
+        let query = match secret {
+            Some(secret) => sqlx::query(
+                r"INSERT INTO classification (key, secret)
+                VALUES (upper($1), $2)
+                ON CONFLICT (key) DO UPDATE SET secret = excluded.secret",
+            )
+            .bind(key)
+            .bind(secret),
+            None => sqlx::query(r"DELETE FROM classification WHERE key = upper($1)").bind(key),
+        };
+
+        query
+            .execute(&self.db)
+            .await
+            .context("failed to classify variable")?;
 
         Ok(())
     }
 
-    /// Soft deletes specified variable in passed environment
-    pub async fn soft_delete_key_in_env(&self, env: &str, key: &str) -> Result<()> {
-        sqlx::query(
-            r"INSERT INTO environments (env, key, value)
-            SELECT env, key, NULL
-            FROM active_envs
-            WHERE
-                env = $1 AND
-                key = UPPER($2)",
+    /// Lists the retention settings of every environment
+    pub async fn list_retention(&self) -> Result<Vec<Retention>> {
+        sqlx::query_as(
+            r"SELECT env, keep_revisions, keep_newer_than
+            FROM retention
+            ORDER BY env",
         )
-        .bind(env)
-        .bind(key)
-        .execute(&self.db)
+        .fetch_all(&self.db)
         .await
-        .context("failed to delete variable from environment")?;
-
-        Ok(())
+        .context("failed to read retention")
     }
 
-    /// deletes environment from database entirely
-    pub async fn delete_env(&self, env: &str) -> Result<()> {
-        sqlx::query(
-            r"DELETE
-            FROM environments
-            WHERE env = $1",
+    /// Lists the revisions of `env`, or of every environment, that [`Self::gc`]
+    /// would remove at unix timestamp `now`: the ones repeating the previous
+    /// value of their variable, and the ones beyond the retention settings of
+    /// their environment. The latest revision of a variable is only removed
+    /// when it repeats the previous one, so no current value is ever lost.
+    /// Read-only environments keep their whole history.
+    pub async fn collectable(&self, env: Option<&str>, now: i64) -> Result<Vec<Revision>> {
+        sqlx::query_as(
+            r"WITH marked AS (
+                SELECT
+                    *
+                    , LAG(revision) OVER w IS NOT NULL AND
+                        value IS LAG(value) OVER w AND
+                        tombstone = LAG(tombstone) OVER w AND
+                        base64 = LAG(base64) OVER w AS duplicate
+                FROM environments
+                WHERE
+                    ($1 IS NULL OR env = $1) AND
+                    env NOT IN (SELECT env FROM environment WHERE read_only)
+                WINDOW w AS (PARTITION BY env, key ORDER BY revision)
+            ), ranked AS (
+                SELECT
+                    *
+                    , ROW_NUMBER() OVER (PARTITION BY env, key ORDER BY revision DESC) AS rn
+                FROM marked
+                WHERE NOT duplicate
+            )
+            SELECT
+                revision AS rowid
+                , env
+                , key
+                , revision
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+                , FALSE AS trashed
+                , FALSE AS staged
+            FROM marked
+            WHERE duplicate
+            UNION ALL
+            SELECT
+                ranked.revision AS rowid
+                , ranked.env
+                , ranked.key
+                , ranked.revision
+                , CAST(DATETIME(ranked.created_at, 'unixepoch') AS text) AS created_at
+                , FALSE AS trashed
+                , FALSE AS staged
+            FROM ranked
+            JOIN retention ON retention.env = ranked.env
+            WHERE
+                ranked.rn > 1 AND (
+                    ranked.rn > retention.keep_revisions OR
+                    ranked.created_at < $2 - retention.keep_newer_than
+                )
+            ORDER BY env, key, revision",
         )
         .bind(env)
-        .execute(&self.db)
+        .bind(now)
+        .fetch_all(&self.db)
         .await
-        .context("failed to drop environment")?;
-
-        Ok(())
+        .context("failed to find collectable revisions")
     }
 
-    /// duplicates `source_env` in a new environment `target_env`.
-    /// In order for this to work, `tgt_env` must not be present.
-    pub async fn duplicate_env(&self, source_env: &str, target_env: &str) -> Result<()> {
-        ensure!(
-            self.env_exists(source_env).await?,
-            "source environment '{source_env}' does not exist"
-        );
+    /// Removes `revisions` from history along with the changesets left empty,
+    /// then vacuums the database to shrink it. The undo history is cleared.
+    /// Pruning a protected environment must be confirmed.
+    pub async fn gc(&self, revisions: &[Revision]) -> Result<()> {
+        let rowids = json_array(&revisions.iter().map(|r| r.rowid).collect::<Vec<_>>());
 
-        ensure!(
-            !self.env_exists(target_env).await?,
-            "target environment '{target_env}' already exists"
-        );
+        let mut tx = self.begin().await?;
+        for revision in revisions {
+            tx.guard(&revision.env).await?;
+        }
+        for query in [
+            r"DELETE FROM environments WHERE revision IN (SELECT value FROM json_each($1))",
+            r"DELETE FROM changesets
+            WHERE
+                id NOT IN (SELECT changeset FROM environments WHERE changeset IS NOT NULL) AND
+                id NOT IN (SELECT changeset FROM trash_environments WHERE changeset IS NOT NULL)",
+        ] {
+            tx.execute(sqlx::query(query).bind(&rowids))
+                .await
+                .context("failed to remove revisions")?;
+        }
+        // the undo log holds copies of the removed revisions
+        tx.execute(sqlx::query(r"DELETE FROM operations"))
+            .await
+            .context("failed to clear undo history")?;
+        tx.commit().await?;
 
-        sqlx::query(
-            r"INSERT INTO environments (env, key, value)
-            SELECT $2, key, value
-            FROM active_envs
-            WHERE env = $1",
-        )
-        .bind(source_env)
-        .bind(target_env)
-        .execute(&self.db)
-        .await
-        .context("failed to duplicate environment")?;
+        sqlx::query("VACUUM")
+            .execute(&self.db)
+            .await
+            .context("failed to vacuum database")?;
 
         Ok(())
     }
 
-    /// lists all active variables in an environment
-    pub async fn list_kv_in_env(&self, env: &str) -> Result<Vec<EnvironmentRow>> {
-        self.list_kv_in_env_alt(env, Truncate::None, "da").await
+    /// Starts recording the changes made by `command` as an operation that can
+    /// be undone, until [`Self::end_operation`] is called
+    pub async fn begin_operation(&self, command: &str) -> Result<Recording> {
+        let changes = self.raw_total_changes().await?;
+        let discarded = self.discarded.load(Ordering::Relaxed);
+
+        let mut tx = self.begin().await?;
+        let id = tx
+            .execute(sqlx::query(r"INSERT INTO operations (command) VALUES ($1)").bind(command))
+            .await
+            .context("failed to record operation")?
+            .last_insert_rowid();
+        for query in [
+            r"DELETE FROM recording",
+            r"INSERT INTO recording (operation, redo) VALUES ($1, FALSE)",
+        ] {
+            tx.execute(sqlx::query(query).bind(id))
+                .await
+                .context("failed to record operation")?;
+        }
+        tx.commit().await?;
+
+        Ok(Recording {
+            id,
+            changes,
+            discarded,
+        })
     }
 
-    /// list all key-value for specified environment, this also takes an option
-    /// to truncate the max length of values returned
-    pub async fn list_kv_in_env_alt(
-        &self,
-        env: &str,
-        truncate: Truncate,
-        sort: &str,
-    ) -> Result<Vec<EnvironmentRow>> {
-        let max = match truncate {
-            Truncate::None => None,
-            Truncate::Max(x) => Some(x),
-        };
+    /// Stops recording the operation. An operation that changed nothing is
+    /// forgotten, otherwise the operations undone so far can no longer be
+    /// redone, and only the latest [`UNDO_LIMIT`] operations are kept.
+    pub async fn end_operation(&self, recording: Recording) -> Result<()> {
+        let mut tx = self.begin().await?;
+        tx.execute(sqlx::query(r"DELETE FROM recording"))
+            .await
+            .context("failed to record operation")?;
+
+        let recorded: bool =
+            sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM undolog WHERE operation = $1)")
+                .bind(recording.id)
+                .fetch_one(tx.conn())
+                .await
+                .context("failed to record operation")?;
+        if recorded {
+            for query in [
+                r"DELETE FROM operations WHERE undone",
+                r"DELETE FROM operations
+                WHERE id NOT IN (SELECT id FROM operations ORDER BY id DESC LIMIT $1)",
+            ] {
+                tx.execute(sqlx::query(query).bind(UNDO_LIMIT))
+                    .await
+                    .context("failed to record operation")?;
+            }
+            return tx.commit().await;
+        }
+
+        tx.execute(sqlx::query(r"DELETE FROM operations WHERE id = $1").bind(recording.id))
+            .await
+            .context("failed to record operation")?;
+        tx.commit().await?;
+
+        // the changes of the operation were only bookkeeping
+        let changes = self.raw_total_changes().await? - recording.changes;
+        let discarded = self.discarded.load(Ordering::Relaxed) - recording.discarded;
+        self.discarded
+            .fetch_add(changes as u64 - discarded, Ordering::Relaxed);
+
+        Ok(())
+    }
 
+    /// Lists the latest `limit` operations, most recent first
+    pub async fn operations(&self, limit: u32) -> Result<Vec<Operation>> {
         sqlx::query_as(
             r"SELECT
-                env
-                , key
-                , created_at
-                , CASE
-                    WHEN $2 IS NULL THEN value
-                    ELSE substr(value, 0, $2 + 1)
-                END AS value
-            FROM active_envs
-            WHERE env = $1
-            ORDER BY
-                CASE
-                    WHEN $3 = 'k' THEN key END,
-                CASE
-                    WHEN $3 = 'kd' THEN key END DESC,
-                CASE
-                    WHEN $3 = 'v' THEN value END,
-                CASE
-                    WHEN $3 = 'vd' THEN value END DESC,
-                CASE
-                    WHEN $3 = 'd' THEN created_at END,
-                CASE
-                    WHEN $3 = 'dd' THEN created_at END DESC,
-                created_at ASC",
+                id
+                , command
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+                , undone
+            FROM operations
+            ORDER BY id DESC
+            LIMIT $1",
         )
-        .bind(env)
-        .bind(max)
-        .bind(sort)
+        .bind(limit)
         .fetch_all(&self.db)
         .await
-        .context("failed to list variables")
+        .context("failed to read operations")
     }
 
-    // lists environments present in the database. Environments that only contain
-    // deletes variables will be listed as well.
-    pub async fn list_environments(&self) -> Result<Vec<Environment>> {
-        sqlx::query_as(r"SELECT DISTINCT env FROM environments ORDER BY created_at")
-            .fetch_all(&self.db)
-            .await
-            .context("failed to list environments")
+    /// Reverts every change made by the latest operation that was not undone.
+    /// Returns the undone operation, if any.
+    pub async fn undo(&self) -> Result<Option<Operation>> {
+        self.replay(false).await
     }
 
-    /// This returns the diff between the two specified environments
-    pub async fn diff(&self, e1: &str, e2: &str) -> Result<Vec<EnvironmentDiff>> {
-        for e in [e1, e2] {
-            ensure!(
-                self.env_exists(e).await?,
-                "environment '{e}' does not exist"
-            );
-        }
+    /// Applies again the changes of the operation undone last. Returns the
+    /// redone operation, if any.
+    pub async fn redo(&self) -> Result<Option<Operation>> {
+        self.replay(true).await
+    }
 
-        sqlx::query_as(
-            r"WITH base AS (
-                SELECT key, value, env
-                FROM active_envs
-                WHERE env IN ($1, $2)
-            )
-            SELECT e1.key, e1.value, null AS diff, '+' AS type
-            FROM base e1
-            WHERE e1.env = $1
-            AND NOT EXISTS (
-                SELECT 1 FROM base e2
-                WHERE e2.env = $2
-                AND e2.key = e1.key
-            )
-            UNION ALL
-            SELECT e2.key, e2.value, null AS diff, '-' AS type
-            FROM base e2
-            WHERE e2.env = $2
-            AND NOT EXISTS (
-                SELECT 1 FROM base e1
-                WHERE e1.env = $1
-                AND e1.key = e2.key
-            )
-            UNION ALL
-            SELECT e1.key, e1.value, e2.value as diff, '/' AS type
-            FROM base e1
-            JOIN base e2 ON e1.key = e2.key
-            WHERE e1.env = $1
-            AND e2.env = $2
-            AND e1.value != e2.value
-            ORDER BY key",
+    /// Runs the undo statements of the latest operation, or the redo
+    /// statements of the earliest undone one, while recording the statements
+    /// reverting them. The changed environments are guarded like the changes
+    /// replayed, except that metadata changes are allowed on read-only ones.
+    async fn replay(&self, redo: bool) -> Result<Option<Operation>> {
+        let mut tx = self.begin().await?;
+
+        let operation: Option<Operation> = sqlx::query_as(
+            r"SELECT
+                id
+                , command
+                , CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
+                , undone
+            FROM operations
+            WHERE undone = $1
+            ORDER BY
+                CASE WHEN $1 THEN id ELSE -id END
+            LIMIT 1",
         )
-        .bind(e1)
-        .bind(e2)
-        .fetch_all(&self.db)
+        .bind(redo)
+        .fetch_optional(tx.conn())
         .await
-        .context("failed to diff environments")
-    }
+        .context("failed to read operations")?;
+        let Some(mut operation) = operation else {
+            return Ok(None);
+        };
 
-    /// Reverts a specific key-value in a specific env by deleting the most
-    /// recent entry in database
-    pub async fn revert(&self, env: &str, key: &str) -> Result<()> {
-        sqlx::query(
-            r"DELETE FROM environments
-            WHERE
-                env = $1 AND
-                key = upper($2) AND
-                EXISTS (
-                    SELECT 1
-                    FROM latest_envs
-                    WHERE
-                        latest_envs.env = environments.env AND
-                        latest_envs.key = environments.key AND
-                        latest_envs.created_at = environments.created_at AND
-                        (latest_envs.value IS environments.value OR latest_envs.value = environments.value)
-                )",
+        let statements: Vec<String> = sqlx::query_scalar(
+            r"SELECT sql
+            FROM undolog
+            WHERE operation = $1 AND redo = $2
+            ORDER BY seq DESC",
         )
-        .bind(env)
-        .bind(key)
-        .execute(&self.db)
+        .bind(operation.id)
+        .bind(redo)
+        .fetch_all(tx.conn())
         .await
-        .context("failed to revert variable")?;
-
-        Ok(())
-    }
-
-    /// Lists all values for an env key pair
-    pub async fn history(&self, env: &str, key: &str) -> Result<Vec<EnvironmentRowNullable>> {
-        sqlx::query_as(
-            "SELECT env, key, value, CAST(DATETIME(created_at, 'unixepoch') AS text) AS created_at
-            FROM environments
-            WHERE
-                env = $1 AND
-                key = upper($2)
-            ORDER BY created_at",
+        .context("failed to read undo log")?;
+
+        // replaying changes the environments like the command did, so they
+        // are guarded the same way
+        let envs: Vec<(String, bool)> = sqlx::query_as(
+            r"SELECT env, MIN(metadata)
+            FROM undolog_envs
+            WHERE operation = $1 AND redo = $2
+            GROUP BY env
+            ORDER BY env",
         )
-        .bind(env)
-        .bind(key)
-        .fetch_all(&self.db)
+        .bind(operation.id)
+        .bind(redo)
+        .fetch_all(tx.conn())
         .await
-        .context("failed to read variable history")
+        .context("failed to read undo log")?;
+        for (env, metadata) in envs {
+            match metadata {
+                true => tx.confirm_protected(&env).await?,
+                false => tx.guard(&env).await?,
+            }
+        }
+
+        for query in [
+            r"DELETE FROM undolog WHERE operation = $1 AND redo = $2",
+            r"DELETE FROM undolog_envs WHERE operation = $1 AND redo = $2",
+            r"INSERT INTO recording (operation, redo, replaying) VALUES ($1, NOT $2, TRUE)",
+        ] {
+            tx.execute(sqlx::query(query).bind(operation.id).bind(redo))
+                .await
+                .context("failed to record operation")?;
+        }
+
+        // rows are restored in reverse order, referenced rows may come later
+        sqlx::query("PRAGMA defer_foreign_keys = ON")
+            .execute(tx.conn())
+            .await
+            .context("failed to defer foreign keys")?;
+        for statement in statements {
+            tx.execute(sqlx::query(AssertSqlSafe(statement)))
+                .await
+                .with_context(|| format!("failed to replay operation {}", operation.id))?;
+        }
+
+        for query in [
+            r"DELETE FROM recording",
+            r"UPDATE operations SET undone = NOT $2 WHERE id = $1",
+        ] {
+            tx.execute(sqlx::query(query).bind(operation.id).bind(redo))
+                .await
+                .context("failed to record operation")?;
+        }
+        tx.commit().await?;
+
+        operation.undone = !redo;
+        Ok(Some(operation))
     }
 
     /// Returns the number of row changes caused by INSERT, UPDATE or DELETE
     /// statements since the current database connection was opened
-    pub(crate) async fn total_changes(&self) -> Result<i64> {
+    async fn raw_total_changes(&self) -> Result<i64> {
         sqlx::query_scalar("SELECT total_changes()")
             .fetch_one(&self.db)
             .await
             .context("failed to read total_changes")
     }
 
+    /// Returns the number of row changes caused by INSERT, UPDATE or DELETE
+    /// statements since the current database connection was opened, leaving
+    /// out the ones that left no trace in the database
+    pub(crate) async fn total_changes(&self) -> Result<i64> {
+        let total = self.raw_total_changes().await?;
+
+        Ok(total - self.discarded.load(Ordering::Relaxed) as i64)
+    }
+
     /// Serializes envelope interal database into bytes
     pub(crate) async fn serialize(&self) -> Result<Vec<u8>> {
         let mut conn = self
@@ -341,6 +1677,12 @@ impl EnvelopeDb {
     }
 }
 
+/// Encodes `ids` as a JSON array, to be expanded with `json_each` in queries
+fn json_array(ids: &[i64]) -> String {
+    let ids: Vec<String> = ids.iter().map(i64::to_string).collect();
+    format!("[{}]", ids.join(","))
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -465,7 +1807,7 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn test_delete_env(pool: SqlitePool) {
+    async fn test_trash_env(pool: SqlitePool) {
         let db = EnvelopeDb::with(pool);
 
         // Insert test data
@@ -474,8 +1816,8 @@ mod tests {
         db.insert("env2", "key2", "value3").await.unwrap();
         db.insert("env3", "key2", "value3").await.unwrap();
 
-        // Delete environment
-        db.delete_env("env2").await.unwrap();
+        // Move environment to the trash
+        db.trash_env("env2").await.unwrap();
 
         // Query database directly to verify stored values
         let rows = sqlx::query(
@@ -510,6 +1852,10 @@ mod tests {
             .collect();
 
         assert_eq!(expected, actual);
+
+        let trash = db.list_trash(None).await.unwrap();
+        assert_eq!(1, trash.len());
+        assert_eq!("env2", trash[0].env);
     }
 
     #[sqlx::test]
@@ -712,11 +2058,11 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY2", "value2"),
             ],
-            db.list_kv_in_env("env1").await.unwrap()
+            db.list_kv_in_env("env1", Resolve::default()).await.unwrap()
         );
         assert_eq!(
             vec![EnvironmentRow::from("env2", "KEY1", "value1"),],
-            db.list_kv_in_env("env2").await.unwrap()
+            db.list_kv_in_env("env2", Resolve::default()).await.unwrap()
         );
     }
 
@@ -746,7 +2092,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY2", "value2"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "da")
+            db.list_kv_in_env_alt("env1", Truncate::None, "da", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -756,7 +2102,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "val"),
                 EnvironmentRow::from("env1", "KEY2", "val"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::Max(3), "da")
+            db.list_kv_in_env_alt("env1", Truncate::Max(3), "da", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -766,7 +2112,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY2", "value2"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::Max(7), "da")
+            db.list_kv_in_env_alt("env1", Truncate::Max(7), "da", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -776,7 +2122,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", ""),
                 EnvironmentRow::from("env1", "KEY2", ""),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::Max(0), "da")
+            db.list_kv_in_env_alt("env1", Truncate::Max(0), "da", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -805,7 +2151,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY4", "value4"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "k")
+            db.list_kv_in_env_alt("env1", Truncate::None, "k", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -816,7 +2162,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY2", "value2"),
                 EnvironmentRow::from("env1", "KEY1", "value1"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "kd")
+            db.list_kv_in_env_alt("env1", Truncate::None, "kd", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -827,7 +2173,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY2", "value2"),
                 EnvironmentRow::from("env1", "KEY4", "value4"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "d")
+            db.list_kv_in_env_alt("env1", Truncate::None, "d", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -838,7 +2184,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY1", "value1"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "dd")
+            db.list_kv_in_env_alt("env1", Truncate::None, "dd", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -849,7 +2195,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY3", "value3"),
                 EnvironmentRow::from("env1", "KEY4", "value4"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "v")
+            db.list_kv_in_env_alt("env1", Truncate::None, "v", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -860,7 +2206,7 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY2", "value2"),
                 EnvironmentRow::from("env1", "KEY1", "value1"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "vd")
+            db.list_kv_in_env_alt("env1", Truncate::None, "vd", Resolve::default())
                 .await
                 .unwrap()
         );
@@ -871,13 +2217,62 @@ mod tests {
                 EnvironmentRow::from("env1", "KEY2", "value2"),
                 EnvironmentRow::from("env1", "KEY4", "value4"),
             ],
-            db.list_kv_in_env_alt("env1", Truncate::None, "invalidsort")
+            db.list_kv_in_env_alt("env1", Truncate::None, "invalidsort", Resolve::default())
                 .await
                 .unwrap(),
             "when sort key is invalid it should fallback to ordering by created_at desc"
         );
     }
 
+    #[sqlx::test]
+    async fn test_list_at(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.exec(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+                ('env1', 'KEY1', 'value1', 0),
+                ('env1', 'KEY1', 'value2', 100),
+                ('env1', 'KEY2', 'value1', 50),
+                ('env1', 'KEY2', NULL, 150),
+                ('env1', 'KEY3', 'value3', 200)
+            ",
+        )
+        .await
+        .unwrap();
+
+        let at = |at| Resolve {
+            at: Some(at),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Vec::<EnvironmentRow>::new(),
+            db.list_kv_in_env("env1", at(-1)).await.unwrap()
+        );
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("env1", "KEY1", "value1"),
+                EnvironmentRow::from("env1", "KEY2", "value1"),
+            ],
+            db.list_kv_in_env("env1", at(50)).await.unwrap()
+        );
+        assert_eq!(
+            vec![EnvironmentRow::from("env1", "KEY1", "value2")],
+            db.list_kv_in_env("env1", at(199)).await.unwrap()
+        );
+        assert_eq!(
+            vec![
+                EnvironmentDiff::Different("KEY1".into(), "value1".into(), "value2".into()),
+                EnvironmentDiff::InFirst("KEY2".into(), "value1".into()),
+                EnvironmentDiff::InSecond("KEY3".into(), "value3".into()),
+            ],
+            db.diff("env1", at(50), "env1", Resolve::default())
+                .await
+                .unwrap()
+        );
+    }
+
     #[sqlx::test]
     async fn test_list_envs(pool: SqlitePool) {
         let db = EnvelopeDb::with(pool);
@@ -941,7 +2336,9 @@ mod tests {
                 EnvironmentDiff::InFirst("KEY4".into(), "value4".into()),
                 EnvironmentDiff::Different("NOTMATCH".into(), "value1".into(), "value2".into())
             ],
-            db.diff("env1", "env2").await.unwrap()
+            db.diff("env1", Resolve::default(), "env2", Resolve::default())
+                .await
+                .unwrap()
         );
         assert_eq!(
             vec![
@@ -951,7 +2348,9 @@ mod tests {
                 EnvironmentDiff::InSecond("KEY4".into(), "value4".into()),
                 EnvironmentDiff::Different("NOTMATCH".into(), "value2".into(), "value1".into())
             ],
-            db.diff("env2", "env1").await.unwrap()
+            db.diff("env2", Resolve::default(), "env1", Resolve::default())
+                .await
+                .unwrap()
         );
     }
 
@@ -963,7 +2362,7 @@ mod tests {
 
         // first argument doesn't exist
         let err = db
-            .diff("ghost", "env1")
+            .diff("ghost", Resolve::default(), "env1", Resolve::default())
             .await
             .expect_err("diff with non-existent first env should fail");
         assert!(
@@ -973,7 +2372,7 @@ mod tests {
 
         // second argument doesn't exist
         let err = db
-            .diff("env1", "ghost")
+            .diff("env1", Resolve::default(), "ghost", Resolve::default())
             .await
             .expect_err("diff with non-existent second env should fail");
         assert!(
@@ -983,7 +2382,7 @@ mod tests {
 
         // both don't exist
         let err = db
-            .diff("nope", "nada")
+            .diff("nope", Resolve::default(), "nada", Resolve::default())
             .await
             .expect_err("diff with two non-existent envs should fail");
         assert!(
@@ -1008,11 +2407,11 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            db.list_kv_in_env("env1").await.unwrap(),
+            db.list_kv_in_env("env1", Resolve::default()).await.unwrap(),
             vec![EnvironmentRow::from("env1", "KEY1", "value2")]
         );
         assert_eq!(
-            db.list_kv_in_env("env2").await.unwrap(),
+            db.list_kv_in_env("env2", Resolve::default()).await.unwrap(),
             vec![EnvironmentRow::from("env2", "KEY1", "value2")]
         );
 
@@ -1020,12 +2419,12 @@ mod tests {
         // and will invalidate the value
         db.revert("env1", "key1").await.unwrap();
         assert_eq!(
-            db.list_kv_in_env("env1").await.unwrap(),
+            db.list_kv_in_env("env1", Resolve::default()).await.unwrap(),
             vec![],
             "env1 should be empty"
         );
         assert_eq!(
-            db.list_kv_in_env("env2").await.unwrap(),
+            db.list_kv_in_env("env2", Resolve::default()).await.unwrap(),
             vec![EnvironmentRow::from("env2", "KEY1", "value2")],
             "env2 should remain untouched"
         );
@@ -1033,11 +2432,11 @@ mod tests {
         // this will delete ('env1', 'KEY1', NULL, 10)
         db.revert("env1", "key1").await.unwrap();
         assert_eq!(
-            db.list_kv_in_env("env1").await.unwrap(),
+            db.list_kv_in_env("env1", Resolve::default()).await.unwrap(),
             vec![EnvironmentRow::from("env1", "KEY1", "value1")]
         );
         assert_eq!(
-            db.list_kv_in_env("env2").await.unwrap(),
+            db.list_kv_in_env("env2", Resolve::default()).await.unwrap(),
             vec![EnvironmentRow::from("env2", "KEY1", "value2")],
             "env2 should remain untouched"
         );
@@ -1062,11 +2461,65 @@ mod tests {
 
         assert_eq!(
             vec![
-                EnvironmentRowNullable::from("env1", "KEY1", Some("value1"), "1970-01-01 00:00:00"),
-                EnvironmentRowNullable::from("env1", "KEY1", None, "1970-01-01 00:00:10"),
-                EnvironmentRowNullable::from("env1", "KEY1", Some("value2"), "1970-01-01 00:01:40"),
+                EnvironmentRowNullable::from(
+                    "env1",
+                    "KEY1",
+                    Some("value1"),
+                    1,
+                    "1970-01-01 00:00:00"
+                ),
+                EnvironmentRowNullable::from("env1", "KEY1", None, 2, "1970-01-01 00:00:10"),
+                EnvironmentRowNullable::from(
+                    "env1",
+                    "KEY1",
+                    Some("value2"),
+                    3,
+                    "1970-01-01 00:01:40"
+                ),
             ],
             db.history("env1", "key1").await.unwrap()
         );
     }
+
+    #[sqlx::test]
+    async fn test_same_second_writes(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        // values written within the same second used to collide
+        db.insert("env1", "KEY1", "a").await.unwrap();
+        db.insert("env1", "KEY1", "b").await.unwrap();
+        db.insert("env1", "KEY1", "a").await.unwrap();
+        db.insert("env1", "KEY2", "x").await.unwrap();
+
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("env1", "KEY1", "a"),
+                EnvironmentRow::from("env1", "KEY2", "x"),
+            ],
+            db.list_kv_in_env_alt("env1", Truncate::None, "k", Resolve::default())
+                .await
+                .unwrap()
+        );
+
+        let revisions: Vec<i64> = db
+            .history("env1", "KEY1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.revision)
+            .collect();
+        assert_eq!(vec![1, 2, 3], revisions);
+
+        db.revert("env1", "KEY1").await.unwrap();
+        assert_eq!(
+            vec![EnvironmentRow::from("env1", "KEY1", "b")],
+            db.list_kv_in_env_alt("env1", Truncate::None, "k", Resolve::default())
+                .await
+                .unwrap()[..1]
+        );
+
+        // revisions of deleted rows are never reused
+        db.insert("env1", "KEY1", "c").await.unwrap();
+        assert_eq!(5, db.history("env1", "KEY1").await.unwrap()[2].revision);
+    }
 }
@@ -1,5 +1,7 @@
-use sqlx::sqlite::SqliteRow;
-use sqlx::{FromRow, Row};
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+use base64::prelude::{BASE64_STANDARD, Engine};
 
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
 pub struct Environment {
@@ -18,6 +20,23 @@ pub struct EnvironmentRow {
     pub env: String,
     pub key: String,
     pub value: String,
+    /// whether `value` is the base64 encoding of arbitrary bytes
+    #[sqlx(default)]
+    pub base64: bool,
+}
+
+impl EnvironmentRow {
+    /// Returns the bytes of the value, decoded if it is base64 encoded
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        if !self.base64 {
+            return Ok(Cow::Borrowed(self.value.as_bytes()));
+        }
+
+        BASE64_STANDARD
+            .decode(&self.value)
+            .map(Cow::Owned)
+            .with_context(|| format!("value of '{}' is not valid base64", self.key))
+    }
 }
 
 #[cfg(test)]
@@ -27,6 +46,7 @@ impl EnvironmentRow {
             env: e.to_owned(),
             key: k.to_owned(),
             value: v.to_owned(),
+            base64: false,
         }
     }
 }
@@ -36,21 +56,126 @@ pub struct EnvironmentRowNullable {
     pub env: String,
     pub key: String,
     pub value: Option<String>,
+    pub tombstone: bool,
+    pub revision: i64,
     pub created_at: String,
 }
 
 #[cfg(test)]
 impl EnvironmentRowNullable {
-    pub(crate) fn from(e: &str, k: &str, v: Option<&str>, date: &str) -> Self {
+    pub(crate) fn from(e: &str, k: &str, v: Option<&str>, rev: i64, date: &str) -> Self {
         Self {
             env: e.to_owned(),
             key: k.to_owned(),
             value: v.map(|x| x.to_owned()),
+            tombstone: false,
+            revision: rev,
             created_at: date.to_owned(),
         }
     }
 }
 
+/// Locates a single stored revision of a variable
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Revision {
+    pub rowid: i64,
+    pub env: String,
+    pub key: String,
+    pub revision: i64,
+    pub created_at: String,
+    /// whether the revision belongs to a dropped environment
+    pub trashed: bool,
+    /// whether the revision is a change waiting to be committed, whose
+    /// `revision` is its position among the staged changes and which has no
+    /// `created_at`
+    pub staged: bool,
+}
+
+/// A variable of an environment whose state differs from the one it had at
+/// some point in time, see [`super::EnvelopeDb::restorable`]
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Restorable {
+    pub key: String,
+    /// current value, `None` if the variable is deleted or unset
+    pub value: Option<String>,
+    /// whether the variable is unset, hiding the one inherited from a parent
+    pub tombstone: bool,
+    pub base64: bool,
+    /// value at the point in time, `None` if the variable was deleted, unset
+    /// or not added yet
+    pub past_value: Option<String>,
+    pub past_tombstone: bool,
+    pub past_base64: bool,
+}
+
+/// Metadata of an environment
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct EnvironmentMeta {
+    pub env: String,
+    pub description: Option<String>,
+    /// person or team to contact about the environment
+    pub owner: Option<String>,
+    /// changes must be confirmed
+    pub protected: bool,
+    /// changes are refused
+    pub read_only: bool,
+    pub created_at: String,
+}
+
+/// An environment that was dropped and moved to the trash
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct TrashedEnv {
+    pub env: String,
+    pub dropped_at: String,
+}
+
+/// How much of the history of an environment is kept by gc
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Retention {
+    pub env: String,
+    /// number of revisions kept per variable
+    pub keep_revisions: Option<i64>,
+    /// age in seconds under which revisions are kept
+    pub keep_newer_than: Option<i64>,
+}
+
+/// A command whose changes can be undone
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Operation {
+    pub id: i64,
+    /// command line of the command
+    pub command: String,
+    pub created_at: String,
+    pub undone: bool,
+}
+
+/// A variable changed by a changeset or by the staged changes
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct ChangedKey {
+    pub env: String,
+    pub key: String,
+}
+
+#[cfg(test)]
+impl ChangedKey {
+    pub(crate) fn from(e: &str, k: &str) -> Self {
+        Self {
+            env: e.to_owned(),
+            key: k.to_owned(),
+        }
+    }
+}
+
+/// A group of values written together by a single command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changeset {
+    pub id: i64,
+    pub message: Option<String>,
+    pub created_at: String,
+    /// variables changed, sorted by environment and key
+    pub keys: Vec<ChangedKey>,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum EnvironmentDiff {
     InFirst(String, String),
@@ -58,20 +183,10 @@ pub enum EnvironmentDiff {
     Different(String, String, String),
 }
 
-impl FromRow<'_, SqliteRow> for EnvironmentDiff {
-    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
-        let (key, value) = (
-            row.try_get::<String, _>("key")?,
-            row.try_get::<String, _>("value")?,
-        );
-
-        let val = match row.try_get::<String, _>("type")?.as_str() {
-            "+" => Self::InFirst(key, value),
-            "-" => Self::InSecond(key, value),
-            "/" => Self::Different(key, value, row.try_get("diff")?),
-            _ => panic!("unknown value"),
-        };
-
-        Ok(val)
+impl EnvironmentDiff {
+    pub fn key(&self) -> &str {
+        match self {
+            Self::InFirst(k, _) | Self::InSecond(k, _) | Self::Different(k, _, _) => k,
+        }
     }
 }
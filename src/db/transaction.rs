@@ -0,0 +1,543 @@
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+
+use anyhow::{Context, Result, bail, ensure};
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteArguments, SqliteQueryResult};
+use sqlx::{Sqlite, SqliteConnection, Transaction};
+
+use super::model::Restorable;
+use super::{Change, EnvelopeDb};
+use crate::interpolate;
+
+/// A transaction on the envelope database.
+///
+/// Operations touching more than one variable go through this handle so that
+/// they either fully apply or not at all: changes are only persisted once
+/// [`Self::commit`] is called, and dropping the handle without committing
+/// rolls every change back. The changes are recorded as a single changeset.
+pub struct EnvelopeTx<'a> {
+    db: &'a EnvelopeDb,
+    tx: Option<Transaction<'static, Sqlite>>,
+    /// rows changed so far, reported to `db` if the transaction is rolled back
+    changes: u64,
+    /// how the changes are recorded
+    change: Change<'a>,
+    /// changeset recording the changes, see [`Self::changeset`]
+    changeset: Option<i64>,
+    /// environments allowed to be changed so far, see [`Self::guard`]
+    guarded: HashSet<String>,
+}
+
+impl<'a> EnvelopeTx<'a> {
+    pub(super) async fn begin(db: &'a EnvelopeDb, change: Change<'a>) -> Result<Self> {
+        let tx = db.db.begin().await.context("failed to begin transaction")?;
+
+        Ok(Self {
+            db,
+            tx: Some(tx),
+            changes: 0,
+            change,
+            changeset: None,
+            guarded: HashSet::new(),
+        })
+    }
+
+    /// Commits every change made through the handle
+    pub async fn commit(mut self) -> Result<()> {
+        let tx = self
+            .tx
+            .take()
+            .expect("transaction is active until committed");
+        if let Err(e) = tx.commit().await {
+            self.db.discarded.fetch_add(self.changes, Ordering::Relaxed);
+            return Err(e).context("failed to commit transaction");
+        }
+
+        Ok(())
+    }
+
+    /// Connection the transaction runs on, to be used for reads
+    pub(super) fn conn(&mut self) -> &mut SqliteConnection {
+        self.tx
+            .as_mut()
+            .expect("transaction is active until committed")
+    }
+
+    /// Executes a statement within the transaction, keeping track of the rows
+    /// it changed
+    pub(super) async fn execute(
+        &mut self,
+        query: Query<'_, Sqlite, SqliteArguments>,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        let res = query.execute(self.conn()).await?;
+        self.changes += res.rows_affected();
+
+        Ok(res)
+    }
+
+    /// checks if an environment exists in the database, see
+    /// [`EnvelopeDb::env_exists`]
+    pub async fn env_exists(&mut self, env: &str) -> Result<bool> {
+        sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM environment WHERE env = $1)")
+            .bind(env)
+            .fetch_one(self.conn())
+            .await
+            .context("failed to check if environment exists")
+    }
+
+    /// lists the revisions of `key` in `env`, oldest first, see
+    /// [`EnvelopeDb::history`]
+    pub async fn revisions(&mut self, env: &str, key: &str) -> Result<Vec<i64>> {
+        sqlx::query_scalar(
+            r"SELECT revision
+            FROM environments
+            WHERE
+                env = $1 AND
+                key = upper($2)
+            ORDER BY revision",
+        )
+        .bind(env)
+        .bind(key)
+        .fetch_all(self.conn())
+        .await
+        .context("failed to read variable history")
+    }
+
+    /// inserts `key` and `value` to environment `env`
+    pub async fn insert(&mut self, env: &str, key: &str, var: &str) -> Result<()> {
+        self.writable(env).await?;
+        self.append(env, key, Some(var), false, false)
+            .await
+            .with_context(|| format!("failed to add variable '{}'", key.to_uppercase()))
+    }
+
+    /// inserts `key` to environment `env`, with `var` the base64 encoding of
+    /// its value
+    pub async fn insert_base64(&mut self, env: &str, key: &str, var: &str) -> Result<()> {
+        self.writable(env).await?;
+        self.append(env, key, Some(var), false, true)
+            .await
+            .with_context(|| format!("failed to add variable '{}'", key.to_uppercase()))
+    }
+
+    /// inserts a tombstone for `key` in environment `env`, which hides the
+    /// value that `env` would otherwise inherit from its parents
+    pub async fn insert_tombstone(&mut self, env: &str, key: &str) -> Result<()> {
+        self.writable(env).await?;
+        self.append(env, key, None, true, false)
+            .await
+            .context("failed to unset variable")
+    }
+
+    /// soft deletes all variables in an environment
+    pub async fn soft_delete_env(&mut self, env: &str) -> Result<()> {
+        self.writable(env).await?;
+        let rows: Vec<(String, String)> =
+            sqlx::query_as(r"SELECT env, key FROM active_envs WHERE env = $1 ORDER BY key")
+                .bind(env)
+                .fetch_all(self.conn())
+                .await
+                .context("failed to delete environment")?;
+
+        self.delete_all(rows)
+            .await
+            .context("failed to delete environment")
+    }
+
+    /// soft deletes all variables with specified key
+    pub async fn soft_delete_keys(&mut self, key: &str) -> Result<()> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as(r"SELECT env, key FROM active_envs WHERE key = UPPER($1) ORDER BY env")
+                .bind(key)
+                .fetch_all(self.conn())
+                .await
+                .context("failed to delete variable")?;
+
+        for (env, _) in &rows {
+            self.writable(env).await?;
+        }
+        self.delete_all(rows)
+            .await
+            .context("failed to delete variable")
+    }
+
+    /// Soft deletes specified variable in passed environment. Deleting a
+    /// tombstone makes the variable inherited from parents visible again.
+    pub async fn soft_delete_key_in_env(&mut self, env: &str, key: &str) -> Result<()> {
+        self.writable(env).await?;
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r"SELECT env, key
+            FROM latest_envs
+            WHERE
+                env = $1 AND
+                key = UPPER($2) AND
+                (value IS NOT NULL OR tombstone)",
+        )
+        .bind(env)
+        .bind(key)
+        .fetch_all(self.conn())
+        .await
+        .context("failed to delete variable from environment")?;
+
+        self.delete_all(rows)
+            .await
+            .context("failed to delete variable from environment")
+    }
+
+    /// duplicates `source_env`, along with the descriptions of its variables,
+    /// in a new environment `target_env`.
+    /// In order for this to work, `tgt_env` must not be present.
+    pub async fn duplicate_env(&mut self, source_env: &str, target_env: &str) -> Result<()> {
+        ensure!(
+            self.env_exists(source_env).await?,
+            "source environment '{source_env}' does not exist"
+        );
+
+        ensure!(
+            !self.env_exists(target_env).await?,
+            "target environment '{target_env}' already exists"
+        );
+
+        let rows: Vec<(String, String, bool)> = sqlx::query_as(
+            r"SELECT key, value, base64 FROM active_envs WHERE env = $1 ORDER BY revision",
+        )
+        .bind(source_env)
+        .fetch_all(self.conn())
+        .await
+        .context("failed to duplicate environment")?;
+
+        for (key, value, base64) in rows {
+            self.append(target_env, &key, Some(&value), false, base64)
+                .await
+                .context("failed to duplicate environment")?;
+        }
+
+        self.execute(
+            sqlx::query(
+                r"INSERT OR REPLACE INTO descriptions (env, key, description)
+                SELECT $2, key, description
+                FROM descriptions
+                WHERE env = $1",
+            )
+            .bind(source_env)
+            .bind(target_env),
+        )
+        .await
+        .context("failed to duplicate environment")?;
+
+        Ok(())
+    }
+
+    /// Lists the variables of `env` whose state differs from the one they had
+    /// at unix timestamp `at`, see [`Self::restore_env`]
+    pub async fn restorable(&mut self, env: &str, at: i64) -> Result<Vec<Restorable>> {
+        sqlx::query_as(
+            r"WITH past AS (
+                SELECT key, value, tombstone, base64
+                FROM (
+                    SELECT
+                        *
+                        , ROW_NUMBER() OVER (PARTITION BY key ORDER BY revision DESC) AS rn
+                    FROM environments
+                    WHERE
+                        env = $1 AND
+                        created_at < $2 + 1
+                )
+                WHERE rn = 1
+            )
+            SELECT
+                cur.key
+                , cur.value
+                , cur.tombstone
+                , cur.base64
+                , past.value AS past_value
+                , COALESCE(past.tombstone, 0) AS past_tombstone
+                , COALESCE(past.base64, 0) AS past_base64
+            FROM latest_envs cur
+            LEFT JOIN past ON past.key = cur.key
+            WHERE
+                cur.env = $1 AND
+                (
+                    cur.value IS NOT past.value OR
+                    cur.tombstone != COALESCE(past.tombstone, 0) OR
+                    cur.base64 != COALESCE(past.base64, 0)
+                )
+            ORDER BY cur.key",
+        )
+        .bind(env)
+        .bind(at)
+        .fetch_all(self.conn())
+        .await
+        .context("failed to read environment history")
+    }
+
+    /// Appends new rows to `env` so that every one of its variables matches the
+    /// state it had at unix timestamp `at`. Variables added after `at` are
+    /// deleted. Returns the number of variables that changed.
+    pub async fn restore_env(&mut self, env: &str, at: i64) -> Result<u64> {
+        self.writable(env).await?;
+        let rows = self.restorable(env, at).await?;
+
+        let restored = rows.len() as u64;
+        for row in rows {
+            self.append(
+                env,
+                &row.key,
+                row.past_value.as_deref(),
+                row.past_tombstone,
+                row.past_base64,
+            )
+            .await
+            .context("failed to restore environment")?;
+        }
+
+        Ok(restored)
+    }
+
+    /// Appends a new entry for `key` in `env` restoring the value it had at
+    /// revision `rev`. Nothing is appended when the current value already
+    /// matches.
+    pub async fn revert_to(&mut self, env: &str, key: &str, rev: i64) -> Result<()> {
+        let row: Option<(String, Option<String>, bool, bool)> = sqlx::query_as(
+            r"SELECT past.key, past.value, past.tombstone, past.base64
+            FROM environments past
+            JOIN latest_envs cur ON cur.env = past.env AND cur.key = past.key
+            WHERE
+                past.env = $1 AND
+                past.key = upper($2) AND
+                past.revision = $3 AND
+                (
+                    cur.value IS NOT past.value OR
+                    cur.tombstone != past.tombstone OR
+                    cur.base64 != past.base64
+                )",
+        )
+        .bind(env)
+        .bind(key)
+        .bind(rev)
+        .fetch_optional(self.conn())
+        .await
+        .context("failed to revert variable")?;
+
+        if let Some((key, value, tombstone, base64)) = row {
+            self.writable(env).await?;
+            self.append(env, &key, value.as_deref(), tombstone, base64)
+                .await
+                .context("failed to revert variable")?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the description of `key` in `env`, or in every environment if
+    /// `env` is `None`. A `None` description removes it. Descriptions are
+    /// never staged.
+    pub async fn set_description(
+        &mut self,
+        env: Option<&str>,
+        key: &str,
+        description: Option<&str>,
+    ) -> Result<()> {
+        if let Some(env) = env {
+            self.guard(env).await?;
+        }
+
+        self.execute(
+            sqlx::query(r"DELETE FROM descriptions WHERE env IS $1 AND key = upper($2)")
+                .bind(env)
+                .bind(key),
+        )
+        .await
+        .context("failed to set description")?;
+
+        if let Some(description) = description {
+            self.execute(
+                sqlx::query(
+                    r"INSERT INTO descriptions (env, key, description)
+                    VALUES ($1, upper($2), $3)",
+                )
+                .bind(env)
+                .bind(key)
+                .bind(description),
+            )
+            .await
+            .context("failed to set description")?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `env` may be changed, unless the changes are staged. Called
+    /// before writing so that a refusal is not hidden behind the context of
+    /// the failed write.
+    async fn writable(&mut self, env: &str) -> Result<()> {
+        match self.change.stage {
+            true => Ok(()),
+            false => self.guard(env).await,
+        }
+    }
+
+    /// Lists the variables, as `env:KEY`, whose current value has a reference
+    /// for which `matches` holds, given the environment of the variable and
+    /// the environment and key named by the reference. Values that cannot be
+    /// parsed have no references.
+    pub(super) async fn referencing(
+        &mut self,
+        matches: impl Fn(&str, Option<&str>, &str) -> bool,
+    ) -> Result<Vec<String>> {
+        let vars: Vec<(String, String, String)> = sqlx::query_as(
+            r"SELECT env, key, value
+            FROM active_envs
+            WHERE NOT base64 AND instr(value, '${') > 0
+            ORDER BY env, key",
+        )
+        .fetch_all(self.conn())
+        .await
+        .context("failed to read references")?;
+
+        Ok(vars
+            .into_iter()
+            .filter(|(env, _, value)| {
+                interpolate::references(value).is_ok_and(|references| {
+                    references
+                        .into_iter()
+                        .any(|(ref_env, ref_key)| matches(env, ref_env, ref_key))
+                })
+            })
+            .map(|(env, key, _)| format!("{env}:{key}"))
+            .collect())
+    }
+
+    /// Checks that `env` may be changed. Read-only environments cannot be, and
+    /// changing a protected one must be confirmed, once per transaction.
+    pub(super) async fn guard(&mut self, env: &str) -> Result<()> {
+        if self.guarded.contains(env) {
+            return Ok(());
+        }
+
+        let read_only: bool = sqlx::query_scalar(
+            r"SELECT EXISTS(SELECT 1 FROM environment WHERE env = $1 AND read_only)",
+        )
+        .bind(env)
+        .fetch_one(self.conn())
+        .await
+        .context("failed to read environment metadata")?;
+        if read_only {
+            bail!("environment '{env}' is read-only");
+        }
+
+        self.confirm_protected(env).await
+    }
+
+    /// Asks for confirmation before changing `env` if it is protected, see
+    /// [`EnvelopeDb::on_confirm`]
+    pub(super) async fn confirm_protected(&mut self, env: &str) -> Result<()> {
+        if self.guarded.contains(env) {
+            return Ok(());
+        }
+
+        let protected: bool = sqlx::query_scalar(
+            r"SELECT EXISTS(SELECT 1 FROM environment WHERE env = $1 AND protected)",
+        )
+        .bind(env)
+        .fetch_one(self.conn())
+        .await
+        .context("failed to read environment metadata")?;
+        if protected {
+            let confirmed = self
+                .db
+                .confirm(&format!("Environment '{env}' is protected, change it?"))?;
+            ensure!(confirmed, "environment '{env}' is protected");
+        }
+
+        self.guarded.insert(env.to_owned());
+
+        Ok(())
+    }
+
+    /// deletes the `(env, key)` variables
+    async fn delete_all(&mut self, rows: Vec<(String, String)>) -> Result<()> {
+        for (env, key) in rows {
+            self.append(&env, &key, None, false, false).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Records a new value of `key` in `env`, base64 encoded if `base64`. The
+    /// value is staged instead if the transaction was begun with
+    /// [`Change::stage`], otherwise it is recorded in the changeset of the
+    /// transaction.
+    async fn append(
+        &mut self,
+        env: &str,
+        key: &str,
+        value: Option<&str>,
+        tombstone: bool,
+        base64: bool,
+    ) -> Result<()> {
+        if self.change.stage {
+            self.execute(
+                sqlx::query(
+                    r"INSERT INTO staged (env, key, value, tombstone, base64)
+                    VALUES ($1, upper($2), $3, $4, $5)",
+                )
+                .bind(env)
+                .bind(key)
+                .bind(value)
+                .bind(tombstone)
+                .bind(base64),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        self.writable(env).await?;
+        let changeset = self.changeset().await?;
+        self.execute(
+            sqlx::query(
+                r"INSERT INTO environments (env, key, value, tombstone, base64, changeset)
+                VALUES ($1, upper($2), $3, $4, $5, $6)",
+            )
+            .bind(env)
+            .bind(key)
+            .bind(value)
+            .bind(tombstone)
+            .bind(base64)
+            .bind(changeset),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Changeset recording the changes of the transaction, created along with
+    /// the first change
+    pub(super) async fn changeset(&mut self) -> sqlx::Result<i64> {
+        if let Some(id) = self.changeset {
+            return Ok(id);
+        }
+
+        let id = self
+            .execute(
+                sqlx::query(r"INSERT INTO changesets (message) VALUES ($1)")
+                    .bind(self.change.message.map(str::to_owned)),
+            )
+            .await?
+            .last_insert_rowid();
+        self.changeset = Some(id);
+
+        Ok(id)
+    }
+}
+
+impl Drop for EnvelopeTx<'_> {
+    fn drop(&mut self) {
+        // the transaction itself is rolled back by sqlx when dropped
+        if self.tx.is_some() {
+            self.db.discarded.fetch_add(self.changes, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Parser of `.env` files, shared by `import`, `edit` and the listing of
+//! variables piped to envelope.
+//!
+//! Every line is either blank, a comment starting with `#` or a variable:
+//!
+//! ```text
+//! # comment
+//! KEY=value
+//! export KEY=value            # the `export` prefix is ignored
+//! KEY = value # inline comment, after whitespace
+//! KEY='literal, kept as is'
+//! KEY="escapes \" \\ \n \r \t, and # is not a comment"
+//! KEY="values in quotes
+//! can span lines"
+//! ```
+//!
+//! Unquoted values are trimmed, and a `#` only starts a comment at the start
+//! of the value or after whitespace, so that `KEY=a#b` is read as `a#b`.
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// Line, or lines for a multiline value, read from a `.env` file
+#[derive(Debug, PartialEq, Eq)]
+pub enum Item {
+    Blank,
+    /// text following the `#` of a comment line
+    Comment(String),
+    Var {
+        key: String,
+        value: String,
+    },
+}
+
+/// Line that could not be parsed
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    /// number of the line, starting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Iterator over the items of a `.env` file. Parsing resumes on the next line
+/// after an error.
+pub struct Parser<'a> {
+    rest: &'a str,
+    /// lines consumed so far
+    line: usize,
+}
+
+/// Parses `input` as the content of a `.env` file
+pub fn parse(input: &str) -> Parser<'_> {
+    Parser {
+        rest: input,
+        line: 0,
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Skips the first `len` bytes of the input left
+    fn advance(&mut self, len: usize) {
+        let (consumed, rest) = self.rest.split_at(len);
+        self.line += consumed.matches('\n').count();
+        self.rest = rest;
+    }
+}
+
+impl Iterator for Parser<'_> {
+    type Item = Result<Item, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match parse_item(self.rest) {
+            Ok((item, len)) => {
+                self.advance(len);
+                Some(Ok(item))
+            }
+            Err(message) => {
+                let line = self.line + 1;
+                self.advance(line_end(self.rest));
+                Some(Err(Error { line, message }))
+            }
+        }
+    }
+}
+
+/// Returns the length of the first line of `input`, including its newline
+fn line_end(input: &str) -> usize {
+    input.find('\n').map_or(input.len(), |idx| idx + 1)
+}
+
+/// Whether `c` may appear in the name of a variable
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+/// Parses the item at the start of `input`, returning it along with the
+/// number of bytes it spans, including its last newline
+fn parse_item(input: &str) -> Result<(Item, usize), String> {
+    let first = &input[..line_end(input)];
+    let line = first.trim_end_matches(['\n', '\r']);
+
+    let rest = line.trim_start_matches([' ', '\t']);
+    if rest.is_empty() {
+        return Ok((Item::Blank, first.len()));
+    }
+    if let Some(comment) = rest.strip_prefix('#') {
+        return Ok((Item::Comment(comment.to_owned()), first.len()));
+    }
+
+    let rest = rest
+        .strip_prefix("export")
+        .filter(|rest| rest.starts_with([' ', '\t']))
+        .map_or(rest, |rest| rest.trim_start_matches([' ', '\t']));
+    let key_len = rest.find(|c| !is_key_char(c)).unwrap_or(rest.len());
+    let (key, rest) = rest.split_at(key_len);
+    if key.is_empty() {
+        return Err(format!("expected a variable name, found '{}'", line.trim()));
+    }
+
+    let Some(rest) = rest.trim_start_matches([' ', '\t']).strip_prefix('=') else {
+        return Err(format!("expected '=' after '{key}'"));
+    };
+    let rest = rest.trim_start_matches([' ', '\t']);
+
+    // offset of the value in `input`, as quoted values may continue on the
+    // following lines
+    let start = line.len() - rest.len();
+    let (value, len) = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => quoted(&input[start + 1..], quote)?,
+        _ => return Ok((var(key, unquoted(rest)), first.len())),
+    };
+
+    // only a comment may follow the closing quote
+    let end = start + 1 + len;
+    let tail = &input[end..];
+    let tail_len = line_end(tail);
+    let tail = tail[..tail_len].trim();
+    if !tail.is_empty() && !tail.starts_with('#') {
+        return Err(format!("unexpected '{tail}' after the value of '{key}'"));
+    }
+
+    Ok((var(key, &value), end + tail_len))
+}
+
+fn var(key: &str, value: &str) -> Item {
+    Item::Var {
+        key: key.to_owned(),
+        value: value.to_owned(),
+    }
+}
+
+/// Reads an unquoted value, up to a comment or the end of the line
+fn unquoted(value: &str) -> &str {
+    let mut prev = ' ';
+    for (idx, c) in value.char_indices() {
+        if c == '#' && prev.is_whitespace() {
+            return value[..idx].trim_end();
+        }
+        prev = c;
+    }
+
+    value.trim_end()
+}
+
+/// Reads a value following its opening `quote`, returning it along with the
+/// length read, including the closing quote. Escape sequences are only
+/// replaced within double quotes.
+fn quoted(input: &str, quote: char) -> Result<(Cow<'_, str>, usize), String> {
+    if quote == '\'' {
+        return match input.find('\'') {
+            Some(idx) => Ok((Cow::Borrowed(&input[..idx]), idx + 1)),
+            None => Err("missing closing quote".to_owned()),
+        };
+    }
+
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Cow::Owned(value), idx + 1)),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some(c @ ('"' | '\\')) => value.push(c),
+                Some(c) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("missing closing quote".to_owned())
+}
+
+/// Returns `value` as it should be written in a `.env` file to be read back
+/// unchanged, in double quotes if needed
+pub fn quote(value: &str) -> Cow<'_, str> {
+    let needs_quotes = value.starts_with(['"', '\'', ' ', '\t'])
+        || value.ends_with([' ', '\t'])
+        || value.contains(['\n', '\r'])
+        || unquoted(value) != value;
+    if !needs_quotes {
+        return Cow::Borrowed(value);
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    Cow::Owned(quoted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(input: &str) -> Vec<(String, String)> {
+        parse(input)
+            .filter_map(|item| match item.unwrap() {
+                Item::Var { key, value } => Some((key, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = "# comment\n\nKEY=value\r\nexport EXPORTED=1\n  SPACED = a b  \nINLINE=x # \
+                     comment\nHASH=a#b\nEMPTY=\nexport=2\n";
+        let items: Vec<Item> = parse(input).map(Result::unwrap).collect();
+        assert_eq!(Item::Comment(" comment".to_owned()), items[0]);
+        assert_eq!(Item::Blank, items[1]);
+        assert_eq!(
+            vec![
+                ("KEY", "value"),
+                ("EXPORTED", "1"),
+                ("SPACED", "a b"),
+                ("INLINE", "x"),
+                ("HASH", "a#b"),
+                ("EMPTY", ""),
+                ("export", "2"),
+            ],
+            items[2..]
+                .iter()
+                .map(|item| match item {
+                    Item::Var { key, value } => (key.as_str(), value.as_str()),
+                    item => panic!("unexpected {item:?}"),
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted() {
+        let input = "SINGLE='a # b \\n'\nDOUBLE=\"a # b\" # comment\nESCAPED=\"say \
+                     \\\"hi\\\"\\n\\tand \\\\ \\$\"\nMULTI=\"first\nsecond\"\nAFTER='x\ny'\nLAST=1";
+        assert_eq!(
+            vec![
+                ("SINGLE".to_owned(), "a # b \\n".to_owned()),
+                ("DOUBLE".to_owned(), "a # b".to_owned()),
+                ("ESCAPED".to_owned(), "say \"hi\"\n\tand \\ \\$".to_owned()),
+                ("MULTI".to_owned(), "first\nsecond".to_owned()),
+                ("AFTER".to_owned(), "x\ny".to_owned()),
+                ("LAST".to_owned(), "1".to_owned()),
+            ],
+            vars(input)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let input = "OK=1\nBROKEN LINE\n=value\nOPEN=\"a\nNEXT=2\nTAIL='a' b\n";
+        let items: Vec<_> = parse(input).collect();
+        let errors: Vec<String> = items
+            .iter()
+            .filter_map(|item| item.as_ref().err().map(ToString::to_string))
+            .collect();
+        assert_eq!(
+            vec![
+                "line 2: expected '=' after 'BROKEN'",
+                "line 3: expected a variable name, found '=value'",
+                "line 4: missing closing quote",
+                "line 6: unexpected 'b' after the value of 'TAIL'",
+            ],
+            errors
+        );
+        assert_eq!(
+            Ok(&Item::Var {
+                key: "NEXT".to_owned(),
+                value: "2".to_owned()
+            }),
+            items[4].as_ref()
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        for value in [
+            "plain",
+            "a#b",
+            "",
+            " padded ",
+            "a # b",
+            "#start",
+            "\"quoted\"",
+            "'single'",
+            "multi\nline\r\n",
+            "back\\slash \"and\" quote\\",
+        ] {
+            let line = format!("KEY={}", quote(value));
+            assert_eq!(
+                vec![("KEY".to_owned(), value.to_owned())],
+                vars(&line),
+                "{line}"
+            );
+        }
+        assert_eq!("a#b", quote("a#b"));
+        assert_eq!("\"a # b\"", quote("a # b"));
+    }
+}
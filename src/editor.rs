@@ -6,6 +6,9 @@ use anyhow::{Context, Result};
 
 use crate::subproc::ChildProcess;
 
+/// Comment appended to the edited data, explaining how to remove variables
+pub const HINT: &str = "# Comment variables to remove them";
+
 pub fn spawn_with(data: &[u8]) -> Result<Vec<u8>> {
     let editor = std::env::var("ENVELOPE_EDITOR")
         .or_else(|_| std::env::var("GIT_EDITOR"))
@@ -18,11 +21,8 @@ pub fn spawn_with(data: &[u8]) -> Result<Vec<u8>> {
         .to_str()
         .context("current directory path contains invalid characters")?;
 
-    std::fs::write(
-        &pb,
-        [data, b"\n\n# Comment variables to remove them\n"].concat(),
-    )
-    .context("failed to write temporary edit file")?;
+    std::fs::write(&pb, [data, b"\n\n", HINT.as_bytes(), b"\n"].concat())
+        .context("failed to write temporary edit file")?;
 
     let args = &[pb_str];
     ChildProcess::new(&editor, args, &[])
@@ -0,0 +1,119 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use super::{Formatter, decoded, double_quoted, write_lines, yaml};
+use crate::db::model::EnvironmentRow;
+
+/// Env file read by `docker run --env-file`, in which every line is taken
+/// literally: quotes are not removed and values cannot span lines. Base64
+/// encoded values are decoded.
+pub struct DockerEnv;
+
+impl Formatter for DockerEnv {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        write_lines(writer, vars, |var| {
+            ensure!(
+                !var.key.starts_with('#')
+                    && !var.key.contains(|c: char| c == '=' || c.is_whitespace()),
+                "'{}' cannot be the name of a variable in a docker env file",
+                var.key
+            );
+            let value = decoded(var, "a docker env file")?;
+            ensure!(
+                !value.contains(['\n', '\r']),
+                "value of '{}' spans multiple lines, which docker env files cannot represent",
+                var.key
+            );
+            Ok(format!("{}={value}", var.key))
+        })
+    }
+}
+
+/// Compose file overriding the `environment` of a service, to be merged with
+/// `docker compose -f compose.yaml -f override.yaml`. Dollar signs are doubled
+/// so that compose does not interpolate them.
+pub struct Compose {
+    pub service: String,
+}
+
+impl Formatter for Compose {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        let mut entries = Vec::with_capacity(vars.len());
+        for var in vars {
+            let value = decoded(var, "a compose file")?;
+            entries.push((
+                yaml::key(&var.key),
+                double_quoted(&value.replace('$', "$$")),
+            ));
+        }
+
+        writeln!(writer, "services:")?;
+        writeln!(writer, "  {}:", yaml::key(&self.service))?;
+        if entries.is_empty() {
+            writeln!(writer, "    environment: {{}}")?;
+            return Ok(());
+        }
+
+        writeln!(writer, "    environment:")?;
+        for (key, value) in entries {
+            writeln!(writer, "      {key}: {value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::write;
+    use super::*;
+
+    #[test]
+    fn test_docker_env() {
+        let vars = vec![
+            EnvironmentRow::from("dev", "QUOTED", "\"kept\" as is "),
+            EnvironmentRow::from("dev", "URL", "http://${HOST}/#x"),
+        ];
+        assert_eq!(
+            "QUOTED=\"kept\" as is \nURL=http://${HOST}/#x\n",
+            write(&DockerEnv, &vars).unwrap()
+        );
+
+        let mut vars = vars;
+        vars.push(EnvironmentRow::from("dev", "MULTI", "a\nb"));
+        let mut output = Vec::new();
+        let err = DockerEnv.write(&mut output, &vars).unwrap_err();
+        assert_eq!(
+            "value of 'MULTI' spans multiple lines, which docker env files cannot represent",
+            err.to_string()
+        );
+        assert!(output.is_empty());
+
+        let mut nul = EnvironmentRow::from("dev", "NUL", "AA==");
+        nul.base64 = true;
+        assert!(write(&DockerEnv, &[nul]).is_err());
+        let vars = [EnvironmentRow::from("dev", "A B", "x")];
+        assert!(write(&DockerEnv, &vars).is_err());
+    }
+
+    #[test]
+    fn test_compose() {
+        let compose = Compose {
+            service: "web".to_owned(),
+        };
+        let vars = vec![
+            EnvironmentRow::from("dev", "MULTI", "a\nb"),
+            EnvironmentRow::from("dev", "PRICE", "$5 or ${X}"),
+        ];
+        assert_eq!(
+            "services:\n  web:\n    environment:\n      MULTI: \"a\\nb\"\n      PRICE: \"$$5 or \
+             $${X}\"\n",
+            write(&compose, &vars).unwrap()
+        );
+        assert_eq!(
+            "services:\n  web:\n    environment: {}\n",
+            write(&compose, &[]).unwrap()
+        );
+    }
+}
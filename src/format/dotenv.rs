@@ -0,0 +1,49 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::Formatter;
+use crate::db::model::EnvironmentRow;
+use crate::dotenv::quote;
+
+/// `KEY=value` lines, quoted when needed so that import reads them back
+/// unchanged
+pub struct Dotenv;
+
+impl Formatter for Dotenv {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        for var in vars {
+            writeln!(writer, "{}={}", var.key, quote(&var.value))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dotenv::{self, Item};
+
+    #[test]
+    fn test_dotenv() {
+        let vars = super::super::test::vars();
+        let mut output = Vec::new();
+        Dotenv.write(&mut output, &vars).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            "A.B=\"x=y: #z\"\nEMPTY=\nMULTI=\"line \\\"1\\\"\\n\tline 2\\\\\"\nUNICODE=\" \
+             é\u{1}\u{7f}😀\"\n",
+            output
+        );
+
+        // import reads the values back unchanged
+        let read: Vec<_> = dotenv::parse(&output)
+            .map(|item| match item.unwrap() {
+                Item::Var { key, value } => EnvironmentRow::from("dev", &key, &value),
+                item => panic!("unexpected {item:?}"),
+            })
+            .collect();
+        assert_eq!(vars, read);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{Formatter, double_quoted};
+use crate::db::model::EnvironmentRow;
+use crate::nested::{self, Node};
+
+/// Object mapping every key to its value, nesting keys on `separator` if
+/// there is one
+pub struct Json {
+    pub separator: Option<String>,
+}
+
+fn write_object(writer: &mut dyn Write, table: &BTreeMap<&str, Node>, depth: usize) -> Result<()> {
+    let indent = "  ".repeat(depth);
+    writeln!(writer, "{{")?;
+    for (i, (key, node)) in table.iter().enumerate() {
+        write!(writer, "{indent}  {}: ", double_quoted(key))?;
+        match node {
+            Node::Value(value) => write!(writer, "{}", double_quoted(value))?,
+            Node::Table(table) => write_object(writer, table, depth + 1)?,
+        }
+        let separator = if i + 1 < table.len() { "," } else { "" };
+        writeln!(writer, "{separator}")?;
+    }
+    write!(writer, "{indent}}}")?;
+
+    Ok(())
+}
+
+impl Formatter for Json {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        let root = nested::nest(vars, self.separator.as_deref())?;
+        if root.is_empty() {
+            writeln!(writer, "{{}}")?;
+            return Ok(());
+        }
+
+        write_object(writer, &root, 0)?;
+        writeln!(writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let mut output = Vec::new();
+        let flat = Json { separator: None };
+        flat.write(&mut output, &super::super::test::vars())
+            .unwrap();
+        assert_eq!(
+            r#"{
+  "A.B": "x=y: #z",
+  "EMPTY": "",
+  "MULTI": "line \"1\"\n\tline 2\\",
+  "UNICODE": " é\u0001\u007f😀"
+}
+"#,
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut output = Vec::new();
+        flat.write(&mut output, &[]).unwrap();
+        assert_eq!("{}\n", String::from_utf8(output).unwrap());
+
+        let nested = Json {
+            separator: Some(".".to_owned()),
+        };
+        let mut vars = super::super::test::vars();
+        vars.insert(0, EnvironmentRow::from("dev", "A.C.D", "1"));
+        let mut output = Vec::new();
+        nested.write(&mut output, &vars).unwrap();
+        assert_eq!(
+            r#"{
+  "A": {
+    "B": "x=y: #z",
+    "C": {
+      "D": "1"
+    }
+  },
+  "EMPTY": "",
+  "MULTI": "line \"1\"\n\tline 2\\",
+  "UNICODE": " é\u0001\u007f😀"
+}
+"#,
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
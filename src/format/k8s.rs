@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::{Context, Result, ensure};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use serde::Deserialize;
+
+use super::{Formatter, Select, double_quoted, yaml};
+use crate::db::model::EnvironmentRow;
+
+/// Kinds of Kubernetes objects holding variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// holds the secrets, base64 encoded
+    Secret,
+    /// holds the variables that are not secrets
+    ConfigMap,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Secret => "Secret",
+            Self::ConfigMap => "ConfigMap",
+        }
+    }
+}
+
+/// Whether `name` is a DNS subdomain as defined by RFC 1123, which Kubernetes
+/// requires of the names of most objects. Namespaces must also be at most 63
+/// characters long.
+fn is_dns_name(name: &str, max_len: usize) -> bool {
+    name.len() <= max_len
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name.ends_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.'))
+}
+
+/// Whether `key` can be a key of the data of a Secret or ConfigMap
+fn is_data_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Manifest of a Secret holding the secrets, or of a ConfigMap holding the
+/// other variables. Base64 encoded values are written to the `data` of a
+/// Secret as is, and to the `binaryData` of a ConfigMap.
+pub struct Manifest {
+    kind: Kind,
+    name: String,
+    namespace: Option<String>,
+    /// write the plain values of a Secret to `stringData`
+    string_data: bool,
+}
+
+impl Manifest {
+    pub fn new(
+        kind: Kind,
+        name: String,
+        namespace: Option<String>,
+        string_data: bool,
+    ) -> Result<Self> {
+        ensure!(
+            is_dns_name(&name, 253),
+            "'{name}' is not a valid Kubernetes name: use lowercase letters, digits, '-' and '.'"
+        );
+        if let Some(namespace) = &namespace {
+            ensure!(
+                !namespace.contains('.') && is_dns_name(namespace, 63),
+                "'{namespace}' is not a valid Kubernetes namespace: use at most 63 lowercase \
+                 letters, digits and '-'"
+            );
+        }
+
+        Ok(Self {
+            kind,
+            name,
+            namespace,
+            string_data,
+        })
+    }
+}
+
+fn write_section(writer: &mut dyn Write, section: &str, entries: &[(&str, String)]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer, "{section}:")?;
+    for (key, value) in entries {
+        writeln!(writer, "  {}: {}", yaml::key(key), double_quoted(value))?;
+    }
+
+    Ok(())
+}
+
+impl Formatter for Manifest {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        // the `data` of a Secret and the `binaryData` of a ConfigMap are
+        // base64 encoded
+        let mut plain = Vec::new();
+        let mut encoded = Vec::new();
+        for var in vars {
+            ensure!(
+                is_data_key(&var.key),
+                "'{}' is not a valid key of a {}: use letters, digits, '-', '_' and '.'",
+                var.key,
+                self.kind.name()
+            );
+            match (self.kind, var.base64) {
+                (Kind::Secret, false) if !self.string_data => {
+                    encoded.push((var.key.as_str(), BASE64_STANDARD.encode(&var.value)))
+                }
+                (_, false) => plain.push((var.key.as_str(), var.value.clone())),
+                (_, true) => encoded.push((var.key.as_str(), var.value.clone())),
+            }
+        }
+
+        writeln!(writer, "apiVersion: v1")?;
+        writeln!(writer, "kind: {}", self.kind.name())?;
+        writeln!(writer, "metadata:")?;
+        writeln!(writer, "  name: {}", yaml::key(&self.name))?;
+        if let Some(namespace) = &self.namespace {
+            writeln!(writer, "  namespace: {}", yaml::key(namespace))?;
+        }
+
+        let (plain_section, encoded_section) = match self.kind {
+            Kind::Secret => {
+                writeln!(writer, "type: Opaque")?;
+                ("stringData", "data")
+            }
+            Kind::ConfigMap => ("data", "binaryData"),
+        };
+        if plain.is_empty() && encoded.is_empty() {
+            writeln!(writer, "data: {{}}")?;
+        }
+        write_section(writer, encoded_section, &encoded)?;
+        write_section(writer, plain_section, &plain)
+    }
+
+    fn select(&self) -> Select {
+        match self.kind {
+            Kind::Secret => Select::Secrets,
+            Kind::ConfigMap => Select::Plain,
+        }
+    }
+}
+
+/// Manifest as read back, ignoring everything but its kind and data
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawManifest {
+    kind: String,
+    #[serde(default)]
+    data: BTreeMap<String, String>,
+    #[serde(default)]
+    string_data: BTreeMap<String, String>,
+    #[serde(default)]
+    binary_data: BTreeMap<String, String>,
+}
+
+/// Reads the variables of the manifest `doc`, which must be of `kind`,
+/// returning them sorted by key along with whether their value is base64
+/// encoded. Encoded values are decoded, unless they are not UTF-8 and must
+/// stay encoded.
+pub fn read_manifest(doc: serde_json::Value, kind: Kind) -> Result<Vec<(String, String, bool)>> {
+    let manifest: RawManifest = serde_json::from_value(doc).context("invalid manifest")?;
+    ensure!(
+        manifest.kind == kind.name(),
+        "expected a {} manifest, found a {}",
+        kind.name(),
+        manifest.kind
+    );
+
+    let (plain, encoded) = match kind {
+        Kind::Secret => (manifest.string_data, manifest.data),
+        Kind::ConfigMap => (manifest.data, manifest.binary_data),
+    };
+
+    let mut vars = BTreeMap::new();
+    for (key, value) in encoded {
+        let bytes = BASE64_STANDARD
+            .decode(&value)
+            .with_context(|| format!("value of '{key}' is not valid base64"))?;
+        let var = match String::from_utf8(bytes) {
+            Ok(value) => (value, false),
+            Err(_) => (value, true),
+        };
+        vars.insert(key, var);
+    }
+    // Kubernetes merges stringData over data
+    for (key, value) in plain {
+        vars.insert(key, (value, false));
+    }
+
+    Ok(vars
+        .into_iter()
+        .map(|(key, (value, base64))| (key, value, base64))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::write;
+    use super::*;
+    use crate::nested::Document;
+
+    fn vars() -> Vec<EnvironmentRow> {
+        let mut cert = EnvironmentRow::from("dev", "CERT", "AP8=");
+        cert.base64 = true;
+        vec![
+            cert,
+            EnvironmentRow::from("dev", "DB_PASSWORD", "hunter2\n"),
+        ]
+    }
+
+    #[test]
+    fn test_secret() {
+        let secret = Manifest::new(
+            Kind::Secret,
+            "app".to_owned(),
+            Some("prod".to_owned()),
+            false,
+        )
+        .unwrap();
+        let output = write(&secret, &vars()).unwrap();
+        assert_eq!(
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\n  namespace: prod\ntype: \
+             Opaque\ndata:\n  CERT: \"AP8=\"\n  DB_PASSWORD: \"aHVudGVyMgo=\"\n",
+            output
+        );
+
+        let doc = Document::Yaml.parse(&output).unwrap();
+        assert_eq!(
+            vec![
+                ("CERT".to_owned(), "AP8=".to_owned(), true),
+                ("DB_PASSWORD".to_owned(), "hunter2\n".to_owned(), false),
+            ],
+            read_manifest(doc, Kind::Secret).unwrap()
+        );
+
+        let secret = Manifest::new(Kind::Secret, "app".to_owned(), None, true).unwrap();
+        let output = write(&secret, &vars()).unwrap();
+        assert_eq!(
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\ntype: Opaque\ndata:\n  CERT: \
+             \"AP8=\"\nstringData:\n  DB_PASSWORD: \"hunter2\\n\"\n",
+            output
+        );
+        let doc = Document::Yaml.parse(&output).unwrap();
+        assert_eq!(2, read_manifest(doc, Kind::Secret).unwrap().len());
+    }
+
+    #[test]
+    fn test_configmap() {
+        let configmap = Manifest::new(Kind::ConfigMap, "1.0".to_owned(), None, false).unwrap();
+        let mut vars = vars();
+        vars.push(EnvironmentRow::from("dev", "NO", "no"));
+        assert_eq!(
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: \"1.0\"\nbinaryData:\n  CERT: \
+             \"AP8=\"\ndata:\n  DB_PASSWORD: \"hunter2\\n\"\n  \"NO\": \"no\"\n",
+            write(&configmap, &vars).unwrap()
+        );
+        assert_eq!(
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: \"1.0\"\ndata: {}\n",
+            write(&configmap, &[]).unwrap()
+        );
+
+        let doc = Document::Yaml
+            .parse(&write(&configmap, &vars).unwrap())
+            .unwrap();
+        assert_eq!(
+            "expected a Secret manifest, found a ConfigMap",
+            read_manifest(doc, Kind::Secret).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Manifest::new(Kind::Secret, "App".to_owned(), None, false).is_err());
+        assert!(Manifest::new(Kind::Secret, "app-".to_owned(), None, false).is_err());
+        assert!(
+            Manifest::new(Kind::Secret, "a".to_owned(), Some("a.b".to_owned()), false).is_err()
+        );
+
+        let secret = Manifest::new(Kind::Secret, "app".to_owned(), None, false).unwrap();
+        let mut output = Vec::new();
+        let err = secret
+            .write(&mut output, &[EnvironmentRow::from("dev", "A B", "x")])
+            .unwrap_err();
+        assert_eq!(
+            "'A B' is not a valid key of a Secret: use letters, digits, '-', '_' and '.'",
+            err.to_string()
+        );
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+//! Formats variables are exported to.
+//!
+//! Every format implements [`Formatter`], writing the variables in the order
+//! they are given, which is by key so that exports can be committed and
+//! diffed. Kubernetes manifests can also be read back, see
+//! [`read_manifest`].
+
+mod docker;
+mod dotenv;
+mod json;
+mod k8s;
+mod properties;
+mod shell;
+mod systemd;
+mod toml;
+mod yaml;
+
+use std::io::Write;
+
+use anyhow::{Context, Result, bail, ensure};
+use clap::ValueEnum;
+pub use k8s::{Kind, read_manifest};
+pub use shell::{Shell, ShellEnv};
+
+use crate::db::model::EnvironmentRow;
+
+/// Variables written by a format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Select {
+    All,
+    /// only the variables classified or guessed as secrets
+    Secrets,
+    /// only the variables that are not secrets
+    Plain,
+}
+
+/// Writes variables in a file format
+pub trait Formatter {
+    /// Writes `vars`, sorted by key. Base64 encoded values are written
+    /// encoded, unless the format has a way to flag them.
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()>;
+
+    /// Which variables are given to [`Formatter::write`]
+    fn select(&self) -> Select {
+        Select::All
+    }
+}
+
+/// Formats variables can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// KEY=value lines, quoted as read by import
+    Dotenv,
+    /// JSON object
+    Json,
+    /// YAML mapping
+    Yaml,
+    /// TOML table
+    Toml,
+    /// Java .properties
+    Properties,
+    /// Env file of `docker run --env-file`, which cannot hold multiline values
+    DockerEnv,
+    /// Compose file setting the environment of a service
+    Compose,
+    /// File read by the EnvironmentFile= of a systemd unit
+    Systemd,
+    /// Drop-in setting the Environment= of a systemd unit
+    SystemdDropin,
+    /// Kubernetes Secret holding the secrets
+    K8sSecret,
+    /// Kubernetes ConfigMap holding the variables that are not secrets
+    K8sConfigmap,
+}
+
+/// Options of the formats, which fail to build if given an option that does
+/// not apply to them
+#[derive(Debug, Default)]
+pub struct Options {
+    /// separator to nest keys on, in json, yaml and toml
+    pub separator: Option<String>,
+    /// name of the Kubernetes object, required by the k8s formats
+    pub name: Option<String>,
+    /// namespace of the Kubernetes object
+    pub namespace: Option<String>,
+    /// write the values of a Secret as stringData instead of base64 data
+    pub string_data: bool,
+    /// service of a compose file, required by the compose format
+    pub service: Option<String>,
+    /// unit of a systemd drop-in, required by the systemd-dropin format
+    pub unit: Option<String>,
+}
+
+impl Format {
+    fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_owned()
+    }
+
+    /// Returns the formatter of the format, nesting the variables by splitting
+    /// their keys on `options.separator` if there is one
+    pub fn formatter(self, options: Options) -> Result<Box<dyn Formatter>> {
+        let manifest = matches!(self, Self::K8sSecret | Self::K8sConfigmap);
+        ensure!(
+            manifest || (options.name.is_none() && options.namespace.is_none()),
+            "--name and --namespace only apply to the k8s formats"
+        );
+        ensure!(
+            self == Self::K8sSecret || !options.string_data,
+            "--string-data only applies to the k8s-secret format"
+        );
+        ensure!(
+            self == Self::Compose || options.service.is_none(),
+            "--service only applies to the compose format"
+        );
+        ensure!(
+            self == Self::SystemdDropin || options.unit.is_none(),
+            "--unit only applies to the systemd-dropin format"
+        );
+
+        let separator = options.separator;
+        ensure!(
+            separator.as_deref() != Some(""),
+            "the separator of nested keys cannot be empty"
+        );
+        Ok(match (self, separator) {
+            (Self::Dotenv, None) => Box::new(dotenv::Dotenv),
+            (Self::Json, separator) => Box::new(json::Json { separator }),
+            (Self::Yaml, separator) => Box::new(yaml::Yaml { separator }),
+            (Self::Toml, separator) => Box::new(toml::Toml { separator }),
+            (Self::Properties, None) => Box::new(properties::Properties),
+            (Self::DockerEnv, None) => Box::new(docker::DockerEnv),
+            (Self::Compose, None) => {
+                let service = options
+                    .service
+                    .context("the compose format needs a --service")?;
+                ensure!(
+                    !service.is_empty()
+                        && service
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+                    "'{service}' is not a valid service name: use letters, digits, '-', '_' and \
+                     '.'"
+                );
+                Box::new(docker::Compose { service })
+            }
+            (Self::Systemd, None) => Box::new(systemd::Systemd),
+            (Self::SystemdDropin, None) => {
+                let unit = options
+                    .unit
+                    .context("the systemd-dropin format needs a --unit")?;
+                Box::new(systemd::SystemdDropin::new(&unit)?)
+            }
+            (Self::K8sSecret | Self::K8sConfigmap, None) => {
+                let kind = match self {
+                    Self::K8sSecret => Kind::Secret,
+                    _ => Kind::ConfigMap,
+                };
+                let name = options
+                    .name
+                    .with_context(|| format!("the {} format needs a --name", self.name()))?;
+                Box::new(k8s::Manifest::new(
+                    kind,
+                    name,
+                    options.namespace,
+                    options.string_data,
+                )?)
+            }
+            (format, Some(_)) => {
+                bail!("variables cannot be nested in the {} format", format.name())
+            }
+        })
+    }
+}
+
+/// Returns the value of `var`, decoded if base64 encoded, failing if it is
+/// not text that can be set as an environment variable by `target`
+fn decoded(var: &EnvironmentRow, target: &str) -> Result<String> {
+    let value = String::from_utf8(var.bytes()?.into_owned()).with_context(|| {
+        format!(
+            "value of '{}' is not UTF-8, it cannot be set in {target}",
+            var.key
+        )
+    })?;
+    ensure!(
+        !value.contains('\0'),
+        "value of '{}' contains a NUL byte, it cannot be set in {target}",
+        var.key
+    );
+
+    Ok(value)
+}
+
+/// Writes the line `line` returns for each of `vars`. Nothing is written
+/// unless every variable can be.
+fn write_lines(
+    writer: &mut dyn Write,
+    vars: &[EnvironmentRow],
+    line: impl Fn(&EnvironmentRow) -> Result<String>,
+) -> Result<()> {
+    let lines = vars.iter().map(line).collect::<Result<Vec<_>>>()?;
+    for line in lines {
+        writeln!(writer, "{line}")?;
+    }
+
+    Ok(())
+}
+
+/// Returns `value` in double quotes, with quotes, backslashes and control
+/// characters escaped as understood by JSON, YAML and TOML alike
+fn double_quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // YAML reads the line and paragraph separators as line breaks
+            c if c.is_control() || matches!(c, '\u{2028}' | '\u{2029}') => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Variables exercising the escaping of every format
+    pub(super) fn vars() -> Vec<EnvironmentRow> {
+        vec![
+            EnvironmentRow::from("dev", "A.B", "x=y: #z"),
+            EnvironmentRow::from("dev", "EMPTY", ""),
+            EnvironmentRow::from("dev", "MULTI", "line \"1\"\n\tline 2\\"),
+            EnvironmentRow::from("dev", "UNICODE", " é\u{1}\u{7f}😀"),
+        ]
+    }
+
+    /// Returns what `formatter` writes for `vars`
+    pub(super) fn write(formatter: &dyn Formatter, vars: &[EnvironmentRow]) -> Result<String> {
+        let mut output = Vec::new();
+        formatter.write(&mut output, vars)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_empty_separator() {
+        let options = Options {
+            separator: Some(String::new()),
+            ..Default::default()
+        };
+        let err = Format::Json.formatter(options).err().unwrap();
+        assert_eq!(
+            "the separator of nested keys cannot be empty",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_double_quoted() {
+        assert_eq!(r#""""#, double_quoted(""));
+        assert_eq!(
+            r#""a \"b\"\\\n\r\t\u0001\u007f\u2028é""#,
+            double_quoted("a \"b\"\\\n\r\t\u{1}\u{7f}\u{2028}é")
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::Formatter;
+use crate::db::model::EnvironmentRow;
+
+/// Java `.properties`, with every character outside of printable ASCII
+/// escaped as `\uXXXX` since the format is read as ISO 8859-1
+pub struct Properties;
+
+/// Escapes `s` as the key of a property if `key`, or as its value
+fn escape(s: &str, key: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{c}' => escaped.push_str("\\f"),
+            // leading whitespace of values is skipped when read
+            ' ' if key || i == 0 => escaped.push_str("\\ "),
+            '=' | ':' | '#' | '!' if key => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+
+    escaped
+}
+
+impl Formatter for Properties {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        for var in vars {
+            writeln!(
+                writer,
+                "{}={}",
+                escape(&var.key, true),
+                escape(&var.value, false)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_properties() {
+        let mut vars = super::super::test::vars();
+        vars.push(EnvironmentRow::from("dev", "KEY:1 =", "!a b"));
+
+        let mut output = Vec::new();
+        Properties.write(&mut output, &vars).unwrap();
+        assert_eq!(
+            r#"A.B=x=y: #z
+EMPTY=
+MULTI=line "1"\n\tline 2\\
+UNICODE=\ \u00e9\u0001\u007f\ud83d\ude00
+KEY\:1\ \==!a b
+"#,
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use super::{Formatter, decoded, write_lines};
+use crate::db::model::EnvironmentRow;
+
+/// Shells that statements setting variables can be written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    /// Nushell
+    Nu,
+    /// PowerShell
+    Pwsh,
+}
+
+impl Shell {
+    /// Returns the shell named by `$SHELL`, or bash if it names none of them
+    pub fn detect() -> Self {
+        let shell = std::env::var("SHELL").unwrap_or_default();
+        match shell.rsplit('/').next().unwrap_or_default() {
+            "zsh" => Self::Zsh,
+            "fish" => Self::Fish,
+            "nu" => Self::Nu,
+            "pwsh" => Self::Pwsh,
+            _ => Self::Bash,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+            Self::Nu => "nu",
+            Self::Pwsh => "pwsh",
+        }
+    }
+
+    /// Fails if `key` cannot name a variable of the shell
+    fn check_key(self, key: &str) -> Result<()> {
+        let valid = match self {
+            Self::Bash | Self::Zsh => {
+                key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            Self::Fish => {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            Self::Nu => !key.is_empty(),
+            Self::Pwsh => !key.is_empty() && !key.contains(['{', '}', '`']),
+        };
+        ensure!(
+            valid,
+            "'{key}' is not a valid variable name in {}",
+            self.name()
+        );
+
+        Ok(())
+    }
+
+    /// Returns the statement setting `key` to `value`
+    fn set(self, key: &str, value: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("export {key}={}", posix_quoted(value)),
+            Self::Fish => format!("set -gx {key} {}", fish_quoted(value)),
+            Self::Nu => format!("$env.{} = {}", nu_key(key), nu_quoted(value)),
+            Self::Pwsh => format!("{} = {}", pwsh_var(key), pwsh_quoted(value)),
+        }
+    }
+
+    /// Returns the statement removing `key`, even if it is not set
+    fn unset(self, key: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("unset {key}"),
+            Self::Fish => format!("set -e {key}"),
+            Self::Nu => format!("hide-env --ignore-errors {}", nu_key(key)),
+            Self::Pwsh => format!(
+                "Remove-Item -LiteralPath {} -ErrorAction SilentlyContinue",
+                pwsh_quoted(&format!("Env:{key}"))
+            ),
+        }
+    }
+}
+
+/// Whether `key` can be written as is, without quotes
+fn is_identifier(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Single quotes, closed around every single quote of `value`
+fn posix_quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Single quotes, in which fish only reads `\\` and `\'` as escapes
+fn fish_quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Double quotes, with quotes, backslashes and control characters escaped
+fn nu_quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn nu_key(key: &str) -> String {
+    match is_identifier(key) {
+        true => key.to_owned(),
+        false => nu_quoted(key),
+    }
+}
+
+/// Single quotes, with every single quote doubled. PowerShell also reads the
+/// typographic single quotes as quotes.
+fn pwsh_quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+
+    quoted
+}
+
+fn pwsh_var(key: &str) -> String {
+    match is_identifier(key) {
+        true => format!("$env:{key}"),
+        false => format!("${{env:{key}}}"),
+    }
+}
+
+/// Statements setting the variables in a shell, or removing them if `unset`.
+/// Base64 encoded values are decoded, and must then be UTF-8.
+pub struct ShellEnv {
+    pub shell: Shell,
+    pub unset: bool,
+}
+
+impl Formatter for ShellEnv {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        write_lines(writer, vars, |var| {
+            self.shell.check_key(&var.key)?;
+            if self.unset {
+                return Ok(self.shell.unset(&var.key));
+            }
+
+            let value = decoded(var, "a shell")?;
+            Ok(self.shell.set(&var.key, &value))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::write;
+    use super::*;
+
+    fn vars() -> Vec<EnvironmentRow> {
+        vec![
+            EnvironmentRow::from("dev", "PLAIN", "a b"),
+            EnvironmentRow::from("dev", "QUOTES", "it's \"$HOME\" `id` \\n ‘x’"),
+            EnvironmentRow::from("dev", "MULTI", "line 1\nline 2"),
+        ]
+    }
+
+    #[test]
+    fn test_posix() {
+        let set = ShellEnv {
+            shell: Shell::Bash,
+            unset: false,
+        };
+        let unset = ShellEnv {
+            shell: Shell::Zsh,
+            unset: true,
+        };
+        assert_eq!(
+            "export PLAIN='a b'\nexport QUOTES='it'\\''s \"$HOME\" `id` \\n ‘x’'\nexport \
+             MULTI='line 1\nline 2'\n",
+            write(&set, &vars()).unwrap()
+        );
+        assert_eq!(
+            "unset PLAIN\nunset QUOTES\nunset MULTI\n",
+            write(&unset, &vars()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fish() {
+        let set = ShellEnv {
+            shell: Shell::Fish,
+            unset: false,
+        };
+        let unset = ShellEnv {
+            shell: Shell::Fish,
+            unset: true,
+        };
+        assert_eq!(
+            "set -gx PLAIN 'a b'\nset -gx QUOTES 'it\\'s \"$HOME\" `id` \\\\n ‘x’'\nset -gx MULTI \
+             'line 1\nline 2'\n",
+            write(&set, &vars()).unwrap()
+        );
+        assert_eq!("set -e PLAIN\n", write(&unset, &vars()[..1]).unwrap());
+    }
+
+    #[test]
+    fn test_nu() {
+        let set = ShellEnv {
+            shell: Shell::Nu,
+            unset: false,
+        };
+        let unset = ShellEnv {
+            shell: Shell::Nu,
+            unset: true,
+        };
+        let mut vars = vars();
+        vars.push(EnvironmentRow::from("dev", "A.B", "\u{1}"));
+        assert_eq!(
+            "$env.PLAIN = \"a b\"\n$env.QUOTES = \"it's \\\"$HOME\\\" `id` \\\\n \
+             ‘x’\"\n$env.MULTI = \"line 1\\nline 2\"\n$env.\"A.B\" = \"\\u{1}\"\n",
+            write(&set, &vars).unwrap()
+        );
+        assert_eq!(
+            "hide-env --ignore-errors \"A.B\"\n",
+            write(&unset, &vars[3..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pwsh() {
+        let set = ShellEnv {
+            shell: Shell::Pwsh,
+            unset: false,
+        };
+        let unset = ShellEnv {
+            shell: Shell::Pwsh,
+            unset: true,
+        };
+        let mut vars = vars();
+        vars.push(EnvironmentRow::from("dev", "A.B", "x"));
+        assert_eq!(
+            "$env:PLAIN = 'a b'\n$env:QUOTES = 'it''s \"$HOME\" `id` \\n ‘‘x’’'\n$env:MULTI = \
+             'line 1\nline 2'\n${env:A.B} = 'x'\n",
+            write(&set, &vars).unwrap()
+        );
+        assert_eq!(
+            "Remove-Item -LiteralPath 'Env:A.B' -ErrorAction SilentlyContinue\n",
+            write(&unset, &vars[3..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let set = ShellEnv {
+            shell: Shell::Bash,
+            unset: false,
+        };
+        let unset = ShellEnv {
+            shell: Shell::Fish,
+            unset: true,
+        };
+        let vars = vec![
+            EnvironmentRow::from("dev", "OK", "1"),
+            EnvironmentRow::from("dev", "A.B", "2"),
+        ];
+        let err = write(&set, &vars).unwrap_err();
+        assert_eq!(
+            "'A.B' is not a valid variable name in bash",
+            err.to_string()
+        );
+        assert!(write(&unset, &vars).is_err());
+    }
+}
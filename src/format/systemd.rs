@@ -0,0 +1,182 @@
+use std::io::Write;
+
+use anyhow::{Result, bail, ensure};
+
+use super::{Formatter, decoded, write_lines};
+use crate::db::model::EnvironmentRow;
+
+/// Fails if `key` is not a name systemd accepts for an environment variable
+fn check_key(key: &str) -> Result<()> {
+    ensure!(
+        key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "'{key}' is not a valid variable name for systemd"
+    );
+
+    Ok(())
+}
+
+/// File read by `EnvironmentFile=`, with every value in double quotes. Within
+/// them, systemd only reads `\"`, `\\`, `` \` `` and `\$` as escapes and keeps
+/// line breaks, so the file can also be sourced by a POSIX shell. Base64
+/// encoded values are decoded.
+pub struct Systemd;
+
+fn env_file_quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '`' | '$') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+impl Formatter for Systemd {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        write_lines(writer, vars, |var| {
+            check_key(&var.key)?;
+            let value = decoded(var, "an environment file")?;
+            Ok(format!("{}={}", var.key, env_file_quoted(&value)))
+        })
+    }
+}
+
+/// Drop-in of a unit setting its variables with `Environment=` lines. Each
+/// assignment is a single double quoted word, in which systemd expands `%`
+/// specifiers and then reads C escapes. Base64 encoded values are decoded.
+pub struct SystemdDropin {
+    /// section of the unit holding `Environment=`
+    section: &'static str,
+}
+
+impl SystemdDropin {
+    /// Returns the drop-in of `unit`, a service unless its name says otherwise
+    pub fn new(unit: &str) -> Result<Self> {
+        ensure!(
+            !unit.is_empty()
+                && unit.len() <= 255
+                && unit.chars().all(|c| c.is_ascii_alphanumeric()
+                    || matches!(c, ':' | '-' | '_' | '.' | '@' | '\\')),
+            "'{unit}' is not a valid unit name"
+        );
+
+        let section = match unit.rsplit_once('.').map(|(_, suffix)| suffix) {
+            None | Some("service") => "Service",
+            Some("socket") => "Socket",
+            Some("mount") => "Mount",
+            Some("swap") => "Swap",
+            Some(suffix) => bail!("units of type '{suffix}' cannot set environment variables"),
+        };
+
+        Ok(Self { section })
+    }
+}
+
+fn dropin_quoted(assignment: &str) -> String {
+    let mut quoted = String::with_capacity(assignment.len() + 2);
+    quoted.push('"');
+    for c in assignment.chars() {
+        match c {
+            '%' => quoted.push_str("%%"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+impl Formatter for SystemdDropin {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        let mut lines = Vec::with_capacity(vars.len());
+        for var in vars {
+            check_key(&var.key)?;
+            let value = decoded(var, "a unit")?;
+            lines.push(format!(
+                "Environment={}",
+                dropin_quoted(&format!("{}={value}", var.key))
+            ));
+        }
+
+        writeln!(writer, "[{}]", self.section)?;
+        for line in lines {
+            writeln!(writer, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::write;
+    use super::*;
+
+    fn vars() -> Vec<EnvironmentRow> {
+        vec![
+            EnvironmentRow::from("dev", "SPACES", " a  b "),
+            EnvironmentRow::from("dev", "QUOTES", r#"say "hi" 'x'"#),
+            EnvironmentRow::from("dev", "SPECIFIER", "100% of %i"),
+            EnvironmentRow::from("dev", "BACKSLASH", r"C:\dir\n"),
+            EnvironmentRow::from("dev", "SHELL", "$HOME `id`"),
+            EnvironmentRow::from("dev", "MULTI", "a\nb\tc"),
+        ]
+    }
+
+    #[test]
+    fn test_environment_file() {
+        assert_eq!(
+            r#"SPACES=" a  b "
+QUOTES="say \"hi\" 'x'"
+SPECIFIER="100% of %i"
+BACKSLASH="C:\\dir\\n"
+SHELL="\$HOME \`id\`"
+MULTI="a
+b	c"
+"#,
+            write(&Systemd, &vars()).unwrap()
+        );
+
+        let vars = [EnvironmentRow::from("dev", "A.B", "x")];
+        assert_eq!(
+            "'A.B' is not a valid variable name for systemd",
+            write(&Systemd, &vars).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_dropin() {
+        let dropin = SystemdDropin::new("app.service").unwrap();
+        assert_eq!(
+            r#"[Service]
+Environment="SPACES= a  b "
+Environment="QUOTES=say \"hi\" 'x'"
+Environment="SPECIFIER=100%% of %%i"
+Environment="BACKSLASH=C:\\dir\\n"
+Environment="SHELL=$HOME `id`"
+Environment="MULTI=a\nb\tc"
+"#,
+            write(&dropin, &vars()).unwrap()
+        );
+
+        let vars = [EnvironmentRow::from("dev", "BELL", "\u{7}")];
+        assert_eq!(
+            "[Socket]\nEnvironment=\"BELL=\\u0007\"\n",
+            write(&SystemdDropin::new("app.socket").unwrap(), &vars).unwrap()
+        );
+        assert!(SystemdDropin::new("app").is_ok());
+        assert!(SystemdDropin::new("app.timer").is_err());
+        assert!(SystemdDropin::new("my app.service").is_err());
+    }
+}
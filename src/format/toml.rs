@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{Formatter, double_quoted};
+use crate::db::model::EnvironmentRow;
+use crate::nested::{self, Node};
+
+/// Top-level table of every key and its value, as basic strings. Keys are
+/// nested in tables on `separator` if there is one.
+pub struct Toml {
+    pub separator: Option<String>,
+}
+
+/// Returns `key` bare if TOML allows it, or double quoted
+fn key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'));
+
+    match bare {
+        true => key.to_owned(),
+        false => double_quoted(key),
+    }
+}
+
+/// Writes the values of `table`, then each of its tables under a header
+/// naming its `path`
+fn write_table(
+    writer: &mut dyn Write,
+    table: &BTreeMap<&str, Node>,
+    path: &mut Vec<String>,
+) -> Result<()> {
+    for (k, node) in table {
+        if let Node::Value(value) = node {
+            writeln!(writer, "{} = {}", key(k), double_quoted(value))?;
+        }
+    }
+
+    for (k, node) in table {
+        if let Node::Table(table) = node {
+            path.push(key(k));
+            // tables holding nothing but tables need no header
+            if table.values().any(|node| matches!(node, Node::Value(_))) {
+                writeln!(writer, "\n[{}]", path.join("."))?;
+            }
+            write_table(writer, table, path)?;
+            path.pop();
+        }
+    }
+
+    Ok(())
+}
+
+impl Formatter for Toml {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        let root = nested::nest(vars, self.separator.as_deref())?;
+        write_table(writer, &root, &mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_toml() {
+        let vars = super::super::test::vars();
+        let mut output = Vec::new();
+        Toml { separator: None }.write(&mut output, &vars).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#""A.B" = "x=y: #z"
+EMPTY = ""
+MULTI = "line \"1\"\n\tline 2\\"
+UNICODE = " é\u0001\u007f😀"
+"#,
+            output
+        );
+
+        let read: BTreeMap<String, String> = ::toml::from_str(&output).unwrap();
+        for var in vars {
+            assert_eq!(var.value, read[&var.key]);
+        }
+    }
+
+    #[test]
+    fn test_toml_nested() {
+        let vars = vec![
+            EnvironmentRow::from("dev", "APP__DB__HOST", "localhost"),
+            EnvironmentRow::from("dev", "APP__DB__PORT", "5432"),
+            EnvironmentRow::from("dev", "APP__NAME.X", "demo"),
+            EnvironmentRow::from("dev", "DEBUG", "true"),
+        ];
+        let mut output = Vec::new();
+        Toml {
+            separator: Some("__".to_owned()),
+        }
+        .write(&mut output, &vars)
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"DEBUG = "true"
+
+[APP]
+"NAME.X" = "demo"
+
+[APP.DB]
+HOST = "localhost"
+PORT = "5432"
+"#,
+            output
+        );
+
+        let read: ::toml::Table = ::toml::from_str(&output).unwrap();
+        assert_eq!("5432", read["APP"]["DB"]["PORT"].as_str().unwrap());
+    }
+}
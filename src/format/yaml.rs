@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{Formatter, double_quoted};
+use crate::db::model::EnvironmentRow;
+use crate::nested::{self, Node};
+
+/// Mapping of every key to its value, always double quoted so that values
+/// such as `yes` or `1.0` are read as strings. Keys are nested on `separator`
+/// if there is one.
+pub struct Yaml {
+    pub separator: Option<String>,
+}
+
+/// Plain scalars read as booleans or null by YAML 1.1 parsers
+const RESERVED: [&str; 10] = [
+    "y", "n", "yes", "no", "on", "off", "true", "false", "null", "~",
+];
+
+/// Returns `key` plain if it would be read as a string, or double quoted
+pub(super) fn key(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        && !RESERVED.contains(&key.to_lowercase().as_str());
+
+    match plain {
+        true => key.to_owned(),
+        false => double_quoted(key),
+    }
+}
+
+fn write_mapping(writer: &mut dyn Write, table: &BTreeMap<&str, Node>, depth: usize) -> Result<()> {
+    let indent = "  ".repeat(depth);
+    for (k, node) in table {
+        match node {
+            Node::Value(value) => writeln!(writer, "{indent}{}: {}", key(k), double_quoted(value))?,
+            Node::Table(table) => {
+                writeln!(writer, "{indent}{}:", key(k))?;
+                write_mapping(writer, table, depth + 1)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Formatter for Yaml {
+    fn write(&self, writer: &mut dyn Write, vars: &[EnvironmentRow]) -> Result<()> {
+        let root = nested::nest(vars, self.separator.as_deref())?;
+        if root.is_empty() {
+            writeln!(writer, "{{}}")?;
+        }
+
+        write_mapping(writer, &root, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_yaml() {
+        let mut vars = super::super::test::vars();
+        vars.push(EnvironmentRow::from("dev", "NO", "no"));
+        vars.push(EnvironmentRow::from("dev", "1KEY", "1.0"));
+
+        let mut output = Vec::new();
+        Yaml { separator: None }.write(&mut output, &vars).unwrap();
+        assert_eq!(
+            r#""1KEY": "1.0"
+A.B: "x=y: #z"
+EMPTY: ""
+MULTI: "line \"1\"\n\tline 2\\"
+"NO": "no"
+UNICODE: " é\u0001\u007f😀"
+"#,
+            String::from_utf8(output).unwrap()
+        );
+
+        let vars = vec![
+            EnvironmentRow::from("dev", "DB__HOST", "localhost"),
+            EnvironmentRow::from("dev", "DB__POOL__NO", "no"),
+            EnvironmentRow::from("dev", "DEBUG", "true"),
+        ];
+        let mut output = Vec::new();
+        Yaml {
+            separator: Some("__".to_owned()),
+        }
+        .write(&mut output, &vars)
+        .unwrap();
+        assert_eq!(
+            "DB:\n  HOST: \"localhost\"\n  POOL:\n    \"NO\": \"no\"\nDEBUG: \"true\"\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
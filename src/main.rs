@@ -1,9 +1,15 @@
 mod command;
 mod core;
 mod db;
+mod dotenv;
 mod editor;
+mod format;
+mod interpolate;
+mod nested;
 mod ops;
+mod schema;
 mod subproc;
+mod time;
 mod utils;
 
 use std::io::{self, IsTerminal, Write};
@@ -30,13 +36,18 @@ static HELP_TEMPLATE: &str = "\
 struct Envelope {
     #[command(subcommand)]
     envelope: Option<EnvelopeCmd>,
+
+    /// Answer yes to every confirmation, such as the one changing a protected
+    /// environment
+    #[arg(short, long, global = true)]
+    yes: bool,
 }
 
 impl Envelope {
     #[tokio::main(flavor = "current_thread")]
     async fn run(self) -> anyhow::Result<()> {
         if let Some(cmd) = self.envelope {
-            cmd.run().await?
+            cmd.run(self.yes).await?
         } else if !io::stdin().is_terminal() {
             ops::print_from_stdin().await?
         } else {
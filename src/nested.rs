@@ -0,0 +1,330 @@
+//! Conversion between nested documents and flat variables.
+//!
+//! Nested keys are joined with a separator when a document is imported, so
+//! that
+//!
+//! ```json
+//! { "db": { "host": "localhost", "ports": [5432, 5433] } }
+//! ```
+//!
+//! becomes `DB__HOST=localhost`, `DB__PORTS__0=5432` and `DB__PORTS__1=5433`,
+//! or `DB__PORTS=[5432,5433]` if arrays are JSON-encoded. Exports split keys
+//! on the same separator to nest them again, arrays being nested as tables
+//! keyed by index.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail, ensure};
+use serde_json::Value;
+
+use crate::db::model::EnvironmentRow;
+
+/// Formats of the documents that can be imported
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Document {
+    Json,
+    Yaml,
+    Toml,
+    /// Kubernetes Secret manifest
+    K8sSecret,
+    /// Kubernetes ConfigMap manifest
+    K8sConfigmap,
+}
+
+impl Document {
+    /// Parses `input`, which must be an object at its top level. Manifests
+    /// are parsed as YAML.
+    pub fn parse(self, input: &str) -> Result<Value> {
+        let doc = match self {
+            Self::Json => serde_json::from_str(input).context("invalid JSON document")?,
+            Self::Yaml | Self::K8sSecret | Self::K8sConfigmap => {
+                let mut doc: serde_norway::Value =
+                    serde_norway::from_str(input).context("invalid YAML document")?;
+                doc.apply_merge().context("invalid YAML document")?;
+                from_yaml(doc)?
+            }
+            Self::Toml => {
+                let doc: toml::Table = toml::from_str(input).context("invalid TOML document")?;
+                from_toml(toml::Value::Table(doc))
+            }
+        };
+        ensure!(
+            doc.is_object(),
+            "expected an object at the top of the document"
+        );
+
+        Ok(doc)
+    }
+}
+
+fn from_number(n: f64) -> Value {
+    serde_json::Number::from_f64(n).map_or_else(|| Value::String(n.to_string()), Value::Number)
+}
+
+fn from_yaml(value: serde_norway::Value) -> Result<Value> {
+    use serde_norway::Value as Yaml;
+
+    Ok(match value {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(b) => Value::Bool(b),
+        Yaml::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) => Value::from(i),
+            (_, Some(u), _) => Value::from(u),
+            (_, _, Some(f)) => from_number(f),
+            _ => Value::String(n.to_string()),
+        },
+        Yaml::String(s) => Value::String(s),
+        Yaml::Sequence(seq) => Value::Array(seq.into_iter().map(from_yaml).collect::<Result<_>>()?),
+        Yaml::Mapping(map) => {
+            let mut object = serde_json::Map::new();
+            for (key, value) in map {
+                let key = match key {
+                    Yaml::String(s) => s,
+                    Yaml::Bool(b) => b.to_string(),
+                    Yaml::Number(n) => n.to_string(),
+                    Yaml::Null => "null".to_owned(),
+                    _ => bail!("keys of a YAML mapping must be scalars"),
+                };
+                object.insert(key, from_yaml(value)?);
+            }
+            Value::Object(object)
+        }
+        Yaml::Tagged(tagged) => from_yaml(tagged.value)?,
+    })
+}
+
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => from_number(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// How arrays of a document are imported
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum Arrays {
+    /// One variable per element, keyed by its index
+    #[default]
+    Index,
+    /// One variable holding the JSON encoding of the array
+    Json,
+}
+
+/// Flattens the object `doc` into variables, whose keys are the keys leading
+/// to each value joined with `separator`. Strings are imported as is, other
+/// scalars as written in JSON and nulls as empty strings. Returns the
+/// variables sorted by key.
+pub fn flatten(doc: &Value, separator: &str, arrays: Arrays) -> Result<Vec<(String, String)>> {
+    let mut vars = BTreeMap::new();
+    flatten_into(&mut vars, None, doc, separator, arrays)?;
+
+    Ok(vars.into_iter().collect())
+}
+
+fn flatten_into(
+    vars: &mut BTreeMap<String, String>,
+    prefix: Option<&str>,
+    value: &Value,
+    separator: &str,
+    arrays: Arrays,
+) -> Result<()> {
+    let join = |key: &str| match prefix {
+        Some(prefix) => format!("{prefix}{separator}{key}"),
+        None => key.to_owned(),
+    };
+
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(object) => object.iter().map(|(k, v)| (join(k), v)).collect(),
+        Value::Array(array) if matches!(arrays, Arrays::Index) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (join(&i.to_string()), v))
+            .collect(),
+        value => {
+            let key = prefix.context("expected an object at the top of the document")?;
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            // keys are stored uppercased, so `a` and `A` would be the same
+            // variable
+            ensure!(
+                vars.insert(key.to_uppercase(), value).is_none(),
+                "'{}' is set more than once in the document",
+                key.to_uppercase()
+            );
+            return Ok(());
+        }
+    };
+
+    for (key, value) in children {
+        flatten_into(vars, Some(&key), value, separator, arrays)?;
+    }
+
+    Ok(())
+}
+
+/// Value of a variable, or table of the variables nested under a key
+#[derive(Debug, PartialEq, Eq)]
+pub enum Node<'a> {
+    Value(&'a str),
+    Table(BTreeMap<&'a str, Node<'a>>),
+}
+
+/// Nests `vars` by splitting their keys on `separator`, or keeps them flat if
+/// there is none
+pub fn nest<'a>(
+    vars: &'a [EnvironmentRow],
+    separator: Option<&str>,
+) -> Result<BTreeMap<&'a str, Node<'a>>> {
+    let mut root = BTreeMap::new();
+    for var in vars {
+        let mut path: Vec<&str> = match separator {
+            Some(separator) => var.key.split(separator).collect(),
+            None => vec![&var.key],
+        };
+        let last = path.pop().unwrap_or_default();
+
+        let mut table = &mut root;
+        for (i, segment) in path.iter().enumerate() {
+            let node = table
+                .entry(*segment)
+                .or_insert_with(|| Node::Table(BTreeMap::new()));
+            table = match node {
+                Node::Table(table) => table,
+                Node::Value(_) => {
+                    let parent = path[..=i].join(separator.unwrap_or_default());
+                    bail!(
+                        "cannot nest '{}' under '{parent}', which has a value",
+                        var.key
+                    )
+                }
+            };
+        }
+        ensure!(
+            table.insert(last, Node::Value(&var.value)).is_none(),
+            "cannot nest variables under '{}', which has a value",
+            var.key
+        );
+    }
+
+    Ok(root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "db": { "host": "localhost", "port": 5432, "replicas": ["a", "b"] },
+        "debug": true,
+        "empty": null
+    }"#;
+
+    fn strs(vars: &[(String, String)]) -> Vec<(&str, &str)> {
+        vars.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+    }
+
+    #[test]
+    fn test_flatten() {
+        let doc = Document::Json.parse(JSON).unwrap();
+        assert_eq!(
+            vec![
+                ("DB__HOST", "localhost"),
+                ("DB__PORT", "5432"),
+                ("DB__REPLICAS__0", "a"),
+                ("DB__REPLICAS__1", "b"),
+                ("DEBUG", "true"),
+                ("EMPTY", ""),
+            ],
+            strs(&flatten(&doc, "__", Arrays::Index).unwrap())
+        );
+        assert_eq!(
+            vec![
+                ("DB.HOST", "localhost"),
+                ("DB.PORT", "5432"),
+                ("DB.REPLICAS", r#"["a","b"]"#),
+                ("DEBUG", "true"),
+                ("EMPTY", ""),
+            ],
+            strs(&flatten(&doc, ".", Arrays::Json).unwrap())
+        );
+
+        let doc = Document::Json
+            .parse(r#"{"db": {"host": 1}, "DB__HOST": 2}"#)
+            .unwrap();
+        assert_eq!(
+            "'DB__HOST' is set more than once in the document",
+            flatten(&doc, "__", Arrays::Index).unwrap_err().to_string()
+        );
+        assert!(Document::Json.parse("[1]").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let json = Document::Json.parse(JSON).unwrap();
+
+        let yaml = "db:\n  host: localhost\n  port: 5432\n  replicas:\n    - a\n    - b\ndebug: \
+                    true\nempty: ~\n";
+        assert_eq!(json, Document::Yaml.parse(yaml).unwrap());
+
+        let toml =
+            "debug = true\n[db]\nhost = \"localhost\"\nport = 5432\nreplicas = [\"a\", \"b\"]\n";
+        let mut expected = json.clone();
+        expected.as_object_mut().unwrap().remove("empty");
+        assert_eq!(expected, Document::Toml.parse(toml).unwrap());
+
+        let yaml = "base: &base\n  a: 1\nmerged:\n  <<: *base\n  1: one\n";
+        assert_eq!(
+            vec![("BASE__A", "1"), ("MERGED__1", "one"), ("MERGED__A", "1")],
+            strs(&flatten(&Document::Yaml.parse(yaml).unwrap(), "__", Arrays::Index).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_nest() {
+        let vars = vec![
+            EnvironmentRow::from("dev", "DB__HOST", "localhost"),
+            EnvironmentRow::from("dev", "DB__POOL__MAX", "10"),
+            EnvironmentRow::from("dev", "DEBUG", "true"),
+        ];
+        let root = nest(&vars, Some("__")).unwrap();
+        assert_eq!(
+            BTreeMap::from([
+                (
+                    "DB",
+                    Node::Table(BTreeMap::from([
+                        ("HOST", Node::Value("localhost")),
+                        (
+                            "POOL",
+                            Node::Table(BTreeMap::from([("MAX", Node::Value("10"))]))
+                        ),
+                    ]))
+                ),
+                ("DEBUG", Node::Value("true")),
+            ]),
+            root
+        );
+        assert_eq!(3, nest(&vars, None).unwrap().len());
+
+        let vars = vec![
+            EnvironmentRow::from("dev", "DB", "x"),
+            EnvironmentRow::from("dev", "DB__HOST", "localhost"),
+        ];
+        assert_eq!(
+            "cannot nest 'DB__HOST' under 'DB', which has a value",
+            nest(&vars, Some("__")).unwrap_err().to_string()
+        );
+    }
+}
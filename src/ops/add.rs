@@ -1,45 +1,154 @@
-use std::io::{BufRead, Write};
+use std::collections::HashMap;
+use std::io::{Read, Write};
 
-use anyhow::{Result, ensure};
+use anyhow::{Context, Result, bail, ensure};
+use base64::prelude::{BASE64_STANDARD, Engine};
 
-use crate::db::EnvelopeDb;
+use crate::db::{Change, EnvelopeDb};
+use crate::dotenv::{self, Item};
+use crate::format::{self, Kind};
+use crate::nested::{self, Arrays, Document};
 
 /// Adds a single key-value element to the database
 ///
 /// If the value of v is None, an empty string is inserted
-pub async fn add_var(db: &EnvelopeDb, env: &str, k: &str, v: &str) -> Result<()> {
+pub async fn add_var(
+    db: &EnvelopeDb,
+    env: &str,
+    k: &str,
+    v: &str,
+    change: Change<'_>,
+) -> Result<()> {
+    ensure!(!k.starts_with('#'), "variable name cannot start with '#'");
+
+    let mut tx = db.begin_with(change).await?;
+    tx.insert(env, k, v).await?;
+    tx.commit().await
+}
+
+/// Adds a single variable whose value is the base64 encoding of arbitrary
+/// bytes, which are decoded when the variable is injected into a command
+pub async fn add_base64_var(
+    db: &EnvelopeDb,
+    env: &str,
+    k: &str,
+    v: &str,
+    change: Change<'_>,
+) -> Result<()> {
     ensure!(!k.starts_with('#'), "variable name cannot start with '#'");
+    ensure!(
+        BASE64_STANDARD.decode(v).is_ok(),
+        "value of '{}' is not valid base64",
+        k.to_uppercase()
+    );
 
-    db.insert(env, k, v).await?;
+    let mut tx = db.begin_with(change).await?;
+    tx.insert_base64(env, k, v).await?;
+    tx.commit().await
+}
 
-    Ok(())
+/// Values owned by `env` by key, along with whether they are base64 encoded
+async fn own_values(db: &EnvelopeDb, env: &str) -> Result<HashMap<String, (String, bool)>> {
+    Ok(super::edit::own_vars(db, env)
+        .await?
+        .into_iter()
+        .map(|var| (var.key, (var.value, var.base64)))
+        .collect())
 }
 
-pub async fn import<W: Write, R: BufRead>(
-    reader: R,
+/// Imports the variables read from `reader` into `env`. Either every
+/// variable is imported or none is.
+///
+/// Lines that cannot be parsed are skipped with a warning, or fail the import
+/// if `strict`.
+pub async fn import<W: Write, R: Read>(
+    mut reader: R,
     writer: &mut W,
     db: &EnvelopeDb,
     env: &str,
+    strict: bool,
+    change: Change<'_>,
 ) -> Result<()> {
-    for line in reader.lines() {
-        if line.is_err() {
-            continue;
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .context("failed to read variables")?;
+
+    let mut vars = Vec::new();
+    for item in dotenv::parse(&input) {
+        match item {
+            Ok(Item::Var { key, value }) => vars.push((key, value)),
+            Ok(Item::Comment(comment)) => writeln!(writer, "skipping #{comment}")?,
+            Ok(Item::Blank) => {}
+            Err(err) if strict => return Err(err.into()),
+            Err(err) => writeln!(writer, "{err}, skipping")?,
         }
+    }
 
-        let line = line.unwrap();
-        if line.starts_with('#') {
-            writeln!(writer, "skipping {line}")?;
-            continue;
+    // variables keeping their value are not recorded again
+    let current = own_values(db, env).await?;
+    let mut tx = db.begin_with(change).await?;
+    for (k, v) in vars {
+        let unchanged = current.get(&k.to_uppercase()) == Some(&(v.clone(), false));
+        if !unchanged {
+            tx.insert(env, &k, &v).await?;
         }
+    }
 
-        if let Some((k, v)) = line.split_once('=') {
-            db.insert(env, k, v).await?;
-        } else {
-            writeln!(writer, "invalid {line}, skipping")?;
+    tx.commit().await
+}
+
+/// Imports the JSON, YAML or TOML `document` read from `reader` into `env`,
+/// flattening its nested keys with `separator`, or the data of a Kubernetes
+/// manifest. Either every variable is imported or none is. Returns the keys of
+/// the imported variables.
+pub async fn import_document<R: Read>(
+    mut reader: R,
+    db: &EnvelopeDb,
+    env: &str,
+    document: Document,
+    separator: &str,
+    arrays: Arrays,
+    change: Change<'_>,
+) -> Result<Vec<String>> {
+    ensure!(
+        !separator.is_empty(),
+        "the separator of nested keys cannot be empty"
+    );
+
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .context("failed to read document")?;
+
+    let doc = document.parse(&input)?;
+    let vars = match document {
+        Document::K8sSecret => format::read_manifest(doc, Kind::Secret)?,
+        Document::K8sConfigmap => format::read_manifest(doc, Kind::ConfigMap)?,
+        _ => nested::flatten(&doc, separator, arrays)?
+            .into_iter()
+            .map(|(k, v)| (k, v, false))
+            .collect(),
+    };
+    if let Some((k, ..)) = vars.iter().find(|(k, ..)| k.starts_with('#')) {
+        bail!("variable name '{k}' cannot start with '#'");
+    }
+
+    // variables keeping their value are not recorded again
+    let current = own_values(db, env).await?;
+    let mut tx = db.begin_with(change).await?;
+    for (k, v, base64) in &vars {
+        if current.get(&k.to_uppercase()) == Some(&(v.clone(), *base64)) {
+            continue;
+        }
+        match base64 {
+            true => tx.insert_base64(env, k, v).await?,
+            false => tx.insert(env, k, v).await?,
         }
     }
+    tx.commit().await?;
 
-    Ok(())
+    Ok(vars.into_iter().map(|(k, ..)| k.to_uppercase()).collect())
 }
 
 #[cfg(test)]
@@ -47,15 +156,45 @@ mod test {
     use std::io::BufReader;
 
     use sqlx::SqlitePool;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 
     use super::*;
-    use crate::db::EnvelopeDb;
     use crate::db::model::EnvironmentRow;
+    use crate::db::{Change, EnvelopeDb};
 
     pub fn stdin_input(s: &str) -> BufReader<&[u8]> {
         BufReader::new(s.as_bytes())
     }
 
+    #[sqlx::test]
+    async fn test_add_base64_var(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        add_base64_var(&db, "dev", "cert", "AP8KAA==", Change::default())
+            .await
+            .unwrap();
+        assert!(
+            add_base64_var(&db, "dev", "cert", "not base64!", Change::default())
+                .await
+                .is_err()
+        );
+
+        let rows = db.list_kv_in_env("dev", Default::default()).await.unwrap();
+        assert_eq!(1, rows.len());
+        assert!(rows[0].base64);
+        assert_eq!(
+            vec![0, 255, b'\n', 0],
+            rows[0].bytes().unwrap().into_owned()
+        );
+
+        // a plain value replaces the encoded one
+        add_var(&db, "dev", "cert", "text", Change::default())
+            .await
+            .unwrap();
+        let rows = db.list_kv_in_env("dev", Default::default()).await.unwrap();
+        assert!(!rows[0].base64);
+        assert_eq!(b"text", rows[0].bytes().unwrap().as_ref());
+    }
+
     #[sqlx::test]
     async fn test_import(pool: SqlitePool) {
         let db = EnvelopeDb::with(pool.clone());
@@ -66,6 +205,8 @@ mod test {
             &mut output,
             &db,
             "prod",
+            false,
+            Change::default(),
         )
         .await;
         assert!(res.is_ok());
@@ -85,6 +226,20 @@ mod test {
             assert_eq!(key_expected[i], row.key);
             assert_eq!(value_expected[i], row.value);
         }
+
+        // only the changed variable is recorded again
+        import(
+            stdin_input("key1=value1\nkey2=changed"),
+            &mut output,
+            &db,
+            "prod",
+            false,
+            Change::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, db.history("prod", "key1").await.unwrap().len());
+        assert_eq!(2, db.history("prod", "key2").await.unwrap().len());
     }
 
     #[sqlx::test]
@@ -93,7 +248,15 @@ mod test {
 
         let mut output: Vec<u8> = Vec::new();
 
-        let res = import(stdin_input("# key1=value1"), &mut output, &db, "prod").await;
+        let res = import(
+            stdin_input("# key1=value1"),
+            &mut output,
+            &db,
+            "prod",
+            false,
+            Change::default(),
+        )
+        .await;
         assert!(res.is_ok());
         assert!(!output.is_empty());
 
@@ -121,6 +284,8 @@ mod test {
             &mut output,
             &db,
             "prod",
+            false,
+            Change::default(),
         )
         .await;
 
@@ -137,8 +302,168 @@ mod test {
 
         let output = String::from_utf8(output).unwrap();
         assert_eq!(
-            "skipping #k=v\nskipping #invalid-value\ninvalid key value, skipping\n",
+            "skipping #k=v\nskipping #invalid-value\nline 3: expected '=' after 'key', skipping\n",
             output
         );
     }
+
+    #[sqlx::test]
+    async fn test_import_dotenv(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        let input = "export URL=\"postgres://db # primary\" # comment\nNAME='a \\n \
+                     b'\nCERT=\"line 1\nline 2\"\nDEBUG = true # local only\n";
+
+        import(
+            stdin_input(input),
+            &mut Vec::new(),
+            &db,
+            "prod",
+            false,
+            Change::default(),
+        )
+        .await
+        .unwrap();
+
+        let rows = sqlx::query_as::<_, EnvironmentRow>(
+            "SELECT * FROM environments WHERE env = 'prod' ORDER BY key",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("prod", "CERT", "line 1\nline 2"),
+                EnvironmentRow::from("prod", "DEBUG", "true"),
+                EnvironmentRow::from("prod", "NAME", "a \\n b"),
+                EnvironmentRow::from("prod", "URL", "postgres://db # primary"),
+            ],
+            rows
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_import_strict(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        let err = import(
+            stdin_input("key1=value1\nkey2=\"value2\nkey3=value3"),
+            &mut Vec::new(),
+            &db,
+            "prod",
+            true,
+            Change::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!("line 2: missing closing quote", err.to_string());
+
+        let rows = sqlx::query_as::<_, EnvironmentRow>("SELECT * FROM environments")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_import_document(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let yaml = "db:\n  host: localhost\n  port: 5432\nhosts: [a, b]\n";
+
+        let keys = import_document(
+            stdin_input(yaml),
+            &db,
+            "prod",
+            Document::Yaml,
+            "__",
+            Arrays::Json,
+            Change::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec!["DB__HOST", "DB__PORT", "HOSTS"], keys);
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("prod", "DB__HOST", "localhost"),
+                EnvironmentRow::from("prod", "DB__PORT", "5432"),
+                EnvironmentRow::from("prod", "HOSTS", r#"["a","b"]"#),
+            ],
+            db.list_kv_in_env("prod", Default::default()).await.unwrap()
+        );
+
+        assert!(
+            import_document(
+                stdin_input("not: [valid"),
+                &db,
+                "prod",
+                Document::Yaml,
+                "__",
+                Arrays::Index,
+                Change::default(),
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            import_document(
+                stdin_input(yaml),
+                &db,
+                "prod",
+                Document::Yaml,
+                "",
+                Arrays::Index,
+                Change::default(),
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_import_rolls_back_on_error(
+        pool_opts: SqlitePoolOptions,
+        conn_opts: SqliteConnectOptions,
+    ) {
+        // total changes are tracked per connection, as in a real envelope
+        let pool = pool_opts
+            .max_connections(1)
+            .connect_with(conn_opts)
+            .await
+            .unwrap();
+        let db = EnvelopeDb::with(pool.clone());
+        db.insert("prod", "KEY1", "old").await.unwrap();
+        let changes = db.total_changes().await.unwrap();
+
+        sqlx::query(
+            r"CREATE TRIGGER reject_bad BEFORE INSERT ON environments
+            WHEN NEW.key = 'BAD'
+            BEGIN
+                SELECT RAISE(ABORT, 'rejected');
+            END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let res = import(
+            stdin_input(
+                "key1=new
+bad=value
+key2=value2",
+            ),
+            &mut Vec::new(),
+            &db,
+            "prod",
+            false,
+            Change::default(),
+        )
+        .await;
+        assert!(res.is_err());
+
+        let rows = sqlx::query_as::<_, EnvironmentRow>("SELECT * FROM environments")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(vec![EnvironmentRow::from("prod", "KEY1", "old")], rows);
+        assert_eq!(changes, db.total_changes().await.unwrap());
+    }
 }
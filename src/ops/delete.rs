@@ -1,18 +1,29 @@
 use anyhow::Result;
 
-use crate::db::EnvelopeDb;
+use crate::db::{Change, EnvelopeDb};
 
 /// Deletes every key found in an enviroment
-pub async fn delete_env(db: &EnvelopeDb, env: &str) -> Result<()> {
-    db.soft_delete_env(env).await
+pub async fn delete_env(db: &EnvelopeDb, env: &str, change: Change<'_>) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    tx.soft_delete_env(env).await?;
+    tx.commit().await
 }
 
 /// Deletes a key for every environments
-pub async fn delete_var_globally(db: &EnvelopeDb, key: &str) -> Result<()> {
-    db.soft_delete_keys(key).await
+pub async fn delete_var_globally(db: &EnvelopeDb, key: &str, change: Change<'_>) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    tx.soft_delete_keys(key).await?;
+    tx.commit().await
 }
 
 /// Deletes a key in a specific env
-pub async fn delete_var_in_env(db: &EnvelopeDb, env: &str, key: &str) -> Result<()> {
-    db.soft_delete_key_in_env(env, key).await
+pub async fn delete_var_in_env(
+    db: &EnvelopeDb,
+    env: &str,
+    key: &str,
+    change: Change<'_>,
+) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    tx.soft_delete_key_in_env(env, key).await?;
+    tx.commit().await
 }
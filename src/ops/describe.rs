@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use crate::db::EnvelopeDb;
+
+/// Sets the description of `key` in `env`, or in every environment if `env`
+/// is `None`. A `None` description removes it.
+pub async fn describe(
+    db: &EnvelopeDb,
+    env: Option<&str>,
+    key: &str,
+    description: Option<&str>,
+) -> Result<()> {
+    if let Some(env) = env {
+        ensure!(
+            db.env_exists(env).await?,
+            "environment '{env}' does not exist"
+        );
+    }
+
+    match description {
+        Some(description) => ensure!(
+            !description.trim().is_empty(),
+            "description cannot be empty"
+        ),
+        None => ensure!(
+            db.description(env, key).await?.is_some(),
+            "variable '{}' has no description",
+            key.to_uppercase()
+        ),
+    }
+
+    db.set_description(env, key, description).await
+}
+
+/// Writes the description of `key` in `env`, falling back to the one of every
+/// environment
+pub async fn show_description<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env: Option<&str>,
+    key: &str,
+) -> Result<()> {
+    let mut description = db.description(env, key).await?;
+    if description.is_none() && env.is_some() {
+        description = db.description(None, key).await?;
+    }
+
+    if let Some(description) = description {
+        writeln!(writer, "{description}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Change;
+    use crate::ops;
+
+    async fn shown(db: &EnvelopeDb, env: Option<&str>, key: &str) -> String {
+        let mut output = Vec::new();
+        show_description(&mut output, db, env, key).await.unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_describe(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_var(&db, "dev", "api_key", "k1", Change::default())
+            .await
+            .unwrap();
+        ops::add_var(&db, "prod", "api_key", "k2", Change::default())
+            .await
+            .unwrap();
+
+        describe(&db, None, "api_key", Some("Key of the payment API"))
+            .await
+            .unwrap();
+        describe(&db, Some("prod"), "API_KEY", Some("Live key"))
+            .await
+            .unwrap();
+        assert_eq!(
+            "Key of the payment API\n",
+            shown(&db, Some("dev"), "api_key").await
+        );
+        assert_eq!("Live key\n", shown(&db, Some("prod"), "api_key").await);
+        assert_eq!(
+            "Live key",
+            db.descriptions("prod").await.unwrap()["API_KEY"]
+        );
+
+        db.duplicate_env("prod", "staging").await.unwrap();
+        db.rename_key(None, "api_key", "payment_key").await.unwrap();
+        assert_eq!(
+            "Live key\n",
+            shown(&db, Some("staging"), "payment_key").await
+        );
+        assert_eq!("", shown(&db, None, "api_key").await);
+
+        describe(&db, Some("prod"), "payment_key", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            "Key of the payment API\n",
+            shown(&db, Some("prod"), "payment_key").await
+        );
+        assert!(
+            describe(&db, Some("prod"), "payment_key", None)
+                .await
+                .is_err()
+        );
+        assert!(
+            describe(&db, Some("missing"), "key", Some("text"))
+                .await
+                .is_err()
+        );
+        assert!(describe(&db, None, "key", Some(" ")).await.is_err());
+    }
+}
@@ -2,25 +2,50 @@ use std::io::Write;
 
 use anyhow::Result;
 
-use crate::db::EnvelopeDb;
+use super::secret::Masker;
 use crate::db::model::EnvironmentDiff;
+use crate::db::{EnvelopeDb, Resolve};
+use crate::dotenv;
 
-const ANSI_RED: &str = "\x1b[31m";
-const ANSI_GREEN: &str = "\x1b[32m";
-const ANSI_GRAY: &str = "\x1b[90m";
-const ANSI_DEFAULT: &str = "\x1b[0m";
+pub(super) const ANSI_RED: &str = "\x1b[31m";
+pub(super) const ANSI_GREEN: &str = "\x1b[32m";
+pub(super) const ANSI_GRAY: &str = "\x1b[90m";
+pub(super) const ANSI_DEFAULT: &str = "\x1b[0m";
 
-pub async fn diff<W: Write>(writer: &mut W, db: &EnvelopeDb, env1: &str, env2: &str) -> Result<()> {
-    let diffs = db.diff(env1, env2).await?;
+pub async fn diff<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env1: &str,
+    resolve1: Resolve,
+    env2: &str,
+    resolve2: Resolve,
+    reveal: bool,
+) -> Result<()> {
+    let diffs = db.diff(env1, resolve1, env2, resolve2).await?;
+    let masker = Masker::load(db, reveal).await?;
+    write_diffs(writer, diffs, &masker)
+}
+
+/// Renders `diffs` with the colors and markers used by the diff command,
+/// masking the values of secrets and quoting the ones spanning lines
+pub(crate) fn write_diffs<W: Write>(
+    writer: &mut W,
+    diffs: Vec<EnvironmentDiff>,
+    masker: &Masker,
+) -> Result<()> {
     for diff in diffs {
         match diff {
             EnvironmentDiff::InFirst(k, v) => {
+                let v = dotenv::quote(masker.mask(&k, &v));
                 writeln!(writer, "{ANSI_GREEN}+ {k}={v}{ANSI_DEFAULT}")?;
             }
             EnvironmentDiff::InSecond(k, v) => {
+                let v = dotenv::quote(masker.mask(&k, &v));
                 writeln!(writer, "{ANSI_RED}- {k}={v}{ANSI_DEFAULT}")?;
             }
             EnvironmentDiff::Different(k, v1, v2) => {
+                let v1 = dotenv::quote(masker.mask(&k, &v1));
+                let v2 = dotenv::quote(masker.mask(&k, &v2));
                 writeln!(writer, "{ANSI_GRAY}/ {k}={v1} -> {v2}{ANSI_DEFAULT}")?;
             }
         }
@@ -2,8 +2,9 @@ use anyhow::Result;
 
 use crate::db::EnvelopeDb;
 
+/// Moves `env` to the trash
 pub async fn drop(db: &EnvelopeDb, env: &str) -> Result<()> {
-    db.delete_env(env).await
+    db.trash_env(env).await
 }
 
 #[cfg(test)]
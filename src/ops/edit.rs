@@ -1,74 +1,168 @@
-use std::io::{BufRead, BufReader, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
 
-use anyhow::Result;
+use anyhow::{Context, Result, ensure};
+use base64::prelude::{BASE64_STANDARD, Engine};
 
 use crate::db::model::EnvironmentRow;
-use crate::db::{EnvelopeDb, Truncate};
+use crate::db::{Change, EnvelopeDb, Resolve, Truncate};
+use crate::dotenv::{self, Item};
 use crate::editor;
 
 pub struct EditorData {
     delete: Vec<String>,
     upsert: Vec<(String, String)>,
+    /// descriptions written as comments above the upserted keys, by
+    /// uppercased key
+    descriptions: BTreeMap<String, String>,
 }
 
-fn parse(bufr: BufReader<&[u8]>) -> EditorData {
-    let (mut delete, mut upsert) = (Vec::new(), Vec::new());
+/// Whether `comment` is a commented out variable, such as `#KEY=value`,
+/// rather than a description. The key must follow `#` directly, since
+/// descriptions are written as `# line`, and only the keys for which `exists`
+/// holds, given the uppercased key, can be commented out.
+fn commented_key(comment: &str, exists: impl Fn(&str) -> bool) -> Option<&str> {
+    let (k, _) = comment.split_once('=')?;
+    let k = k.trim_end();
+    let is_key = !k.is_empty() && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
 
-    for kv in bufr.lines() {
-        if kv.is_err() {
-            continue;
-        }
+    (is_key && exists(&k.to_uppercase())).then_some(k)
+}
 
-        let kv = kv.unwrap();
+/// Parses the variables written in the editor, failing on the first line
+/// that cannot be parsed. A commented out variable is a delete only if
+/// `exists` holds for its uppercased key, and a description otherwise.
+pub fn parse(input: &str, exists: impl Fn(&str) -> bool) -> Result<EditorData> {
+    let (mut delete, mut upsert) = (Vec::new(), Vec::new());
+    let mut descriptions = BTreeMap::new();
+    // comment lines read since the last variable, describing the next one
+    let mut comments: Vec<String> = Vec::new();
+    let hint = editor::HINT.strip_prefix('#');
 
-        match kv.starts_with("#") {
-            true => {
-                if let Some((k, _)) = kv.split_once("=") {
-                    delete.push(k[1..k.len()].trim().into());
+    for item in dotenv::parse(input) {
+        match item? {
+            Item::Comment(comment) if Some(comment.as_str()) == hint => {}
+            Item::Comment(comment) => match commented_key(&comment, &exists) {
+                Some(k) => {
+                    delete.push(k.into());
+                    comments.clear();
                 }
-            }
-            false => {
-                if let Some((k, v)) = kv.split_once("=") {
-                    upsert.push((k.trim().into(), v.trim().into()));
+                None => comments.push(comment.strip_prefix(' ').unwrap_or(&comment).into()),
+            },
+            Item::Var { key, value } => {
+                if !comments.is_empty() {
+                    descriptions.insert(key.to_uppercase(), comments.join("\n"));
                 }
+                upsert.push((key, value));
+                comments.clear();
             }
+            Item::Blank => comments.clear(),
         }
     }
 
-    EditorData { delete, upsert }
+    Ok(EditorData {
+        delete,
+        upsert,
+        descriptions,
+    })
 }
 
-pub async fn edit(db: &EnvelopeDb, env: &str) -> Result<()> {
+/// Lists the variables owned by `env`. Inherited variables belong to the
+/// parent environments, so only these are editable.
+pub(super) async fn own_vars(db: &EnvelopeDb, env: &str) -> Result<Vec<EnvironmentRow>> {
+    db.list_kv_in_env_alt(
+        env,
+        Truncate::None,
+        "da",
+        Resolve {
+            inherit: false,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Writes the variables of `env` to edit, each below its description
+async fn render(db: &EnvelopeDb, env: &str) -> Result<Vec<u8>> {
     let mut kv_list = Vec::new();
-    let envs: Vec<EnvironmentRow> = db.list_kv_in_env_alt(env, Truncate::None, "da").await?;
-    for env in envs {
-        writeln!(&mut kv_list, "{}={}", &env.key, &env.value)?;
+    let descriptions = db.descriptions(env).await?;
+    for env in own_vars(db, env).await? {
+        if let Some(description) = descriptions.get(&env.key) {
+            for line in description.lines() {
+                writeln!(&mut kv_list, "# {line}")?;
+            }
+        }
+        writeln!(&mut kv_list, "{}={}", &env.key, dotenv::quote(&env.value))?;
     }
 
-    let bytes = editor::spawn_with(&kv_list)?;
-    let EditorData { delete, upsert } = parse(BufReader::new(&bytes));
+    Ok(kv_list)
+}
+
+pub async fn edit(db: &EnvelopeDb, env: &str, change: Change<'_>) -> Result<()> {
+    let bytes = editor::spawn_with(&render(db, env).await?)?;
+    let input = String::from_utf8(bytes).context("edited variables are not valid UTF-8")?;
+
+    apply(db, env, &input, change).await
+}
 
+/// Applies the variables of `env` as edited in `input`. Only the variables
+/// that were added, changed or commented out are recorded.
+async fn apply(db: &EnvelopeDb, env: &str, input: &str, change: Change<'_>) -> Result<()> {
+    let descriptions = db.descriptions(env).await?;
+    let current: HashMap<String, EnvironmentRow> = own_vars(db, env)
+        .await?
+        .into_iter()
+        .map(|var| (var.key.clone(), var))
+        .collect();
+    let EditorData {
+        delete,
+        upsert,
+        descriptions: edited,
+    } = parse(input, |k| current.contains_key(k))?;
+
+    // the changes are applied all at once, or not at all
+    let mut tx = db.begin_with(change).await?;
     for k in delete {
-        db.soft_delete_key_in_env(env, &k).await?;
+        tx.soft_delete_key_in_env(env, &k).await?;
     }
 
     for (k, v) in upsert {
-        db.insert(env, &k, &v).await?;
+        let key = k.to_uppercase();
+        match current.get(&key) {
+            Some(var) if var.value == v => {}
+            // base64 encoded values are edited encoded, and stay so
+            Some(var) if var.base64 => {
+                ensure!(
+                    BASE64_STANDARD.decode(&v).is_ok(),
+                    "value of '{key}' is not valid base64"
+                );
+                tx.insert_base64(env, &k, &v).await?;
+            }
+            _ => tx.insert(env, &k, &v).await?,
+        }
+
+        // only changed descriptions are set on `env`, so that the ones of
+        // every environment keep applying
+        let description = edited.get(&key);
+        if description != descriptions.get(&key) {
+            tx.set_description(Some(env), &key, description.map(String::as_str))
+                .await?;
+        }
     }
 
-    Ok(())
+    tx.commit().await
 }
 
 #[cfg(test)]
 mod test {
-    use std::io::BufReader;
+    use sqlx::SqlitePool;
 
     use super::*;
 
     #[test]
     fn test_upsert() {
-        let bytes = b"key1=value1\nkey2=value2\n\nkey3=value3\n\n\nk4=v4";
-        let EditorData { delete, upsert } = parse(BufReader::new(bytes));
+        let input = "key1=value1\nkey2=value2\n\nkey3=value3\n\n\nk4=v4";
+        let EditorData { delete, upsert, .. } = parse(input, |_| true).unwrap();
         assert_eq!(4, upsert.len());
         for (i, kv) in vec![("key1", "value1"), ("key2", "value2"), ("key3", "value3")]
             .into_iter()
@@ -81,19 +175,17 @@ mod test {
 
     #[test]
     fn test_delete() {
-        let bytes = b"#key1=value1\n#  key2=value2\n\n\n#key3=value3";
-        let EditorData { delete, upsert } = parse(BufReader::new(bytes));
-        assert_eq!(3, delete.len());
-        for (i, k) in ["key1", "key2", "key3"].iter().enumerate() {
-            assert_eq!(&delete[i], k);
-        }
+        let input = "#key1=value1\n#  key2=value2\n\n\n#key3=value3";
+        let EditorData { delete, upsert, .. } = parse(input, |_| true).unwrap();
+        // a space after `#` makes it a description
+        assert_eq!(vec!["key1", "key3"], delete);
         assert!(upsert.is_empty());
     }
 
     #[test]
     fn test_upsert_delete() {
-        let bytes = b"#key1=value1\n  key2=value2\n\n\n#key3=value3";
-        let EditorData { delete, upsert } = parse(BufReader::new(bytes));
+        let input = "#key1=value1\n  key2=value2\n\n\n#key3=value3";
+        let EditorData { delete, upsert, .. } = parse(input, |_| true).unwrap();
         assert_eq!(2, delete.len());
         assert_eq!(1, upsert.len());
         assert_eq!("key1", delete[0]);
@@ -101,4 +193,117 @@ mod test {
         assert_eq!("key2", upsert[0].0);
         assert_eq!("value2", upsert[0].1);
     }
+
+    #[test]
+    fn test_descriptions() {
+        let input = "# Key of the payment API\n# rotated monthly\nAPI_KEY=k1\n\n# \
+                     detached\n\ndebug=true\n# Database url, e.g. host=localhost\nDB_URL=db\n# \
+                     gone\n#OLD=1\n\n# Comment variables to remove them\nNEW=1";
+        let EditorData {
+            delete,
+            upsert,
+            descriptions,
+        } = parse(input, |_| true).unwrap();
+        assert_eq!(vec!["OLD"], delete);
+        assert_eq!(4, upsert.len());
+        assert_eq!(
+            BTreeMap::from([
+                (
+                    "API_KEY".to_owned(),
+                    "Key of the payment API\nrotated monthly".to_owned()
+                ),
+                (
+                    "DB_URL".to_owned(),
+                    "Database url, e.g. host=localhost".to_owned()
+                ),
+            ]),
+            descriptions
+        );
+    }
+
+    #[test]
+    fn test_commented_unknown_key() {
+        let input = "#NOTE=not a variable\nA=1\n#b=2\n";
+        let EditorData {
+            delete,
+            descriptions,
+            ..
+        } = parse(input, |k| k == "B").unwrap();
+        assert_eq!(vec!["b"], delete);
+        assert_eq!(
+            BTreeMap::from([("A".to_owned(), "NOTE=not a variable".to_owned())]),
+            descriptions
+        );
+    }
+
+    #[test]
+    fn test_quoted_values() {
+        let input = "URL=\"postgres://db # primary\"\nCERT=\"a\\nb\" # multiline\nBROKEN";
+        let err = parse(input, |_| true).err().unwrap();
+        assert_eq!("line 3: expected '=' after 'BROKEN'", err.to_string());
+
+        let EditorData { upsert, .. } = parse(&input[..input.len() - 7], |_| true).unwrap();
+        assert_eq!(
+            vec![
+                ("URL".to_owned(), "postgres://db # primary".to_owned()),
+                ("CERT".to_owned(), "a\nb".to_owned()),
+            ],
+            upsert
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_apply_changed_only(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        db.insert("dev", "A", "1").await.unwrap();
+        db.insert("dev", "B", "2").await.unwrap();
+        db.insert("dev", "C", "3").await.unwrap();
+
+        apply(&db, "dev", "A=1\nB=20\n#C=3\nD=4\n", Change::default())
+            .await
+            .unwrap();
+
+        for (key, revisions) in [("A", 1), ("B", 2), ("C", 2), ("D", 1)] {
+            assert_eq!(revisions, db.history("dev", key).await.unwrap().len());
+        }
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("dev", "A", "1"),
+                EnvironmentRow::from("dev", "B", "20"),
+                EnvironmentRow::from("dev", "D", "4"),
+            ],
+            own_vars(&db, "dev").await.unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_render_round_trip(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        db.insert("dev", "DEBUG", "0").await.unwrap();
+        db.insert("dev", "HOST", "localhost").await.unwrap();
+        db.set_description(Some("dev"), "debug", Some("DEBUG=1 enables tracing"))
+            .await
+            .unwrap();
+
+        let rendered = String::from_utf8(render(&db, "dev").await.unwrap()).unwrap();
+        assert_eq!(
+            "# DEBUG=1 enables tracing\nDEBUG=0\nHOST=localhost\n",
+            rendered
+        );
+
+        // saving the buffer unchanged changes nothing
+        apply(&db, "dev", &rendered, Change::default())
+            .await
+            .unwrap();
+        assert_eq!(1, db.history("dev", "DEBUG").await.unwrap().len());
+        assert_eq!(2, own_vars(&db, "dev").await.unwrap().len());
+        assert_eq!(
+            Some("DEBUG=1 enables tracing"),
+            db.descriptions("dev")
+                .await
+                .unwrap()
+                .get("DEBUG")
+                .map(String::as_str)
+        );
+    }
 }
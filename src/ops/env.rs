@@ -0,0 +1,246 @@
+use std::io::Write;
+
+use anyhow::{Context, Result, ensure};
+use prettytable::{Table, row};
+
+use crate::db::model::EnvironmentMeta;
+use crate::db::{EnvelopeDb, MetaUpdate};
+
+/// Updates the metadata of `env`
+pub async fn set_meta(db: &EnvelopeDb, env: &str, update: MetaUpdate<'_>) -> Result<()> {
+    ensure!(
+        update.description.is_some()
+            || update.owner.is_some()
+            || update.protected.is_some()
+            || update.read_only.is_some(),
+        "expected a description, an owner or a flag to set"
+    );
+
+    db.set_meta(env, update).await
+}
+
+/// Writes the metadata of `env`, one field per line
+pub async fn show_meta<W: Write>(writer: &mut W, db: &EnvelopeDb, env: &str) -> Result<()> {
+    let meta = db
+        .environment_meta(env)
+        .await?
+        .with_context(|| format!("environment '{env}' does not exist"))?;
+
+    writeln!(writer, "environment: {}", meta.env)?;
+    writeln!(
+        writer,
+        "description: {}",
+        meta.description.as_deref().unwrap_or("")
+    )?;
+    writeln!(writer, "owner: {}", meta.owner.as_deref().unwrap_or(""))?;
+    writeln!(writer, "flags: {}", flags(&meta))?;
+    writeln!(writer, "created: {}", meta.created_at)?;
+
+    Ok(())
+}
+
+/// Prints a table of every environment along with its metadata
+pub async fn table_list_envs(db: &EnvelopeDb) -> Result<()> {
+    let envs = db.list_environment_meta().await?;
+    if envs.is_empty() {
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row![
+        "ENVIRONMENT",
+        "DESCRIPTION",
+        "OWNER",
+        "FLAGS",
+        "CREATED"
+    ]);
+    for meta in &envs {
+        table.add_row(row![
+            Fy->&meta.env,
+            meta.description.as_deref().unwrap_or(""),
+            meta.owner.as_deref().unwrap_or(""),
+            Fr->flags(meta),
+            meta.created_at,
+        ]);
+    }
+    table.printstd();
+
+    Ok(())
+}
+
+/// Returns the flags set on an environment, separated by commas
+fn flags(meta: &EnvironmentMeta) -> String {
+    let mut flags = Vec::new();
+    if meta.protected {
+        flags.push("protected");
+    }
+    if meta.read_only {
+        flags.push("read-only");
+    }
+
+    flags.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Change;
+    use crate::ops;
+
+    fn read_only() -> MetaUpdate<'static> {
+        MetaUpdate {
+            read_only: Some(true),
+            ..Default::default()
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_set_meta(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_var(&db, "prod", "key", "value", Change::default())
+            .await
+            .unwrap();
+
+        let update = MetaUpdate {
+            description: Some("Production"),
+            owner: Some("ops@example.com"),
+            ..Default::default()
+        };
+        set_meta(&db, "prod", update).await.unwrap();
+        set_meta(&db, "prod", read_only()).await.unwrap();
+
+        let mut output = Vec::new();
+        show_meta(&mut output, &db, "prod").await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(
+            "environment: prod\ndescription: Production\nowner: ops@example.com\nflags: \
+             read-only\n"
+        ));
+
+        // an empty value clears the field, the others are kept
+        let update = MetaUpdate {
+            owner: Some(""),
+            ..Default::default()
+        };
+        set_meta(&db, "prod", update).await.unwrap();
+        let meta = db.environment_meta("prod").await.unwrap().unwrap();
+        assert_eq!(Some("Production"), meta.description.as_deref());
+        assert_eq!(None, meta.owner);
+        assert!(meta.read_only);
+
+        assert!(set_meta(&db, "prod", MetaUpdate::default()).await.is_err());
+        assert!(set_meta(&db, "missing", read_only()).await.is_err());
+        assert!(show_meta(&mut Vec::new(), &db, "missing").await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_read_only_env(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_var(&db, "prod", "key", "v1", Change::default())
+            .await
+            .unwrap();
+        set_meta(&db, "prod", read_only()).await.unwrap();
+
+        let err = ops::add_var(&db, "prod", "key", "v2", Change::default())
+            .await
+            .unwrap_err();
+        assert_eq!("environment 'prod' is read-only", err.to_string());
+        assert!(db.rename_env("prod", "live").await.is_err());
+        assert!(db.trash_env("prod").await.is_err());
+
+        // changes can still be staged, but not committed
+        let stage = Change {
+            message: None,
+            stage: true,
+        };
+        ops::add_var(&db, "prod", "key", "v2", stage).await.unwrap();
+        assert!(db.commit_staged(None).await.is_err());
+
+        let update = MetaUpdate {
+            read_only: Some(false),
+            ..Default::default()
+        };
+        set_meta(&db, "prod", update).await.unwrap();
+        assert_eq!(1, db.commit_staged(None).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn test_protected_env(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_var(&db, "prod", "key", "v1", Change::default())
+            .await
+            .unwrap();
+        let protect = MetaUpdate {
+            protected: Some(true),
+            ..Default::default()
+        };
+        set_meta(&db, "prod", protect).await.unwrap();
+
+        // changes are refused until they can be confirmed
+        let err = ops::add_var(&db, "prod", "key", "v2", Change::default())
+            .await
+            .unwrap_err();
+        assert_eq!("environment 'prod' is protected", err.to_string());
+        assert!(set_meta(&db, "prod", read_only()).await.is_err());
+
+        db.on_confirm(|prompt| {
+            assert_eq!("Environment 'prod' is protected, change it?", prompt);
+            Ok(true)
+        });
+        ops::add_var(&db, "prod", "key", "v2", Change::default())
+            .await
+            .unwrap();
+        ops::add_var(&db, "dev", "key", "v1", Change::default())
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_meta_follows_env(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        sqlx::query(
+            r"INSERT INTO env_parents (env, parent, position, created_at)
+            VALUES ('dev', 'base', 0, 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        db.insert("base", "KEY", "v").await.unwrap();
+
+        let envs: Vec<String> = db
+            .list_environment_meta()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.env)
+            .collect();
+        assert_eq!(vec!["dev", "base"], envs);
+        assert_eq!(
+            "1970-01-01 00:00:00",
+            db.environment_meta("dev")
+                .await
+                .unwrap()
+                .unwrap()
+                .created_at
+        );
+
+        let update = MetaUpdate {
+            description: Some("Development"),
+            ..Default::default()
+        };
+        set_meta(&db, "dev", update).await.unwrap();
+        db.trash_env("dev").await.unwrap();
+        assert_eq!(None, db.environment_meta("dev").await.unwrap());
+
+        db.restore_trashed_env("dev").await.unwrap();
+        let meta = db.environment_meta("dev").await.unwrap().unwrap();
+        assert_eq!(Some("Development"), meta.description.as_deref());
+        assert_eq!("1970-01-01 00:00:00", meta.created_at);
+
+        db.rename_env("dev", "local").await.unwrap();
+        let meta = db.environment_meta("local").await.unwrap().unwrap();
+        assert_eq!(Some("Development"), meta.description.as_deref());
+    }
+}
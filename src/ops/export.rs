@@ -0,0 +1,135 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::read_expanded;
+use super::secret::{MASK, Masker};
+use crate::db::{EnvelopeDb, Resolve};
+use crate::format::{Formatter, Select};
+
+/// Writes the variables of `env`, as `run` would pass them to a command,
+/// sorted by key with `formatter`. Only the secrets, or only the other
+/// variables, are written if the format selects them.
+pub async fn export<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env: &str,
+    formatter: &dyn Formatter,
+    resolve: Resolve,
+    reveal: bool,
+) -> Result<()> {
+    let mut masker = Masker::load(db, reveal).await?;
+    let mut vars = read_expanded(db, env, resolve, &mut masker).await?;
+    vars.sort_by(|a, b| a.key.cmp(&b.key));
+
+    match formatter.select() {
+        Select::All => {}
+        Select::Secrets => vars.retain(|var| masker.is_secret(&var.key)),
+        Select::Plain => vars.retain(|var| !masker.is_secret(&var.key)),
+    }
+    for var in vars.iter_mut() {
+        if !reveal && masker.is_secret(&var.key) {
+            var.value = MASK.to_owned();
+            var.base64 = false;
+        }
+    }
+
+    formatter.write(writer, &vars)
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Change;
+    use crate::format::{Format, Options};
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_export(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        for (k, v) in [
+            ("url", "http://${HOST}"),
+            ("host", "localhost"),
+            ("token", "t1"),
+        ] {
+            ops::add_var(&db, "dev", k, v, Change::default())
+                .await
+                .unwrap();
+        }
+
+        let mut output = Vec::new();
+        let json = Format::Json.formatter(Options::default()).unwrap();
+        export(
+            &mut output,
+            &db,
+            "dev",
+            json.as_ref(),
+            Resolve::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            "{\n  \"HOST\": \"localhost\",\n  \"TOKEN\": \"********\",\n  \"URL\": \
+             \"http://localhost\"\n}\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let dotenv = Format::Dotenv.formatter(Options::default()).unwrap();
+        assert!(
+            export(
+                &mut Vec::new(),
+                &db,
+                "prod",
+                dotenv.as_ref(),
+                Resolve::default(),
+                true
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_export_k8s(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        for (k, v) in [("host", "localhost"), ("token", "t1")] {
+            ops::add_var(&db, "dev", k, v, Change::default())
+                .await
+                .unwrap();
+        }
+
+        let mut written = Vec::new();
+        for format in [Format::K8sSecret, Format::K8sConfigmap] {
+            let options = Options {
+                name: Some("app".to_owned()),
+                ..Default::default()
+            };
+            let formatter = format.formatter(options).unwrap();
+            let mut output = Vec::new();
+            export(
+                &mut output,
+                &db,
+                "dev",
+                formatter.as_ref(),
+                Resolve::default(),
+                true,
+            )
+            .await
+            .unwrap();
+            written.push(String::from_utf8(output).unwrap());
+        }
+        // secrets only go to the Secret
+        assert!(written[0].ends_with("data:\n  TOKEN: \"dDE=\"\n"));
+        assert!(written[1].ends_with("data:\n  HOST: \"localhost\"\n"));
+
+        assert!(Format::K8sSecret.formatter(Options::default()).is_err());
+        let options = Options {
+            name: Some("app".to_owned()),
+            ..Default::default()
+        };
+        assert!(Format::Json.formatter(options).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use crate::db::EnvelopeDb;
+use crate::db::model::{Retention, Revision};
+use crate::time;
+
+/// Lists the revisions of `env`, or of every environment, that [`gc`] would
+/// remove
+pub async fn collectable(db: &EnvelopeDb, env: Option<&str>) -> Result<Vec<Revision>> {
+    if let Some(env) = env {
+        ensure!(
+            db.env_exists(env).await?,
+            "environment '{env}' does not exist"
+        );
+    }
+
+    db.collectable(env, time::now()).await
+}
+
+/// Removes `revisions` from history and shrinks the database
+pub async fn gc(db: &EnvelopeDb, revisions: &[Revision]) -> Result<()> {
+    db.gc(revisions).await
+}
+
+/// Sets how much of the history of `env` is kept by [`gc`]
+pub async fn set_retention(
+    db: &EnvelopeDb,
+    env: &str,
+    keep_revisions: Option<u32>,
+    keep_newer_than: Option<u64>,
+) -> Result<()> {
+    ensure!(
+        keep_revisions.is_some() || keep_newer_than.is_some(),
+        "expected a number of revisions or a duration to keep"
+    );
+    ensure!(
+        keep_revisions != Some(0),
+        "at least one revision must be kept"
+    );
+    ensure!(
+        db.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    db.set_retention(env, keep_revisions, keep_newer_than).await
+}
+
+/// Removes the retention settings of `env`
+pub async fn clear_retention(db: &EnvelopeDb, env: &str) -> Result<()> {
+    ensure!(
+        db.clear_retention(env).await?,
+        "environment '{env}' has no retention settings"
+    );
+
+    Ok(())
+}
+
+/// Lists the retention settings of every environment
+pub async fn list_retention<W: Write>(writer: &mut W, db: &EnvelopeDb) -> Result<()> {
+    for Retention {
+        env,
+        keep_revisions,
+        keep_newer_than,
+    } in db.list_retention().await?
+    {
+        let mut settings = Vec::new();
+        if let Some(n) = keep_revisions {
+            settings.push(format!("last {n} revisions"));
+        }
+        if let Some(secs) = keep_newer_than {
+            settings.push(format!("newer than {}", time::format_duration(secs as u64)));
+        }
+
+        writeln!(writer, "{env}: {}", settings.join(", "))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Resolve;
+    use crate::db::model::EnvironmentRow;
+
+    async fn seed(pool: &SqlitePool) {
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+            ('dev', 'KEY', 'v1', 0),
+            ('dev', 'KEY', 'v1', 10),
+            ('dev', 'KEY', 'v2', 20),
+            ('dev', 'KEY', NULL, 30),
+            ('dev', 'KEY', NULL, 40),
+            ('dev', 'KEY', 'v3', 50),
+            ('prod', 'KEY', 'p1', 0),
+            ('prod', 'KEY', 'p2', 10),
+            ('prod', 'KEY', 'p2', 20);",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn revisions(revisions: &[Revision]) -> Vec<i64> {
+        revisions.iter().map(|r| r.revision).collect()
+    }
+
+    #[sqlx::test]
+    async fn test_gc_collapses_duplicates(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        seed(&pool).await;
+
+        let found = collectable(&db, None).await.unwrap();
+        assert_eq!(vec![2, 5, 9], revisions(&found));
+        assert_eq!(
+            vec![2, 5],
+            revisions(&collectable(&db, Some("dev")).await.unwrap())
+        );
+        assert!(collectable(&db, Some("missing")).await.is_err());
+
+        gc(&db, &found).await.unwrap();
+        assert!(collectable(&db, None).await.unwrap().is_empty());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY", "v1")],
+            db.list_kv_in_env(
+                "dev",
+                Resolve {
+                    at: Some(15),
+                    ..Default::default()
+                }
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            vec![EnvironmentRow::from("prod", "KEY", "p2")],
+            db.list_kv_in_env("prod", Resolve::default()).await.unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_gc_keeps_encoding_changes(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        // the same text, plain and then as the base64 encoding of a value
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, base64, created_at)
+            VALUES
+            ('dev', 'CERT', 'AP8=', FALSE, 0),
+            ('dev', 'CERT', 'AP8=', TRUE, 10),
+            ('dev', 'CERT', 'AP8=', TRUE, 20);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(vec![3], revisions(&collectable(&db, None).await.unwrap()));
+    }
+
+    #[sqlx::test]
+    async fn test_gc_guards_envs(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        seed(&pool).await;
+        sqlx::query(r"UPDATE environment SET read_only = TRUE WHERE env = 'prod'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // read-only environments keep their whole history
+        let found = collectable(&db, None).await.unwrap();
+        assert_eq!(vec![2, 5], revisions(&found));
+        assert!(collectable(&db, Some("prod")).await.unwrap().is_empty());
+        let err = set_retention(&db, "prod", Some(1), None).await.unwrap_err();
+        assert_eq!("environment 'prod' is read-only", err.to_string());
+        assert!(clear_retention(&db, "prod").await.is_err());
+
+        // pruning a protected environment must be confirmed
+        sqlx::query(r"UPDATE environment SET protected = TRUE WHERE env = 'dev'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let err = gc(&db, &found).await.unwrap_err();
+        assert_eq!("environment 'dev' is protected", err.to_string());
+        assert!(set_retention(&db, "dev", Some(1), None).await.is_err());
+        assert_eq!(found.len(), collectable(&db, None).await.unwrap().len());
+
+        db.on_confirm(|_| Ok(true));
+        gc(&db, &found).await.unwrap();
+        assert!(collectable(&db, None).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_gc_retention(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        seed(&pool).await;
+
+        set_retention(&db, "dev", Some(2), None).await.unwrap();
+        assert_eq!(
+            vec![1, 2, 3, 5, 9],
+            revisions(&db.collectable(None, 100).await.unwrap())
+        );
+
+        // the latest value is kept even when older than the duration
+        set_retention(&db, "dev", None, Some(60)).await.unwrap();
+        set_retention(&db, "prod", Some(5), Some(95)).await.unwrap();
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 7, 9],
+            revisions(&db.collectable(None, 100).await.unwrap())
+        );
+
+        let mut output = Vec::new();
+        list_retention(&mut output, &db).await.unwrap();
+        assert_eq!(
+            "dev: newer than 1m\nprod: last 5 revisions, newer than 1m35s\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        // with both set, a revision is removed once beyond either of them
+        set_retention(&db, "dev", Some(3), Some(75)).await.unwrap();
+        assert_eq!(
+            vec![1, 2, 3, 5, 7, 9],
+            revisions(&db.collectable(None, 100).await.unwrap())
+        );
+        set_retention(&db, "dev", Some(2), Some(95)).await.unwrap();
+        assert_eq!(
+            vec![1, 2, 3, 5, 7, 9],
+            revisions(&db.collectable(None, 100).await.unwrap())
+        );
+
+        clear_retention(&db, "dev").await.unwrap();
+        assert!(clear_retention(&db, "dev").await.is_err());
+        assert!(set_retention(&db, "dev", None, None).await.is_err());
+        assert!(set_retention(&db, "dev", Some(0), None).await.is_err());
+        let err = set_retention(&db, "missing", Some(1), None)
+            .await
+            .unwrap_err();
+        assert_eq!("environment 'missing' does not exist", err.to_string());
+        assert_eq!(
+            vec![2, 5, 7, 9],
+            revisions(&db.collectable(None, 100).await.unwrap())
+        );
+    }
+}
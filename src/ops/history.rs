@@ -2,29 +2,77 @@ use std::io::Write;
 
 use anyhow::Result;
 
+use super::secret::Masker;
 use crate::db::EnvelopeDb;
 use crate::db::model::EnvironmentRowNullable;
+use crate::dotenv;
 
 pub async fn history<W: Write>(
     writer: &mut W,
     db: &EnvelopeDb,
     env: &str,
     key: &str,
+    reveal: bool,
 ) -> Result<()> {
     let kvs: Vec<EnvironmentRowNullable> = db.history(env, key).await?;
+    let masker = Masker::load(db, reveal).await?;
     for EnvironmentRowNullable {
         key,
         value,
+        tombstone,
+        revision,
         created_at,
         ..
     } in kvs
     {
         if let Some(value) = value {
-            writeln!(writer, "{created_at} {key}={value}")?;
+            let value = dotenv::quote(masker.mask(&key, &value));
+            writeln!(writer, "{revision} {created_at} {key}={value}")?;
+        } else if tombstone {
+            writeln!(writer, "{revision} {created_at} {key} unset")?;
         } else {
-            writeln!(writer, "{created_at} {key} inactive")?;
+            writeln!(writer, "{revision} {created_at} {key} inactive")?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_history_masks_secrets(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES ('dev', 'DB_PASSWORD', 'hunter2', 0), ('dev', 'DB_PASSWORD', NULL, 60)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut output = Vec::new();
+        history(&mut output, &db, "dev", "db_password", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            "1 1970-01-01 00:00:00 DB_PASSWORD=********\n2 1970-01-01 00:01:00 DB_PASSWORD \
+             inactive\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut output = Vec::new();
+        history(&mut output, &db, "dev", "db_password", true)
+            .await
+            .unwrap();
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("DB_PASSWORD=hunter2")
+        );
+    }
+}
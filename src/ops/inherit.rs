@@ -0,0 +1,224 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use crate::db::{Change, EnvelopeDb};
+
+/// Makes `env` inherit the variables of `parent`
+pub async fn add_parent(db: &EnvelopeDb, env: &str, parent: &str) -> Result<()> {
+    db.add_parent(env, parent).await
+}
+
+/// Stops `env` from inheriting the variables of `parent`
+pub async fn remove_parent(db: &EnvelopeDb, env: &str, parent: &str) -> Result<()> {
+    db.remove_parent(env, parent).await
+}
+
+pub async fn list_parents<W: Write>(writer: &mut W, db: &EnvelopeDb, env: &str) -> Result<()> {
+    ensure!(
+        db.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    for parent in db.parents(env).await? {
+        writeln!(writer, "{parent}")?;
+    }
+
+    Ok(())
+}
+
+/// Hides the value that `env` would otherwise inherit for `key`
+pub async fn unset_var(db: &EnvelopeDb, env: &str, key: &str, change: Change<'_>) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    ensure!(
+        tx.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    tx.insert_tombstone(env, key).await?;
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::model::EnvironmentRow;
+    use crate::db::{Resolve, Truncate};
+
+    #[sqlx::test]
+    async fn test_inherit_override_and_unset(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("base", "HOST", "base.host").await.unwrap();
+        db.insert("base", "PORT", "5432").await.unwrap();
+        db.insert("base", "DEBUG", "false").await.unwrap();
+        db.insert("dev", "DEBUG", "true").await.unwrap();
+        db.insert("dev", "TRACE", "1").await.unwrap();
+
+        add_parent(&db, "dev", "base").await.unwrap();
+        add_parent(&db, "dev-alice", "dev").await.unwrap();
+        unset_var(&db, "dev-alice", "trace", Change::default())
+            .await
+            .unwrap();
+        db.insert("dev-alice", "HOST", "alice.host").await.unwrap();
+
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("dev", "DEBUG", "true"),
+                EnvironmentRow::from("dev-alice", "HOST", "alice.host"),
+                EnvironmentRow::from("base", "PORT", "5432"),
+            ],
+            db.list_kv_in_env_alt("dev-alice", Truncate::None, "k", Resolve::default())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![EnvironmentRow::from("dev-alice", "HOST", "alice.host")],
+            db.list_kv_in_env_alt(
+                "dev-alice",
+                Truncate::None,
+                "k",
+                Resolve {
+                    inherit: false,
+                    ..Default::default()
+                }
+            )
+            .await
+            .unwrap()
+        );
+
+        // deleting the tombstone makes the inherited value visible again
+        db.soft_delete_key_in_env("dev-alice", "TRACE")
+            .await
+            .unwrap();
+        let keys: Vec<String> = db
+            .list_kv_in_env("dev-alice", Resolve::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.key)
+            .collect();
+        assert!(keys.contains(&"TRACE".to_string()));
+    }
+
+    #[sqlx::test]
+    async fn test_parent_precedence(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("first", "KEY", "first").await.unwrap();
+        db.insert("second", "KEY", "second").await.unwrap();
+        db.insert("second", "ONLY_SECOND", "yes").await.unwrap();
+
+        add_parent(&db, "child", "first").await.unwrap();
+        add_parent(&db, "child", "second").await.unwrap();
+
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("first", "KEY", "first"),
+                EnvironmentRow::from("second", "ONLY_SECOND", "yes"),
+            ],
+            db.list_kv_in_env_alt("child", Truncate::None, "k", Resolve::default())
+                .await
+                .unwrap()
+        );
+
+        let mut output = Vec::new();
+        list_parents(&mut output, &db, "child").await.unwrap();
+        assert_eq!("first\nsecond\n", String::from_utf8(output).unwrap());
+
+        remove_parent(&db, "child", "first").await.unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("second", "KEY", "second"),
+                EnvironmentRow::from("second", "ONLY_SECOND", "yes"),
+            ],
+            db.list_kv_in_env_alt("child", Truncate::None, "k", Resolve::default())
+                .await
+                .unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_removed_parent_at(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES ('base', 'KEY', 'base', 0), ('dev', 'OWN', 'dev', 0);
+            INSERT INTO env_parents (env, parent, position, created_at)
+            VALUES ('dev', 'base', 0, 10);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        remove_parent(&db, "dev", "base").await.unwrap();
+        assert!(remove_parent(&db, "dev", "base").await.is_err());
+        sqlx::query(r"UPDATE env_parents SET removed_at = 100")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let list = |at| {
+            db.list_kv_in_env_alt(
+                "dev",
+                Truncate::None,
+                "k",
+                Resolve {
+                    at,
+                    ..Default::default()
+                },
+            )
+        };
+        let inherited = vec![
+            EnvironmentRow::from("base", "KEY", "base"),
+            EnvironmentRow::from("dev", "OWN", "dev"),
+        ];
+        let own = vec![EnvironmentRow::from("dev", "OWN", "dev")];
+
+        // the parent is inherited from until it was removed
+        assert_eq!(own, list(Some(5)).await.unwrap());
+        assert_eq!(inherited, list(Some(99)).await.unwrap());
+        assert_eq!(own, list(Some(100)).await.unwrap());
+        assert_eq!(own, list(None).await.unwrap());
+
+        // and can be added again
+        add_parent(&db, "dev", "base").await.unwrap();
+        assert_eq!(inherited, list(None).await.unwrap());
+        assert_eq!(inherited, list(Some(99)).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn test_parent_cycle_rejected(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("a", "KEY", "a").await.unwrap();
+        add_parent(&db, "b", "a").await.unwrap();
+        add_parent(&db, "c", "b").await.unwrap();
+
+        let err = add_parent(&db, "a", "c")
+            .await
+            .expect_err("a -> c -> b -> a is a cycle");
+        assert!(
+            err.to_string().contains("cycle"),
+            "error should mention the cycle, got: {err}"
+        );
+
+        let err = add_parent(&db, "a", "a")
+            .await
+            .expect_err("an environment cannot inherit from itself");
+        assert!(
+            err.to_string().contains("itself"),
+            "error should mention self inheritance, got: {err}"
+        );
+
+        let err = add_parent(&db, "a", "ghost")
+            .await
+            .expect_err("parent must exist");
+        assert!(
+            err.to_string().contains("does not exist"),
+            "error should mention the missing parent, got: {err}"
+        );
+    }
+}
@@ -1,28 +1,32 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::io::{BufRead, Write};
+use std::io::{Read, Write};
 
-use anyhow::{Result, ensure};
-use prettytable::{Table, row};
+use anyhow::{Context, Result, ensure};
+use prettytable::{Cell, Table, row};
 
+use super::secret::{MASK, Masker};
 use crate::db::model::{Environment, EnvironmentRow};
-use crate::db::{EnvelopeDb, Truncate};
+use crate::db::{EnvelopeDb, Resolve, Truncate};
+use crate::dotenv::{self, Item};
+use crate::interpolate::Interpolator;
 
 pub async fn print_from_stdin() -> Result<()> {
     let mut table = Table::new();
     table.add_row(row!["VARIABLE", "VALUE"]);
 
-    let buf = io::BufReader::new(io::stdin());
-    for line in buf.lines() {
-        if line.is_err() {
-            continue;
-        }
-
-        if line.as_ref().unwrap().starts_with('#') {
-            continue;
-        }
-
-        if let Some((k, v)) = line.unwrap().split_once('=') {
-            table.add_row(row![FrB->k, Fb->v]);
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .context("failed to read variables")?;
+    for item in dotenv::parse(&input) {
+        match item {
+            Ok(Item::Var { key, value }) => {
+                table.add_row(row![FrB->key, Fb->value]);
+            }
+            Ok(_) => {}
+            Err(err) => writeln!(io::stderr(), "{err}, skipping")?,
         }
     }
 
@@ -31,30 +35,101 @@ pub async fn print_from_stdin() -> Result<()> {
     Ok(())
 }
 
-struct EnvRows(Vec<EnvironmentRow>);
+/// Variables along with their description
+struct EnvRows(Vec<(EnvironmentRow, Option<String>)>);
+
+impl EnvRows {
+    /// Looks up the description of every variable in the environment it is
+    /// resolved from
+    async fn describe(db: &EnvelopeDb, envs: Vec<EnvironmentRow>) -> Result<Self> {
+        let mut descriptions: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+        let mut rows = Vec::with_capacity(envs.len());
+        for env in envs {
+            if !descriptions.contains_key(&env.env) {
+                descriptions.insert(env.env.clone(), db.descriptions(&env.env).await?);
+            }
+            let description = descriptions[&env.env].get(&env.key).cloned();
+            rows.push((env, description));
+        }
+
+        Ok(Self(rows))
+    }
+}
 
 impl From<EnvRows> for Table {
     fn from(value: EnvRows) -> Self {
         let mut table = Table::new();
-        table.set_titles(row!["ENVIRONMENT", "VARIABLE", "VALUE"]);
+        // the description column is only added when a variable has one
+        let described = value.0.iter().any(|(_, d)| d.is_some());
+        match described {
+            true => table.set_titles(row!["ENVIRONMENT", "VARIABLE", "VALUE", "DESCRIPTION"]),
+            false => table.set_titles(row!["ENVIRONMENT", "VARIABLE", "VALUE"]),
+        }
 
-        for env in value.0 {
-            table.add_row(row![Fy->&env.env, Frb->&env.key, Fb->&env.value]);
+        for (env, description) in value.0 {
+            let mut row = row![Fy->&env.env, Frb->&env.key, Fb->&env.value];
+            if described {
+                row.add_cell(Cell::new(&description.unwrap_or_default()));
+            }
+            table.add_row(row);
         }
 
         table
     }
 }
 
-pub async fn table_list(db: &EnvelopeDb, env: &str, truncate: Truncate, sort: &str) -> Result<()> {
+/// Reads the variables of `env`, expanding references in their values unless
+/// `raw` is set. The variables embedding secrets are tracked by `masker`.
+async fn read_vars(
+    db: &EnvelopeDb,
+    env: &str,
+    truncate: Truncate,
+    sort: &str,
+    resolve: Resolve,
+    raw: bool,
+    masker: &mut Masker,
+) -> Result<Vec<EnvironmentRow>> {
+    if raw {
+        return db.list_kv_in_env_alt(env, truncate, sort, resolve).await;
+    }
+
+    // references must be expanded before values are truncated
+    let mut envs = db
+        .list_kv_in_env_alt(env, Truncate::None, sort, resolve)
+        .await?;
+    let mut interpolator = Interpolator::new(db, resolve);
+    interpolator.expand(env, &mut envs).await?;
+    masker.track(env, &envs, &interpolator);
+    if let Truncate::Max(max) = truncate {
+        for row in envs.iter_mut() {
+            row.value = row.value.chars().take(max as usize).collect();
+        }
+    }
+
+    Ok(envs)
+}
+
+pub async fn table_list(
+    db: &EnvelopeDb,
+    env: &str,
+    truncate: Truncate,
+    sort: &str,
+    resolve: Resolve,
+    raw: bool,
+    reveal: bool,
+) -> Result<()> {
     ensure!(
         db.env_exists(env).await?,
         "environment '{env}' does not exist"
     );
 
-    let envs: Vec<EnvironmentRow> = db.list_kv_in_env_alt(env, truncate, sort).await?;
+    let mut masker = Masker::load(db, reveal).await?;
+    let mut envs = read_vars(db, env, truncate, sort, resolve, raw, &mut masker).await?;
+    for row in envs.iter_mut() {
+        row.value = masker.mask(&row.key, &row.value).to_owned();
+    }
     if !envs.is_empty() {
-        Table::from(EnvRows(envs)).printstd();
+        Table::from(EnvRows::describe(db, envs).await?).printstd();
     }
 
     Ok(())
@@ -65,15 +140,58 @@ pub async fn list_raw<W: Write>(
     db: &EnvelopeDb,
     env: &str,
     sort: &str,
+    resolve: Resolve,
+    raw: bool,
+    reveal: bool,
+) -> Result<()> {
+    ensure!(
+        db.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    let mut masker = Masker::load(db, reveal).await?;
+    let envs = read_vars(db, env, Truncate::None, sort, resolve, raw, &mut masker).await?;
+    for env in envs {
+        let value = dotenv::quote(masker.mask(&env.key, &env.value));
+        writeln!(writer, "{}={value}", &env.key)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the variables of `env` as `KEY=value` entries ending with a NUL
+/// byte, with values as is and base64 encoded values decoded, for scripts
+/// to read values spanning lines or holding arbitrary bytes
+pub async fn list_nul<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env: &str,
+    sort: &str,
+    resolve: Resolve,
+    raw: bool,
+    reveal: bool,
 ) -> Result<()> {
     ensure!(
         db.env_exists(env).await?,
         "environment '{env}' does not exist"
     );
 
-    let envs: Vec<EnvironmentRow> = db.list_kv_in_env_alt(env, Truncate::None, sort).await?;
+    let mut masker = Masker::load(db, reveal).await?;
+    let envs = read_vars(db, env, Truncate::None, sort, resolve, raw, &mut masker).await?;
     for env in envs {
-        writeln!(writer, "{}={}", &env.key, &env.value)?;
+        let value = match !reveal && masker.is_secret(&env.key) {
+            true => Cow::Borrowed(MASK.as_bytes()),
+            false => env.bytes()?,
+        };
+        ensure!(
+            !value.contains(&0),
+            "value of '{}' contains a NUL byte, which cannot be delimited",
+            env.key
+        );
+        writer.write_all(env.key.as_bytes())?;
+        writer.write_all(b"=")?;
+        writer.write_all(&value)?;
+        writer.write_all(b"\0")?;
     }
 
     Ok(())
@@ -87,3 +205,105 @@ pub async fn list_envs<W: Write>(writer: &mut W, db: &EnvelopeDb) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Change;
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_list_multiline(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_var(&db, "dev", "cert", "line 1\nline 2", Change::default())
+            .await
+            .unwrap();
+        ops::add_base64_var(&db, "dev", "bin", "/wE=", Change::default())
+            .await
+            .unwrap();
+        ops::add_var(&db, "dev", "token", "t1", Change::default())
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        list_raw(
+            &mut output,
+            &db,
+            "dev",
+            "k",
+            Resolve::default(),
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            "BIN=/wE=\nCERT=\"line 1\\nline 2\"\nTOKEN=********\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut output = Vec::new();
+        list_nul(
+            &mut output,
+            &db,
+            "dev",
+            "k",
+            Resolve::default(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            b"BIN=\xff\x01\0CERT=line 1\nline 2\0TOKEN=t1\0".as_slice(),
+            output
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_masks_embedded_secrets(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        for (k, v) in [
+            ("db_password", "hunter2"),
+            ("database_url", "postgres://u:${DB_PASSWORD}@h"),
+            ("replica_url", "${DATABASE_URL}?replica"),
+            ("cache_url", "redis://${CACHE_PASSWORD:-}@h"),
+            ("host", "h"),
+            ("health_url", "http://${HOST}/health"),
+        ] {
+            ops::add_var(&db, "dev", k, v, Change::default())
+                .await
+                .unwrap();
+        }
+
+        let list = |reveal| {
+            let db = &db;
+            async move {
+                let mut output = Vec::new();
+                list_raw(
+                    &mut output,
+                    db,
+                    "dev",
+                    "k",
+                    Resolve::default(),
+                    false,
+                    reveal,
+                )
+                .await
+                .unwrap();
+                String::from_utf8(output).unwrap()
+            }
+        };
+        assert_eq!(
+            "CACHE_URL=redis://@h\nDATABASE_URL=********\nDB_PASSWORD=********\nHEALTH_URL=http://h/health\nHOST=h\nREPLICA_URL=********\n",
+            list(false).await
+        );
+        assert!(
+            list(true)
+                .await
+                .contains("DATABASE_URL=postgres://u:hunter2@h\n")
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::db::EnvelopeDb;
+use crate::db::model::ChangedKey;
+
+/// Lists the latest `limit` changesets along with the variables they changed,
+/// most recent first. Staged changes are listed on top.
+pub async fn log<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env: Option<&str>,
+    limit: Option<u32>,
+) -> Result<()> {
+    let staged = db.staged(env).await?;
+    if !staged.is_empty() {
+        writeln!(writer, "staged")?;
+        write_keys(writer, &staged)?;
+    }
+
+    for changeset in db.log(env, limit).await? {
+        match changeset.message {
+            Some(message) => writeln!(
+                writer,
+                "{} {} {message}",
+                changeset.id, changeset.created_at
+            )?,
+            None => writeln!(writer, "{} {}", changeset.id, changeset.created_at)?,
+        }
+        write_keys(writer, &changeset.keys)?;
+    }
+
+    Ok(())
+}
+
+/// Applies the staged changes as a single changeset. Returns the number of
+/// applied changes.
+pub async fn commit(db: &EnvelopeDb, message: Option<&str>) -> Result<u64> {
+    db.commit_staged(message).await
+}
+
+/// Discards the staged changes. Returns the number of discarded changes.
+pub async fn reset(db: &EnvelopeDb) -> Result<u64> {
+    db.reset_staged().await
+}
+
+/// Writes `keys`, sorted by environment, one line per environment
+fn write_keys<W: Write>(writer: &mut W, keys: &[ChangedKey]) -> Result<()> {
+    for keys in keys.chunk_by(|a, b| a.env == b.env) {
+        let names: Vec<&str> = keys.iter().map(|k| k.key.as_str()).collect();
+        writeln!(writer, "  {}: {}", keys[0].env, names.join(", "))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::model::EnvironmentRow;
+    use crate::db::{Change, Resolve};
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_changesets(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let change = Change {
+            message: Some("rotate stripe keys"),
+            stage: false,
+        };
+
+        ops::add_var(&db, "dev", "stripe_key", "k1", Change::default())
+            .await
+            .unwrap();
+        ops::import(
+            "stripe_key=k2\nstripe_secret=s2".as_bytes(),
+            &mut Vec::new(),
+            &db,
+            "dev",
+            false,
+            change,
+        )
+        .await
+        .unwrap();
+        ops::delete_var_globally(&db, "missing", change)
+            .await
+            .unwrap();
+
+        let log = db.log(None, None).await.unwrap();
+        assert_eq!(2, log.len());
+        assert_eq!(Some("rotate stripe keys"), log[0].message.as_deref());
+        assert_eq!(
+            vec![
+                ChangedKey::from("dev", "STRIPE_KEY"),
+                ChangedKey::from("dev", "STRIPE_SECRET"),
+            ],
+            log[0].keys
+        );
+        assert_eq!(None, log[1].message);
+        assert_eq!(vec![ChangedKey::from("dev", "STRIPE_KEY")], log[1].keys);
+
+        assert_eq!(1, db.log(Some("dev"), Some(1)).await.unwrap().len());
+        assert!(db.log(Some("prod"), None).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_stage_commit_and_reset(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let stage = Change {
+            message: None,
+            stage: true,
+        };
+
+        db.insert("dev", "KEY1", "v1").await.unwrap();
+        ops::add_var(&db, "dev", "key2", "v2", stage).await.unwrap();
+        ops::delete_var_in_env(&db, "dev", "key1", stage)
+            .await
+            .unwrap();
+
+        // staged changes are not applied until committed
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY1", "v1")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+
+        let mut output = Vec::new();
+        log(&mut output, &db, None, None).await.unwrap();
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .starts_with("staged\n  dev: KEY1, KEY2\n")
+        );
+
+        assert_eq!(2, commit(&db, Some("swap keys")).await.unwrap());
+        assert_eq!(0, commit(&db, None).await.unwrap());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY2", "v2")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+        let log = db.log(None, Some(1)).await.unwrap();
+        assert_eq!(Some("swap keys"), log[0].message.as_deref());
+        assert_eq!(
+            vec![
+                ChangedKey::from("dev", "KEY1"),
+                ChangedKey::from("dev", "KEY2"),
+            ],
+            log[0].keys
+        );
+
+        ops::add_var(&db, "dev", "key3", "v3", stage).await.unwrap();
+        assert_eq!(1, reset(&db).await.unwrap());
+        assert!(db.staged(None).await.unwrap().is_empty());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY2", "v2")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+    }
+}
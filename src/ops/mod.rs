@@ -1,21 +1,74 @@
+use anyhow::{Result, ensure};
+
+use crate::db::model::EnvironmentRow;
+use crate::db::{EnvelopeDb, Resolve};
+use crate::interpolate::Interpolator;
+
 mod add;
 mod check;
 mod delete;
+mod describe;
 mod diff;
 mod drop;
 mod duplicate;
 mod edit;
+mod env;
+mod export;
+mod gc;
 mod history;
+mod inherit;
 mod list;
+mod log;
+mod purge;
+mod rename;
+mod restore;
 mod revert;
+mod secret;
+mod trash;
+mod undo;
+mod validate;
 
 pub use add::*;
 pub use check::*;
 pub use delete::*;
+pub use describe::*;
 pub use diff::*;
 pub use drop::*;
 pub use duplicate::*;
 pub use edit::*;
+pub use env::*;
+pub use export::*;
+pub use gc::*;
 pub use history::*;
+pub use inherit::*;
 pub use list::*;
+pub use log::*;
+pub use purge::*;
+pub use rename::*;
+pub use restore::*;
 pub use revert::*;
+pub use secret::*;
+pub use trash::*;
+pub use undo::*;
+pub use validate::*;
+
+/// Reads the variables of `env` as `run` would pass them to a command. The
+/// variables embedding secrets are tracked by `masker`.
+pub async fn read_expanded(
+    db: &EnvelopeDb,
+    env: &str,
+    resolve: Resolve,
+    masker: &mut Masker,
+) -> Result<Vec<EnvironmentRow>> {
+    ensure!(
+        db.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    let mut vars = db.list_kv_in_env(env, resolve).await?;
+    let mut interpolator = Interpolator::new(db, resolve);
+    interpolator.expand(env, &mut vars).await?;
+    masker.track(env, &vars, &interpolator);
+
+    Ok(vars)
+}
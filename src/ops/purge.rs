@@ -0,0 +1,211 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use crate::db::EnvelopeDb;
+use crate::db::model::Revision;
+
+/// Lists revisions `revs` of `key` in `env`, failing if any of them does not
+/// exist
+pub async fn find_revisions(
+    db: &EnvelopeDb,
+    env: &str,
+    key: &str,
+    revs: &[i64],
+) -> Result<Vec<Revision>> {
+    let found = db.find_revisions(env, key, revs).await?;
+    for rev in revs {
+        ensure!(
+            found.iter().any(|r| r.revision == *rev),
+            "revision {rev} of '{}' does not exist in environment '{env}'",
+            key.to_uppercase()
+        );
+    }
+
+    Ok(found)
+}
+
+/// Lists every revision whose value is `value`, in any environment, staged
+/// changes included
+pub async fn find_value(db: &EnvelopeDb, value: &str) -> Result<Vec<Revision>> {
+    db.find_value(value).await
+}
+
+/// Writes one line per revision, without ever printing the purged values
+pub fn write_revisions<W: Write>(writer: &mut W, revisions: &[Revision]) -> Result<()> {
+    for Revision {
+        env,
+        key,
+        revision,
+        created_at,
+        trashed,
+        staged,
+        ..
+    } in revisions
+    {
+        if *staged {
+            writeln!(writer, "{env} {key} staged change")?;
+            continue;
+        }
+
+        let trash = if *trashed { " in trash" } else { "" };
+        writeln!(
+            writer,
+            "{env} {key} revision {revision} ({created_at}){trash}"
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Permanently removes `revisions` from history
+pub async fn purge(db: &EnvelopeDb, revisions: &[Revision]) -> Result<()> {
+    db.purge(revisions).await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::model::EnvironmentRow;
+    use crate::db::{Change, Resolve};
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_purge_value(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+            ('dev', 'NOTE', 'hunter2', 0),
+            ('dev', 'NOTE', 'fixed', 10),
+            ('prod', 'PASSWORD', 'hunter2', 0),
+            ('prod', 'USER', 'admin', 0),
+            ('old', 'PASSWORD', 'hunter2', 0);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        db.trash_env("old").await.unwrap();
+
+        let found = find_value(&db, "hunter2").await.unwrap();
+        let mut output = Vec::new();
+        write_revisions(&mut output, &found).unwrap();
+        assert_eq!(
+            "dev NOTE revision 1 (1970-01-01 00:00:00)\nprod PASSWORD revision 3 (1970-01-01 \
+             00:00:00)\nold PASSWORD revision 5 (1970-01-01 00:00:00) in trash\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        purge(&db, &found).await.unwrap();
+
+        assert!(find_value(&db, "hunter2").await.unwrap().is_empty());
+        assert_eq!(1, db.history("dev", "NOTE").await.unwrap().len());
+        assert_eq!(
+            vec![EnvironmentRow::from("prod", "USER", "admin")],
+            db.list_kv_in_env("prod", Resolve::default()).await.unwrap()
+        );
+
+        let bytes = db.serialize().await.unwrap();
+        assert!(
+            !bytes.windows(b"hunter2".len()).any(|w| w == b"hunter2"),
+            "purged value should not survive in the serialized database"
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_purge_base64_value(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        ops::add_base64_var(&db, "dev", "cert", "aHVudGVyMg==", Change::default())
+            .await
+            .unwrap();
+        // the encoded text of a plain value is not the value
+        ops::add_var(&db, "dev", "note", "aHVudGVyMg==", Change::default())
+            .await
+            .unwrap();
+
+        let found = find_value(&db, "hunter2").await.unwrap();
+        let mut output = Vec::new();
+        write_revisions(&mut output, &found).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("dev CERT revision 1 "), "{output}");
+        assert_eq!(1, found.len());
+
+        purge(&db, &found).await.unwrap();
+        assert!(find_value(&db, "hunter2").await.unwrap().is_empty());
+        assert_eq!(1, find_value(&db, "aHVudGVyMg==").await.unwrap().len());
+    }
+
+    #[sqlx::test]
+    async fn test_purge_staged_value(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let stage = Change {
+            message: None,
+            stage: true,
+        };
+
+        ops::add_var(&db, "dev", "password", "hunter2", stage)
+            .await
+            .unwrap();
+        ops::add_var(&db, "dev", "user", "admin", stage)
+            .await
+            .unwrap();
+
+        let found = find_value(&db, "hunter2").await.unwrap();
+        let mut output = Vec::new();
+        write_revisions(&mut output, &found).unwrap();
+        assert_eq!(
+            "dev PASSWORD staged change\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        purge(&db, &found).await.unwrap();
+
+        assert!(find_value(&db, "hunter2").await.unwrap().is_empty());
+        assert_eq!(1, ops::commit(&db, None).await.unwrap());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "USER", "admin")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+
+        let bytes = db.serialize().await.unwrap();
+        assert!(
+            !bytes.windows(b"hunter2".len()).any(|w| w == b"hunter2"),
+            "purged staged value should not survive in the serialized database"
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_purge_revisions(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+            ('dev', 'KEY', 'v1', 0),
+            ('dev', 'KEY', 'v2', 10),
+            ('dev', 'KEY', 'v3', 20);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let err = find_revisions(&db, "dev", "key", &[2, 4])
+            .await
+            .expect_err("revision 4 does not exist");
+        assert!(
+            err.to_string().contains("revision 4"),
+            "error should mention the missing revision, got: {err}"
+        );
+
+        let found = find_revisions(&db, "dev", "key", &[2, 3]).await.unwrap();
+        purge(&db, &found).await.unwrap();
+
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY", "v1")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+    }
+}
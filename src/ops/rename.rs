@@ -0,0 +1,182 @@
+use anyhow::{Result, ensure};
+
+use crate::db::EnvelopeDb;
+
+/// Renames environment `env` to `new_env`, preserving its history
+pub async fn rename_env(db: &EnvelopeDb, env: &str, new_env: &str) -> Result<()> {
+    ensure!(
+        env != new_env,
+        "source and target environments cannot be the same"
+    );
+
+    db.rename_env(env, new_env).await
+}
+
+/// Renames variable `key` to `new_key` in `env`, or in every environment if
+/// `env` is `None`, preserving its history
+pub async fn rename_key(
+    db: &EnvelopeDb,
+    env: Option<&str>,
+    key: &str,
+    new_key: &str,
+) -> Result<()> {
+    ensure!(
+        !key.eq_ignore_ascii_case(new_key),
+        "source and target variables cannot be the same"
+    );
+
+    db.rename_key(env, key, new_key).await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::model::EnvironmentRow;
+    use crate::db::{Change, Resolve};
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_rename_env(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("staging", "KEY", "v1").await.unwrap();
+        db.insert("staging", "KEY", "v2").await.unwrap();
+        db.insert("base", "SHARED", "yes").await.unwrap();
+        db.add_parent("staging", "base").await.unwrap();
+        db.add_parent("child", "staging").await.unwrap();
+        db.insert("prod", "KEY", "prod").await.unwrap();
+
+        let err = rename_env(&db, "staging", "prod")
+            .await
+            .expect_err("prod already exists");
+        assert!(
+            err.to_string().contains("already exists"),
+            "error should mention the existing target, got: {err}"
+        );
+
+        rename_env(&db, "staging", "stage").await.unwrap();
+
+        assert!(!db.env_exists("staging").await.unwrap());
+        assert_eq!(2, db.history("stage", "KEY").await.unwrap().len());
+        assert_eq!(vec!["base"], db.parents("stage").await.unwrap());
+        assert_eq!(vec!["stage"], db.parents("child").await.unwrap());
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("stage", "KEY", "v2"),
+                EnvironmentRow::from("base", "SHARED", "yes"),
+            ],
+            db.list_kv_in_env("child", Resolve::default())
+                .await
+                .unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rename_key(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("dev", "DB_URL", "dev1").await.unwrap();
+        db.insert("dev", "DB_URL", "dev2").await.unwrap();
+        db.insert("prod", "DB_URL", "prod").await.unwrap();
+        db.insert("prod", "DATABASE_URL", "taken").await.unwrap();
+
+        let err = rename_key(&db, None, "db_url", "database_url")
+            .await
+            .expect_err("DATABASE_URL already exists in prod");
+        assert!(
+            err.to_string().contains("'prod'"),
+            "error should mention the conflicting environment, got: {err}"
+        );
+        assert_eq!(2, db.history("dev", "DB_URL").await.unwrap().len());
+
+        rename_key(&db, Some("dev"), "db_url", "database_url")
+            .await
+            .unwrap();
+        assert!(db.history("dev", "DB_URL").await.unwrap().is_empty());
+        assert_eq!(2, db.history("dev", "DATABASE_URL").await.unwrap().len());
+        assert_eq!(1, db.history("prod", "DB_URL").await.unwrap().len());
+
+        let err = rename_key(&db, Some("dev"), "db_url", "url")
+            .await
+            .expect_err("DB_URL no longer exists in dev");
+        assert!(
+            err.to_string().contains("does not exist"),
+            "error should mention the missing variable, got: {err}"
+        );
+
+        rename_key(&db, None, "database_url", "url").await.unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("prod", "DB_URL", "prod"),
+                EnvironmentRow::from("prod", "URL", "taken"),
+            ],
+            db.list_kv_in_env("prod", Resolve::default()).await.unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rename_staged_key(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let stage = Change {
+            message: None,
+            stage: true,
+        };
+        db.insert("dev", "A", "1").await.unwrap();
+        ops::add_var(&db, "dev", "a", "2", stage).await.unwrap();
+        ops::add_var(&db, "prod", "c", "3", stage).await.unwrap();
+        db.set_classification("a", Some(true)).await.unwrap();
+        db.insert("prod", "B", "taken").await.unwrap();
+
+        // a staged variable is taken like a committed one
+        assert!(rename_key(&db, None, "b", "c").await.is_err());
+
+        rename_key(&db, None, "a", "b").await.unwrap();
+        assert_eq!(2, ops::commit(&db, None).await.unwrap());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "B", "2")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+        assert_eq!(
+            vec![("B".to_owned(), true)],
+            db.classification().await.unwrap()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rename_referenced(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("base", "HOST", "h").await.unwrap();
+        db.insert("dev", "URL", "http://${HOST}").await.unwrap();
+        db.add_parent("dev", "base").await.unwrap();
+        db.insert("prod", "URL", "http://${HOST}").await.unwrap();
+        db.insert("stage", "LINK", "${base:HOST}").await.unwrap();
+        db.insert("stage", "PRICE", "$${base:HOST}").await.unwrap();
+        db.insert("old", "KEY", "v").await.unwrap();
+        db.add_parent("old", "base").await.unwrap();
+        db.trash_env("old").await.unwrap();
+
+        // prod does not inherit HOST from base
+        let err = rename_key(&db, Some("base"), "host", "hostname")
+            .await
+            .expect_err("HOST is referenced");
+        assert_eq!(
+            "variable 'HOST' is referenced by dev:URL, stage:LINK, update them first",
+            err.to_string()
+        );
+        let err = rename_env(&db, "base", "core")
+            .await
+            .expect_err("base is referenced");
+        assert_eq!(
+            "environment 'base' is referenced by stage:LINK, update them first",
+            err.to_string()
+        );
+
+        db.soft_delete_key_in_env("stage", "link").await.unwrap();
+        rename_env(&db, "base", "core").await.unwrap();
+        db.restore_trashed_env("old").await.unwrap();
+        assert_eq!(vec!["core"], db.parents("old").await.unwrap());
+    }
+}
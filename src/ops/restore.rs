@@ -0,0 +1,186 @@
+use std::io::Write;
+
+use anyhow::{Result, ensure};
+
+use super::diff::{ANSI_DEFAULT, ANSI_GRAY, ANSI_GREEN, ANSI_RED};
+use super::secret::Masker;
+use crate::db::{Change, EnvelopeDb};
+use crate::dotenv;
+
+/// Prints the changes that restoring `env` to unix timestamp `at` would apply,
+/// rendered like `diff env@at env`. Unset variables are shown as `(unset)` and
+/// base64 encoded values are followed by `(base64)`, so that changes to these
+/// alone are visible. Returns whether there is anything to restore.
+pub async fn restore_preview<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    env: &str,
+    at: i64,
+    reveal: bool,
+) -> Result<bool> {
+    ensure!(
+        db.env_exists(env).await?,
+        "environment '{env}' does not exist"
+    );
+
+    let rows = db.restorable(env, at).await?;
+    let masker = Masker::load(db, reveal).await?;
+    for row in &rows {
+        let k = &row.key;
+        let past = state(
+            &masker,
+            k,
+            &row.past_value,
+            row.past_tombstone,
+            row.past_base64,
+        );
+        let current = state(&masker, k, &row.value, row.tombstone, row.base64);
+        match (past, current) {
+            (Some(past), None) => writeln!(writer, "{ANSI_GREEN}+ {k}={past}{ANSI_DEFAULT}")?,
+            (None, Some(current)) => writeln!(writer, "{ANSI_RED}- {k}={current}{ANSI_DEFAULT}")?,
+            (Some(past), Some(current)) => {
+                writeln!(writer, "{ANSI_GRAY}/ {k}={past} -> {current}{ANSI_DEFAULT}")?
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(!rows.is_empty())
+}
+
+/// Renders the state of variable `key` for [`restore_preview`], `None` if it
+/// is deleted
+fn state(
+    masker: &Masker,
+    key: &str,
+    value: &Option<String>,
+    tombstone: bool,
+    base64: bool,
+) -> Option<String> {
+    if tombstone {
+        return Some("(unset)".to_owned());
+    }
+
+    let value = dotenv::quote(masker.mask(key, value.as_deref()?));
+    Some(match base64 {
+        true => format!("{value} (base64)"),
+        false => value.into_owned(),
+    })
+}
+
+/// Restores every variable of `env` to the state it had at unix timestamp `at`
+pub async fn restore(db: &EnvelopeDb, env: &str, at: i64, change: Change<'_>) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    tx.restore_env(env, at).await?;
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Resolve;
+    use crate::db::model::EnvironmentRow;
+
+    #[sqlx::test]
+    async fn test_restore(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+            ('dev', 'KEPT', 'same', 0),
+            ('dev', 'CHANGED', 'old', 0),
+            ('dev', 'CHANGED', 'new', 100),
+            ('dev', 'DELETED', 'gone', 0),
+            ('dev', 'DELETED', NULL, 100),
+            ('dev', 'ADDED', 'later', 100),
+            ('prod', 'CHANGED', 'untouched', 100);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut output = Vec::new();
+        assert!(
+            restore_preview(&mut output, &db, "dev", 50, false)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            "\x1b[31m- ADDED=later\x1b[0m\n\x1b[90m/ CHANGED=old -> new\x1b[0m\n\x1b[32m+ \
+             DELETED=gone\x1b[0m\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        restore(&db, "dev", 50, Change::default()).await.unwrap();
+
+        let mut rows = db.list_kv_in_env("dev", Resolve::default()).await.unwrap();
+        rows.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("dev", "CHANGED", "old"),
+                EnvironmentRow::from("dev", "DELETED", "gone"),
+                EnvironmentRow::from("dev", "KEPT", "same"),
+            ],
+            rows
+        );
+        assert_eq!(
+            vec![EnvironmentRow::from("prod", "CHANGED", "untouched")],
+            db.list_kv_in_env("prod", Resolve::default()).await.unwrap()
+        );
+
+        // history is preserved and nothing is left to restore
+        let mut output = Vec::new();
+        assert!(
+            !restore_preview(&mut output, &db, "dev", 50, false)
+                .await
+                .unwrap()
+        );
+        assert_eq!(3, db.history("dev", "CHANGED").await.unwrap().len());
+    }
+
+    #[sqlx::test]
+    async fn test_restore_tombstone_and_encoding(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, tombstone, base64, created_at)
+            VALUES
+            ('base', 'HOST', 'base.host', FALSE, FALSE, 0),
+            ('dev', 'CERT', 'AP8=', FALSE, FALSE, 0),
+            ('dev', 'CERT', 'AP8=', FALSE, TRUE, 100),
+            ('dev', 'HOST', NULL, TRUE, FALSE, 100);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        crate::ops::add_parent(&db, "dev", "base").await.unwrap();
+
+        let mut output = Vec::new();
+        assert!(
+            restore_preview(&mut output, &db, "dev", 50, false)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            "\x1b[90m/ CERT=AP8= -> AP8= (base64)\x1b[0m\n\x1b[31m- HOST=(unset)\x1b[0m\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        restore(&db, "dev", 50, Change::default()).await.unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("base", "HOST", "base.host"),
+                EnvironmentRow::from("dev", "CERT", "AP8="),
+            ],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+        assert!(
+            !restore_preview(&mut Vec::new(), &db, "dev", 50, false)
+                .await
+                .unwrap()
+        );
+    }
+}
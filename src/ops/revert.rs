@@ -1,9 +1,130 @@
-use anyhow::Result;
+use anyhow::{Result, ensure};
 
-use crate::db::EnvelopeDb;
+use crate::db::{Change, EnvelopeDb};
 
-pub async fn revert(db: &EnvelopeDb, env: &str, key: &str) -> Result<()> {
+/// Restores the value `key` had at revision `rev` by recording it as a new
+/// revision
+pub async fn revert_to(
+    db: &EnvelopeDb,
+    env: &str,
+    key: &str,
+    rev: i64,
+    change: Change<'_>,
+) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    let revisions = tx.revisions(env, key).await?;
+    ensure!(
+        !revisions.is_empty(),
+        "variable '{key}' does not exist in environment '{env}'"
+    );
+    ensure!(
+        revisions.contains(&rev),
+        "revision {rev} is not a revision of '{key}' in environment '{env}'"
+    );
+
+    tx.revert_to(env, key, rev).await?;
+    tx.commit().await
+}
+
+/// Restores the value `key` had `steps` revisions ago by recording it as a new
+/// revision
+pub async fn revert_steps(
+    db: &EnvelopeDb,
+    env: &str,
+    key: &str,
+    steps: i64,
+    change: Change<'_>,
+) -> Result<()> {
+    let mut tx = db.begin_with(change).await?;
+    let revisions = tx.revisions(env, key).await?;
+    let count = revisions.len() as i64;
+    ensure!(
+        count > steps,
+        "cannot go back {steps} revisions, '{key}' only has {count}"
+    );
+
+    let rev = revisions[(count - 1 - steps) as usize];
+    tx.revert_to(env, key, rev).await?;
+    tx.commit().await
+}
+
+/// Deletes the latest revision of `key` from history
+pub async fn revert_discard(db: &EnvelopeDb, env: &str, key: &str) -> Result<()> {
     db.revert(env, key).await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Resolve;
+    use crate::db::model::EnvironmentRow;
+
+    #[sqlx::test]
+    async fn test_revert_preserves_history(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        sqlx::query(
+            r"INSERT INTO environments (env, key, value, created_at)
+            VALUES
+            ('dev', 'KEY', 'v1', 0),
+            ('dev', 'KEY', 'v2', 10),
+            ('dev', 'KEY', NULL, 20),
+            ('dev', 'KEY', 'v3', 30);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        revert_to(&db, "dev", "key", 1, Change::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "KEY", "v1")],
+            db.list_kv_in_env("dev", Resolve::default()).await.unwrap()
+        );
+        assert_eq!(5, db.history("dev", "KEY").await.unwrap().len());
+
+        // reverting to the current value records nothing
+        revert_to(&db, "dev", "key", 5, Change::default())
+            .await
+            .unwrap();
+        assert_eq!(5, db.history("dev", "KEY").await.unwrap().len());
+
+        // two steps back from revision 5 is revision 3, where KEY was deleted
+        revert_steps(&db, "dev", "key", 2, Change::default())
+            .await
+            .unwrap();
+        assert!(
+            db.list_kv_in_env("dev", Resolve::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(6, db.history("dev", "KEY").await.unwrap().len());
+
+        assert!(
+            revert_to(&db, "dev", "key", 0, Change::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            revert_to(&db, "dev", "key", 7, Change::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            revert_steps(&db, "dev", "key", 6, Change::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            revert_to(&db, "dev", "missing", 1, Change::default())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::db::EnvelopeDb;
+use crate::db::model::EnvironmentRow;
+use crate::interpolate::Interpolator;
+
+/// Replaces the values of secrets in output
+pub const MASK: &str = "********";
+
+/// Names, or suffixes of names following an underscore, of the variables
+/// guessed to hold secrets
+const SECRET_NAMES: [&str; 10] = [
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "SECRET",
+    "SECRET_KEY",
+    "API_KEY",
+    "PRIVATE_KEY",
+    "ACCESS_KEY",
+    "CREDENTIALS",
+    "AUTH",
+];
+
+/// Whether the name of `key` suggests that it holds a secret, e.g.
+/// `GITHUB_TOKEN` or `DB_PASSWORD`
+pub fn is_secret_name(key: &str) -> bool {
+    let key = key.to_uppercase();
+    SECRET_NAMES.iter().any(|name| {
+        key == *name
+            || key
+                .strip_suffix(name)
+                .is_some_and(|prefix| prefix.ends_with('_'))
+    })
+}
+
+/// Decides which values are secret, and masks them unless revealed
+pub struct Masker {
+    /// classification set with the classify command, by uppercased key
+    classification: HashMap<String, bool>,
+    /// variables whose expanded value embeds the value of a secret
+    embedding: HashSet<String>,
+    reveal: bool,
+}
+
+impl Masker {
+    pub async fn load(db: &EnvelopeDb, reveal: bool) -> Result<Self> {
+        Ok(Self {
+            classification: db.classification().await?.into_iter().collect(),
+            embedding: HashSet::new(),
+            reveal,
+        })
+    }
+
+    /// Marks as secret the variables of `vars`, expanded in `env` by
+    /// `interpolator`, whose value embeds the value of a secret through a
+    /// reference, whatever their own classification
+    pub fn track(&mut self, env: &str, vars: &[EnvironmentRow], interpolator: &Interpolator) {
+        for var in vars {
+            if interpolator
+                .references(env, &var.key)
+                .any(|key| self.is_secret(key))
+            {
+                self.embedding.insert(var.key.to_uppercase());
+            }
+        }
+    }
+
+    /// Whether the values of `key` are secret, as classified or else as
+    /// guessed from its name, or because they embed a secret
+    pub fn is_secret(&self, key: &str) -> bool {
+        if self.embedding.contains(&key.to_uppercase()) {
+            return true;
+        }
+
+        match self.classification.get(&key.to_uppercase()) {
+            Some(secret) => *secret,
+            None => is_secret_name(key),
+        }
+    }
+
+    /// Returns `value`, or [`MASK`] if it is the value of a secret and secrets
+    /// are not revealed
+    pub fn mask<'a>(&self, key: &str, value: &'a str) -> &'a str {
+        match !self.reveal && self.is_secret(key) {
+            true => MASK,
+            false => value,
+        }
+    }
+}
+
+/// Classifies `key` as secret or not, or lets its name decide if `secret` is
+/// `None`
+pub async fn classify(db: &EnvelopeDb, key: &str, secret: Option<bool>) -> Result<()> {
+    db.set_classification(key, secret).await
+}
+
+/// Writes whether `key` is secret, and why
+pub async fn show_classification<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    key: &str,
+) -> Result<()> {
+    let classified = db
+        .classification()
+        .await?
+        .into_iter()
+        .find(|(k, _)| *k == key.to_uppercase());
+
+    let class = |secret: bool| match secret {
+        true => "secret",
+        false => "plain",
+    };
+    match classified {
+        Some((_, secret)) => writeln!(writer, "{}", class(secret))?,
+        None => writeln!(writer, "{} (from its name)", class(is_secret_name(key)))?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+
+    #[test]
+    fn test_is_secret_name() {
+        for key in [
+            "github_token",
+            "DB_PASSWORD",
+            "SECRET",
+            "STRIPE_SECRET_KEY",
+            "api_key",
+        ] {
+            assert!(is_secret_name(key), "{key}");
+        }
+        for key in ["TOKENS", "PASSWORD_POLICY", "DEBUG", "HOSTAUTH", "KEY"] {
+            assert!(!is_secret_name(key), "{key}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_classify(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        classify(&db, "license", Some(true)).await.unwrap();
+        classify(&db, "csrf_token", Some(false)).await.unwrap();
+
+        let masker = Masker::load(&db, false).await.unwrap();
+        assert_eq!(MASK, masker.mask("LICENSE", "abc"));
+        assert_eq!(MASK, masker.mask("DB_PASSWORD", "pass"));
+        assert_eq!("t1", masker.mask("CSRF_TOKEN", "t1"));
+        assert_eq!("true", masker.mask("DEBUG", "true"));
+        assert_eq!(
+            "pass",
+            Masker::load(&db, true)
+                .await
+                .unwrap()
+                .mask("DB_PASSWORD", "pass")
+        );
+
+        let mut output = Vec::new();
+        for key in ["license", "csrf_token", "db_password", "debug"] {
+            show_classification(&mut output, &db, key).await.unwrap();
+        }
+        assert_eq!(
+            "secret\nplain\nsecret (from its name)\nplain (from its name)\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        classify(&db, "csrf_token", None).await.unwrap();
+        assert!(
+            Masker::load(&db, false)
+                .await
+                .unwrap()
+                .is_secret("CSRF_TOKEN")
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use crate::db::EnvelopeDb;
+use crate::time;
+
+/// How long dropped environments are kept in the trash, unless overridden by
+/// `ENVELOPE_TRASH_RETENTION`
+const DEFAULT_TRASH_RETENTION: &str = "30d";
+
+/// Returns how long dropped environments are kept in the trash, in seconds
+pub fn trash_retention() -> Result<u64> {
+    match std::env::var("ENVELOPE_TRASH_RETENTION") {
+        Ok(retention) => time::parse_duration(&retention)
+            .with_context(|| format!("invalid ENVELOPE_TRASH_RETENTION '{retention}'")),
+        Err(_) => time::parse_duration(DEFAULT_TRASH_RETENTION),
+    }
+}
+
+/// Returns the unix timestamp at or before which dropped environments have
+/// outlived the trash retention, and are deleted by gc
+pub fn trash_expiry() -> Result<i64> {
+    Ok(time::now() - trash_retention()? as i64)
+}
+
+/// Lists the environments in the trash dropped at or before `before`, or all
+/// of them if `before` is `None`. Returns whether there is any.
+pub async fn list_trash<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    before: Option<i64>,
+) -> Result<bool> {
+    let trash = db.list_trash(before).await?;
+    for env in &trash {
+        writeln!(writer, "{} (dropped {})", env.env, env.dropped_at)?;
+    }
+
+    Ok(!trash.is_empty())
+}
+
+/// Restores the most recently dropped environment named `env`
+pub async fn restore_env(db: &EnvelopeDb, env: &str) -> Result<()> {
+    db.restore_trashed_env(env).await
+}
+
+/// Permanently deletes the environments dropped at or before `before`, or the
+/// whole trash if `before` is `None`
+pub async fn empty_trash(db: &EnvelopeDb, before: Option<i64>) -> Result<u64> {
+    db.empty_trash(before).await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::Resolve;
+    use crate::db::model::EnvironmentRow;
+    use crate::ops;
+
+    #[sqlx::test]
+    async fn test_drop_and_restore(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        db.insert("base", "HOST", "base.host").await.unwrap();
+        db.insert("dev", "KEY", "v1").await.unwrap();
+        db.insert("dev", "KEY", "v2").await.unwrap();
+        db.add_parent("dev", "base").await.unwrap();
+        db.add_parent("child", "dev").await.unwrap();
+
+        ops::drop(&db, "dev").await.unwrap();
+        assert!(!db.env_exists("dev").await.unwrap());
+        assert!(
+            db.list_kv_in_env("child", Resolve::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let err = ops::drop(&db, "dev")
+            .await
+            .expect_err("dev no longer exists");
+        assert!(
+            err.to_string().contains("does not exist"),
+            "error should mention the missing environment, got: {err}"
+        );
+
+        restore_env(&db, "dev").await.unwrap();
+        assert_eq!(2, db.history("dev", "KEY").await.unwrap().len());
+        assert_eq!(vec!["base"], db.parents("dev").await.unwrap());
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("base", "HOST", "base.host"),
+                EnvironmentRow::from("dev", "KEY", "v2"),
+            ],
+            db.list_kv_in_env("child", Resolve::default())
+                .await
+                .unwrap()
+        );
+
+        let err = restore_env(&db, "dev")
+            .await
+            .expect_err("dev is no longer in the trash");
+        assert!(
+            err.to_string().contains("already exists"),
+            "error should mention the existing environment, got: {err}"
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_empty_trash(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool.clone());
+
+        db.insert("old", "KEY", "old").await.unwrap();
+        db.insert("new", "KEY", "new").await.unwrap();
+        ops::drop(&db, "old").await.unwrap();
+        ops::drop(&db, "new").await.unwrap();
+        sqlx::query("UPDATE trash SET dropped_at = 0 WHERE env = 'old'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        assert!(list_trash(&mut output, &db, Some(10)).await.unwrap());
+        assert_eq!(
+            "old (dropped 1970-01-01 00:00:00)\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        assert_eq!(1, empty_trash(&db, Some(10)).await.unwrap());
+        let err = restore_env(&db, "old")
+            .await
+            .expect_err("old was permanently deleted");
+        assert!(
+            err.to_string().contains("not in the trash"),
+            "error should mention the trash, got: {err}"
+        );
+
+        assert_eq!(1, empty_trash(&db, None).await.unwrap());
+        assert!(!list_trash(&mut Vec::new(), &db, None).await.unwrap());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM trash_environments")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, count);
+    }
+}
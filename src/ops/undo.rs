@@ -0,0 +1,200 @@
+use std::io::Write;
+
+use anyhow::{Result, bail};
+
+use crate::db::EnvelopeDb;
+use crate::db::model::Operation;
+
+/// Number of operations listed by [`list_operations`]
+const LISTED_OPERATIONS: u32 = 10;
+
+/// Reverts every change made by the latest command
+pub async fn undo<W: Write>(writer: &mut W, db: &EnvelopeDb) -> Result<()> {
+    let Some(operation) = db.undo().await? else {
+        bail!("nothing to undo");
+    };
+
+    writeln!(writer, "undid {}", operation.command)?;
+
+    Ok(())
+}
+
+/// Applies again the changes of the command undone last
+pub async fn redo<W: Write>(writer: &mut W, db: &EnvelopeDb) -> Result<()> {
+    let Some(operation) = db.redo().await? else {
+        bail!("nothing to redo");
+    };
+
+    writeln!(writer, "redid {}", operation.command)?;
+
+    Ok(())
+}
+
+/// Lists the latest commands that can be undone or redone, most recent first
+pub async fn list_operations<W: Write>(writer: &mut W, db: &EnvelopeDb) -> Result<()> {
+    for Operation {
+        id,
+        command,
+        created_at,
+        undone,
+    } in db.operations(LISTED_OPERATIONS).await?
+    {
+        let undone = if undone { " (undone)" } else { "" };
+        writeln!(writer, "{id} {created_at} {command}{undone}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db::model::EnvironmentRow;
+    use crate::db::{Change, MetaUpdate, Resolve};
+    use crate::ops;
+
+    async fn record(db: &EnvelopeDb, command: &str, keys: &[(&str, &str)]) {
+        let recording = db.begin_operation(command).await.unwrap();
+        let mut tx = db.begin().await.unwrap();
+        for (k, v) in keys {
+            tx.insert("dev", k, v).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+        db.end_operation(recording).await.unwrap();
+    }
+
+    async fn list(db: &EnvelopeDb, env: &str) -> Vec<EnvironmentRow> {
+        db.list_kv_in_env_alt(env, crate::db::Truncate::None, "k", Resolve::default())
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_undo_redo(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+
+        record(&db, "import dev", &[("A", "1"), ("B", "2")]).await;
+        record(&db, "import dev", &[("A", "3"), ("C", "4")]).await;
+        // commands changing nothing are not recorded
+        record(&db, "list dev", &[]).await;
+        assert_eq!(2, db.operations(10).await.unwrap().len());
+
+        let mut output = Vec::new();
+        undo(&mut output, &db).await.unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("dev", "A", "1"),
+                EnvironmentRow::from("dev", "B", "2"),
+            ],
+            list(&db, "dev").await
+        );
+        undo(&mut output, &db).await.unwrap();
+        assert!(list(&db, "dev").await.is_empty());
+        assert!(undo(&mut output, &db).await.is_err());
+
+        redo(&mut output, &db).await.unwrap();
+        assert_eq!(2, list(&db, "dev").await.len());
+        assert_eq!(
+            "undid import dev\nundid import dev\nredid import dev\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        // a new operation discards the ones left to redo
+        record(&db, "add dev", &[("D", "5")]).await;
+        assert!(redo(&mut Vec::new(), &db).await.is_err());
+        let operations = db.operations(10).await.unwrap();
+        assert_eq!(
+            vec![("add dev", false), ("import dev", false)],
+            operations
+                .iter()
+                .map(|o| (o.command.as_str(), o.undone))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_undo_drop_and_rename(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        db.insert("base", "HOST", "base.host").await.unwrap();
+        db.insert("dev", "DEBUG", "true").await.unwrap();
+        ops::add_parent(&db, "dev", "base").await.unwrap();
+
+        let recording = db.begin_operation("drop base").await.unwrap();
+        ops::drop(&db, "base").await.unwrap();
+        db.end_operation(recording).await.unwrap();
+
+        let recording = db.begin_operation("rename-key").await.unwrap();
+        ops::rename_key(&db, None, "debug", "verbose")
+            .await
+            .unwrap();
+        ops::delete_var_globally(&db, "verbose", Change::default())
+            .await
+            .unwrap();
+        db.end_operation(recording).await.unwrap();
+        assert!(list(&db, "dev").await.is_empty());
+
+        undo(&mut Vec::new(), &db).await.unwrap();
+        undo(&mut Vec::new(), &db).await.unwrap();
+        assert_eq!(
+            vec![
+                EnvironmentRow::from("dev", "DEBUG", "true"),
+                EnvironmentRow::from("base", "HOST", "base.host"),
+            ],
+            list(&db, "dev").await
+        );
+        assert!(db.list_trash(None).await.unwrap().is_empty());
+
+        redo(&mut Vec::new(), &db).await.unwrap();
+        assert_eq!(1, db.list_trash(None).await.unwrap().len());
+        assert_eq!(
+            vec![EnvironmentRow::from("dev", "DEBUG", "true")],
+            list(&db, "dev").await
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_undo_guards_envs(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        db.insert("prod", "A", "1").await.unwrap();
+
+        let recording = db.begin_operation("add prod").await.unwrap();
+        ops::add_var(&db, "prod", "b", "2", Change::default())
+            .await
+            .unwrap();
+        db.end_operation(recording).await.unwrap();
+
+        let protect = MetaUpdate {
+            protected: Some(true),
+            ..Default::default()
+        };
+        let recording = db.begin_operation("env set-meta prod").await.unwrap();
+        ops::set_meta(&db, "prod", protect).await.unwrap();
+        db.end_operation(recording).await.unwrap();
+
+        // without confirmation, the protection cannot be undone
+        let err = undo(&mut Vec::new(), &db).await.unwrap_err();
+        assert_eq!("environment 'prod' is protected", err.to_string());
+        assert!(
+            db.environment_meta("prod")
+                .await
+                .unwrap()
+                .unwrap()
+                .protected
+        );
+
+        db.on_confirm(|_| Ok(true));
+        undo(&mut Vec::new(), &db).await.unwrap();
+        let read_only = MetaUpdate {
+            read_only: Some(true),
+            ..Default::default()
+        };
+        ops::set_meta(&db, "prod", read_only).await.unwrap();
+
+        // the data of a read-only environment cannot be changed by undo either
+        let err = undo(&mut Vec::new(), &db).await.unwrap_err();
+        assert_eq!("environment 'prod' is read-only", err.to_string());
+        assert_eq!(2, list(&db, "prod").await.len());
+    }
+}
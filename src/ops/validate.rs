@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::diff::{ANSI_DEFAULT, ANSI_GRAY, ANSI_GREEN, ANSI_RED};
+use super::read_expanded;
+use super::secret::Masker;
+use crate::db::model::EnvironmentRow;
+use crate::db::{EnvelopeDb, Resolve};
+use crate::dotenv;
+use crate::schema::Schema;
+
+/// A variable not matching the schema
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// required variable that is not set
+    Missing(String),
+    /// variable that is not declared, along with its value
+    Extra(String, String),
+    /// variable whose value does not have the declared type
+    Malformed {
+        key: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl Problem {
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Missing(k) | Self::Extra(k, _) | Self::Malformed { key: k, .. } => k,
+        }
+    }
+}
+
+/// Checks the variables of an environment against `schema`, returning the
+/// problems sorted by key
+pub fn check_vars(schema: &Schema, vars: &[EnvironmentRow]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for var in vars {
+        match schema.keys.get(&var.key) {
+            Some(key) => {
+                if let Err(expected) = key.check(&var.value) {
+                    problems.push(Problem::Malformed {
+                        key: var.key.clone(),
+                        value: var.value.clone(),
+                        expected,
+                    });
+                }
+            }
+            None if !schema.allow_extra => {
+                problems.push(Problem::Extra(var.key.clone(), var.value.clone()))
+            }
+            None => {}
+        }
+    }
+
+    let set: HashSet<&str> = vars.iter().map(|var| var.key.as_str()).collect();
+    for (name, key) in &schema.keys {
+        if key.required && key.default.is_none() && !set.contains(name.as_str()) {
+            problems.push(Problem::Missing(name.clone()));
+        }
+    }
+
+    problems.sort_by(|a, b| a.key().cmp(b.key()));
+    problems
+}
+
+/// Adds the defaults of the variables of `schema` missing from `vars`
+pub fn apply_defaults(schema: &Schema, env: &str, vars: &mut Vec<EnvironmentRow>) {
+    let set: HashSet<String> = vars.iter().map(|var| var.key.clone()).collect();
+    for (name, key) in &schema.keys {
+        if let Some(default) = &key.default
+            && !set.contains(name)
+        {
+            vars.push(EnvironmentRow {
+                env: env.to_owned(),
+                key: name.clone(),
+                value: default.clone(),
+                base64: false,
+            });
+        }
+    }
+}
+
+/// Renders `problems` with the markers of the diff command: missing variables
+/// in red, extra ones in green and malformed ones in gray
+pub fn write_problems<W: Write>(
+    writer: &mut W,
+    problems: &[Problem],
+    masker: &Masker,
+) -> Result<()> {
+    for problem in problems {
+        match problem {
+            Problem::Missing(k) => {
+                writeln!(writer, "{ANSI_RED}- {k} is missing{ANSI_DEFAULT}")?;
+            }
+            Problem::Extra(k, v) => {
+                let v = dotenv::quote(masker.mask(k, v));
+                writeln!(
+                    writer,
+                    "{ANSI_GREEN}+ {k}={v} is not declared{ANSI_DEFAULT}"
+                )?;
+            }
+            Problem::Malformed {
+                key,
+                value,
+                expected,
+            } => {
+                let value = dotenv::quote(masker.mask(key, value));
+                writeln!(
+                    writer,
+                    "{ANSI_GRAY}/ {key}={value} is not {expected}{ANSI_DEFAULT}"
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks every environment in `envs` against `schema` and writes the
+/// problems found, under the name of their environment when there is more
+/// than one. Returns the environments not matching the schema.
+pub async fn validate<W: Write>(
+    writer: &mut W,
+    db: &EnvelopeDb,
+    schema: &Schema,
+    envs: &[String],
+    reveal: bool,
+) -> Result<Vec<String>> {
+    let mut invalid = Vec::new();
+    for env in envs {
+        // variables embed secrets in some environments only
+        let mut masker = Masker::load(db, reveal).await?;
+        let vars = read_expanded(db, env, Resolve::default(), &mut masker).await?;
+        let problems = check_vars(schema, &vars);
+        if problems.is_empty() {
+            continue;
+        }
+
+        if envs.len() > 1 {
+            writeln!(writer, "{env}")?;
+        }
+        write_problems(writer, &problems, &masker)?;
+        invalid.push(env.clone());
+    }
+
+    Ok(invalid)
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        [keys.DATABASE_URL]
+        type = "url"
+
+        [keys.PORT]
+        type = "port"
+        default = "8080"
+
+        [keys.DB_PASSWORD]
+        required = false
+    "#;
+
+    #[test]
+    fn test_check() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let vars = vec![
+            EnvironmentRow::from("dev", "PORT", "http"),
+            EnvironmentRow::from("dev", "DEBUG", "true"),
+        ];
+        assert_eq!(
+            vec![
+                Problem::Missing("DATABASE_URL".to_owned()),
+                Problem::Extra("DEBUG".to_owned(), "true".to_owned()),
+                Problem::Malformed {
+                    key: "PORT".to_owned(),
+                    value: "http".to_owned(),
+                    expected: "a port".to_owned(),
+                },
+            ],
+            check_vars(&schema, &vars)
+        );
+
+        let schema = Schema::parse(&format!("allow_extra = true\n{SCHEMA}")).unwrap();
+        let mut vars = vec![
+            EnvironmentRow::from("dev", "DATABASE_URL", "postgres://db"),
+            EnvironmentRow::from("dev", "DEBUG", "true"),
+        ];
+        assert!(check_vars(&schema, &vars).is_empty());
+
+        apply_defaults(&schema, "dev", &mut vars);
+        assert_eq!(EnvironmentRow::from("dev", "PORT", "8080"), vars[2]);
+        assert_eq!(3, vars.len());
+    }
+
+    #[sqlx::test]
+    async fn test_validate(pool: SqlitePool) {
+        let db = EnvelopeDb::with(pool);
+        let schema = Schema::parse(SCHEMA).unwrap();
+        db.insert("dev", "DATABASE_URL", "postgres://${HOST}/db")
+            .await
+            .unwrap();
+        db.insert("dev", "DB_PASSWORD", "hunter2").await.unwrap();
+        db.insert("dev", "HOST", "localhost").await.unwrap();
+        db.insert("prod", "DATABASE_URL", "postgres://prod/db")
+            .await
+            .unwrap();
+        db.insert("prod", "DB_PASSWORD", "s3cret").await.unwrap();
+
+        let envs = vec!["dev".to_owned(), "prod".to_owned()];
+        let mut output = Vec::new();
+        let invalid = validate(&mut output, &db, &schema, &envs, false)
+            .await
+            .unwrap();
+        assert_eq!(vec!["dev"], invalid);
+        assert_eq!(
+            format!("dev\n{ANSI_GREEN}+ HOST=localhost is not declared{ANSI_DEFAULT}\n"),
+            String::from_utf8(output).unwrap()
+        );
+
+        // secrets are masked
+        let schema = Schema::parse("[keys.DB_PASSWORD]\ntype = \"int\"").unwrap();
+        let mut output = Vec::new();
+        validate(&mut output, &db, &schema, &envs[1..], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            format!(
+                "{ANSI_GREEN}+ DATABASE_URL=postgres://prod/db is not \
+                 declared{ANSI_DEFAULT}\n{ANSI_GRAY}/ DB_PASSWORD=******** is not an \
+                 integer{ANSI_DEFAULT}\n"
+            ),
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Schema declaring the variables expected in every environment.
+//!
+//! The schema is read from `.envelope.schema.toml`, next to the envelope:
+//!
+//! ```toml
+//! # accept variables that are not declared below, defaults to false
+//! allow_extra = true
+//!
+//! [keys.DATABASE_URL]
+//! type = "url"
+//! required = true
+//!
+//! [keys.PORT]
+//! type = "port"
+//! default = "8080"
+//!
+//! [keys.LOG_LEVEL]
+//! type = "enum"
+//! values = ["debug", "info", "warn"]
+//!
+//! [keys.RELEASE]
+//! type = "regex"
+//! pattern = 'v\d+\.\d+'
+//! ```
+//!
+//! Supported types are `string` (the default), `int`, `bool`, `url`, `port`,
+//! `enum` and `regex`. A key is required unless it says otherwise or has a
+//! default, which is used when the variable is missing.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail, ensure};
+use regex::Regex;
+use serde::Deserialize;
+
+pub const SCHEMA_FILENAME: &str = ".envelope.schema.toml";
+
+/// Returns the path of the schema file, in the current directory
+pub fn schema_path() -> Result<PathBuf> {
+    Ok(env::current_dir()?.join(SCHEMA_FILENAME))
+}
+
+/// Type of the values of a variable
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Type {
+    #[default]
+    String,
+    Int,
+    Bool,
+    Url,
+    Port,
+    Enum,
+    Regex,
+}
+
+/// Declaration of a variable, as written in the schema file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawKey {
+    #[serde(default, rename = "type")]
+    ty: Type,
+    required: Option<bool>,
+    default: Option<String>,
+    /// allowed values of an `enum`
+    values: Option<Vec<String>>,
+    /// pattern matching the whole value of a `regex`
+    pattern: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSchema {
+    #[serde(default)]
+    allow_extra: bool,
+    #[serde(default)]
+    keys: BTreeMap<String, RawKey>,
+}
+
+/// Constraint on the values of a variable
+#[derive(Debug)]
+enum Check {
+    Any,
+    Int,
+    Bool,
+    Url,
+    Port,
+    OneOf(Vec<String>),
+    /// `re` matches the whole value, unlike `pattern` as written
+    Matches {
+        re: Regex,
+        pattern: String,
+    },
+}
+
+/// Declaration of a variable
+#[derive(Debug)]
+pub struct Key {
+    check: Check,
+    pub required: bool,
+    pub default: Option<String>,
+}
+
+impl Key {
+    /// Checks `value` against the type of the variable, returning what was
+    /// expected if it does not match
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let valid = match &self.check {
+            Check::Any => true,
+            Check::Int => value.parse::<i64>().is_ok(),
+            Check::Bool => matches!(
+                value.to_lowercase().as_str(),
+                "true" | "false" | "1" | "0" | "yes" | "no"
+            ),
+            Check::Url => is_url(value),
+            Check::Port => value.parse::<u16>().is_ok_and(|port| port > 0),
+            Check::OneOf(values) => values.iter().any(|v| v == value),
+            Check::Matches { re, .. } => re.is_match(value),
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(self.expected()),
+        }
+    }
+
+    /// Describes the values accepted by the variable
+    fn expected(&self) -> String {
+        match &self.check {
+            Check::Any => "a string".to_owned(),
+            Check::Int => "an integer".to_owned(),
+            Check::Bool => "a boolean".to_owned(),
+            Check::Url => "a url".to_owned(),
+            Check::Port => "a port".to_owned(),
+            Check::OneOf(values) => format!("one of {}", values.join(", ")),
+            Check::Matches { pattern, .. } => format!("a value matching '{pattern}'"),
+        }
+    }
+}
+
+/// Whether `value` looks like `scheme://rest`
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !rest.contains(char::is_whitespace)
+}
+
+/// Variables expected in every environment
+#[derive(Debug)]
+pub struct Schema {
+    /// whether variables that are not declared are accepted
+    pub allow_extra: bool,
+    /// declared variables, by uppercased name
+    pub keys: BTreeMap<String, Key>,
+}
+
+impl Schema {
+    /// Reads the schema at `path`, or returns `None` if there is none
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("invalid schema {}", path.display()))
+            .map(Some)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let raw: RawSchema = toml::from_str(content)?;
+
+        let mut keys = BTreeMap::new();
+        for (name, key) in raw.keys {
+            let check = match key.ty {
+                Type::String => Check::Any,
+                Type::Int => Check::Int,
+                Type::Bool => Check::Bool,
+                Type::Url => Check::Url,
+                Type::Port => Check::Port,
+                Type::Enum => match key.values {
+                    Some(values) if !values.is_empty() => Check::OneOf(values),
+                    _ => bail!("key '{name}' of type enum needs a list of values"),
+                },
+                Type::Regex => {
+                    let pattern = key
+                        .pattern
+                        .with_context(|| format!("key '{name}' of type regex needs a pattern"))?;
+                    let re = Regex::new(&format!("^(?:{pattern})$"))
+                        .with_context(|| format!("invalid pattern of key '{name}'"))?;
+                    Check::Matches { re, pattern }
+                }
+            };
+
+            let key = Key {
+                check,
+                required: key.required.unwrap_or(key.default.is_none()),
+                default: key.default,
+            };
+            if let Some(default) = &key.default
+                && let Err(expected) = key.check(default)
+            {
+                bail!("default of key '{name}' is not {expected}");
+            }
+
+            let upper = name.to_uppercase();
+            ensure!(
+                keys.insert(upper.clone(), key).is_none(),
+                "key '{upper}' is declared more than once"
+            );
+        }
+
+        Ok(Self {
+            allow_extra: raw.allow_extra,
+            keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        [keys.database_url]
+        type = "url"
+
+        [keys.PORT]
+        type = "port"
+        default = "8080"
+
+        [keys.DEBUG]
+        type = "bool"
+        required = false
+
+        [keys.LOG_LEVEL]
+        type = "enum"
+        values = ["debug", "info"]
+
+        [keys.RELEASE]
+        type = "regex"
+        pattern = 'v\d+'
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        assert!(!schema.allow_extra);
+        assert_eq!(
+            vec!["DATABASE_URL", "DEBUG", "LOG_LEVEL", "PORT", "RELEASE"],
+            schema.keys.keys().collect::<Vec<_>>()
+        );
+        assert!(schema.keys["DATABASE_URL"].required);
+        assert!(!schema.keys["PORT"].required);
+        assert!(!schema.keys["DEBUG"].required);
+
+        assert!(Schema::parse("[keys.A]\ntype = \"float\"").is_err());
+        assert!(Schema::parse("[keys.A]\ntype = \"enum\"").is_err());
+        assert!(Schema::parse("[keys.A]\ntype = \"regex\"\npattern = \"(\"").is_err());
+        assert!(Schema::parse("[keys.A]\ntype = \"int\"\ndefault = \"x\"").is_err());
+        assert!(Schema::parse("[keys.a]\n[keys.A]").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let check = |key: &str, value: &str| schema.keys[key].check(value);
+
+        assert!(check("DATABASE_URL", "postgres://localhost/db").is_ok());
+        assert_eq!(Err("a url".to_owned()), check("DATABASE_URL", "localhost"));
+        assert!(check("PORT", "443").is_ok());
+        assert_eq!(Err("a port".to_owned()), check("PORT", "0"));
+        assert_eq!(Err("a port".to_owned()), check("PORT", "70000"));
+        assert!(check("DEBUG", "False").is_ok());
+        assert_eq!(Err("a boolean".to_owned()), check("DEBUG", "maybe"));
+        assert_eq!(
+            Err("one of debug, info".to_owned()),
+            check("LOG_LEVEL", "trace")
+        );
+        assert!(check("RELEASE", "v12").is_ok());
+        assert_eq!(
+            Err(r"a value matching 'v\d+'".to_owned()),
+            check("RELEASE", "v12-rc")
+        );
+    }
+}
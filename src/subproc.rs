@@ -1,3 +1,4 @@
+use std::ffi::OsStr;
 use std::process::{Command, ExitStatus};
 
 use anyhow::{Context, Result};
@@ -5,12 +6,12 @@ use anyhow::{Context, Result};
 pub struct ChildProcess<'a> {
     command: &'a str,
     args: &'a [&'a str],
-    env: &'a [(&'a str, &'a str)],
+    env: &'a [(&'a str, &'a OsStr)],
     isolated: bool,
 }
 
 impl<'a> ChildProcess<'a> {
-    pub fn new(command: &'a str, args: &'a [&'a str], env: &'a [(&'a str, &'a str)]) -> Self {
+    pub fn new(command: &'a str, args: &'a [&'a str], env: &'a [(&'a str, &'a OsStr)]) -> Self {
         Self {
             command,
             args,
@@ -0,0 +1,197 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail, ensure};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Returns the current unix timestamp in seconds
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Parses a duration made of one or more `<number><unit>` pairs, e.g. `90s`,
+/// `2h30m` or `1w`, into seconds. Supported units are `s`, `m`, `h`, `d` and
+/// `w`.
+pub fn parse_duration(s: &str) -> Result<u64> {
+    ensure!(!s.is_empty(), "empty duration");
+
+    let mut total: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        ensure!(digits > 0, "invalid duration '{s}'");
+
+        let n: u64 = rest[..digits]
+            .parse()
+            .with_context(|| format!("invalid duration '{s}'"))?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => MINUTE,
+            Some('h') => HOUR,
+            Some('d') => DAY,
+            Some('w') => WEEK,
+            _ => bail!("invalid duration '{s}', expected a unit among s, m, h, d, w"),
+        };
+
+        total = n
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .with_context(|| format!("duration '{s}' is too large"))?;
+        rest = &rest[digits + 1..];
+    }
+
+    Ok(total)
+}
+
+/// Formats `secs` as a duration accepted by [`parse_duration`], e.g. `2h30m`.
+/// Days are the largest unit used.
+pub fn format_duration(secs: u64) -> String {
+    if secs == 0 {
+        return "0s".to_owned();
+    }
+
+    let mut rest = secs;
+    let mut out = String::new();
+    for (unit, name) in [(DAY, 'd'), (HOUR, 'h'), (MINUTE, 'm'), (1, 's')] {
+        if rest >= unit {
+            out.push_str(&format!("{}{name}", rest / unit));
+            rest %= unit;
+        }
+    }
+
+    out
+}
+
+/// Parses a point in time into a unix timestamp in seconds.
+///
+/// Accepts a unix timestamp (`1700000000`), a UTC date or date and time
+/// (`2024-01-31`, `2024-01-31 12:30`, `2024-01-31T12:30:00Z`) as printed by
+/// `history`, or a duration relative to now (`2h`, `3d12h`).
+pub fn parse_at(s: &str) -> Result<i64> {
+    if let Ok(ts) = s.parse::<i64>() {
+        return Ok(ts);
+    }
+
+    if let Some(ts) = parse_datetime(s) {
+        return Ok(ts);
+    }
+
+    let ago = parse_duration(s).with_context(|| {
+        format!("invalid point in time '{s}', expected a timestamp, a date or a duration")
+    })?;
+
+    Ok(now().saturating_sub(ago as i64))
+}
+
+/// Parses `YYYY-MM-DD[( |T)HH:MM[:SS]][Z]` as a UTC date
+fn parse_datetime(s: &str) -> Option<i64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((d, t)) => (d, Some(t)),
+        None => (s, None),
+    };
+
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    if !(1..=12).contains(&m) || !(1..=days_in_month(y, m)).contains(&d) {
+        return None;
+    }
+
+    let secs = match time {
+        None => 0,
+        Some(time) => {
+            let parts: Vec<i64> = time
+                .split(':')
+                .map(|p| p.parse().ok())
+                .collect::<Option<_>>()?;
+            let (h, min, sec) = match parts[..] {
+                [h, min] => (h, min, 0),
+                [h, min, sec] => (h, min, sec),
+                _ => return None,
+            };
+            if !(0..24).contains(&h) || !(0..60).contains(&min) || !(0..60).contains(&sec) {
+                return None;
+            }
+            h * 3600 + min * 60 + sec
+        }
+    };
+
+    Some(days_from_civil(y, m, d) * DAY as i64 + secs)
+}
+
+/// Returns the number of days in month `m` of year `y`
+fn days_in_month(y: i64, m: i64) -> i64 {
+    match m {
+        2 if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days between 1970-01-01 and the given date
+///
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(90, parse_duration("90s").unwrap());
+        assert_eq!(2 * HOUR + 30 * MINUTE, parse_duration("2h30m").unwrap());
+        assert_eq!(WEEK + DAY, parse_duration("1w1d").unwrap());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        for secs in [0, 90, 2 * HOUR + 30 * MINUTE, WEEK + DAY, 30 * DAY] {
+            assert_eq!(secs, parse_duration(&format_duration(secs)).unwrap());
+        }
+        assert_eq!("30d", format_duration(30 * DAY));
+        assert_eq!("1m30s", format_duration(90));
+    }
+
+    #[test]
+    fn test_parse_at() {
+        assert_eq!(1700000000, parse_at("1700000000").unwrap());
+        assert_eq!(0, parse_at("1970-01-01").unwrap());
+        assert_eq!(100, parse_at("1970-01-01 00:01:40").unwrap());
+        assert_eq!(1706704200, parse_at("2024-01-31T12:30Z").unwrap());
+        assert_eq!(951782400, parse_at("2000-02-29").unwrap());
+
+        let ago = parse_at("1h").unwrap();
+        assert!((now() - HOUR as i64 - ago).abs() <= 1);
+
+        assert!(parse_at("2024-13-01").is_err());
+        assert!(parse_at("2024-02-31").is_err());
+        assert!(parse_at("2023-02-29").is_err());
+        assert!(parse_at("1900-02-29").is_err());
+        assert!(parse_at("2023-04-31").is_err());
+        assert!(parse_at("2023-04-30").is_ok());
+        assert!(parse_at("2024-01-01 25:00").is_err());
+        assert!(parse_at("yesterday").is_err());
+    }
+}
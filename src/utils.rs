@@ -1,3 +1,5 @@
+use std::io::{BufRead, IsTerminal, Write};
+
 use anyhow::{Context, Result, ensure};
 use zeroize::Zeroizing;
 
@@ -26,3 +28,33 @@ pub(crate) fn prompt_password_confirm() -> Result<Zeroizing<String>> {
 
     Ok(password)
 }
+
+/// Strips the line break ending `value`, if any, keeping any other trailing
+/// whitespace
+pub(crate) fn strip_line_break(value: &str) -> &str {
+    value
+        .strip_suffix('\n')
+        .map(|v| v.strip_suffix('\r').unwrap_or(v))
+        .unwrap_or(value)
+}
+
+/// Asks the user a yes/no question on stdin, defaulting to no. Fails if stdin
+/// is not a terminal, since the answer would be read from piped input.
+pub(crate) fn confirm(prompt: &str) -> Result<bool> {
+    ensure!(
+        std::io::stdin().is_terminal(),
+        "cannot ask for confirmation, stdin is not a terminal: pass --yes to answer \"{prompt}\""
+    );
+
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{prompt} [y/N] ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("failed to read confirmation")?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}